@@ -2,7 +2,7 @@ use ark_bls12_377::Fr;
 use ark_ff::{FftField, PrimeField};
 use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
 use dist_primitives::{
-    dfft::{d_fft, fft_in_place_rearrange, FftMask},
+    dfft::{d_fft, fft_in_place_rearrange, FftMask, FftOrdering},
     utils::pack::transpose,
 };
 use mpc_net::ser_net::MpcSerNet;
@@ -41,14 +41,13 @@ pub async fn d_fft_test<F: FftField + PrimeField, Net: MpcNet>(
         .collect::<Vec<_>>();
 
     // using a dummy mask as this example will eventually be removed
-    let fft_mask =
-        FftMask::<F>::new(vec![F::zero(); mbyl], vec![F::zero(); mbyl]);
+    let fft_mask = FftMask::<F>::zero(mbyl);
 
     // Rearranging x
     let peval_share = d_fft(
         pcoeff_share,
         &fft_mask,
-        false,
+        FftOrdering::Natural,
         dom,
         pp,
         net,
@@ -82,12 +82,37 @@ async fn main() {
         .collect();
     let y_shares = transpose(y_shares);
 
-    let msm_masks = MsmMask::sample(&pp, rng);
+    // the dealer samples the masks, set MASK_SEED to derive them from a seed
+    // instead (see MsmMask::sample_for_party) and replay a run
+    let msm_masks = match std::env::var("MASK_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+    {
+        Some(seed) => {
+            log::info!("mask seed: {seed}");
+            (0..pp.n as u32)
+                .map(|id| MsmMask::sample_for_party(id, seed, &pp))
+                .collect()
+        }
+        None => MsmMask::sample(&pp, rng),
+    };
 
     network
-        .simulate_network_round((x_pub, y_pub, x_shares, y_shares, msm_masks, pp), |net, (x_pub, y_pub, x_shares, y_shares, msm_masks, pp)| async move {
-            let idx = net.party_id() as usize;
-            d_msm_test::<ark_bls12_377::G1Projective, _>(&x_pub, &y_pub, &x_shares[idx], &y_shares[idx], &msm_masks[idx], &pp, &net).await;
-        })
+        .simulate_network_round(
+            (x_pub, y_pub, x_shares, y_shares, msm_masks, pp),
+            |net, (x_pub, y_pub, x_shares, y_shares, msm_masks, pp)| async move {
+                let idx = net.party_id() as usize;
+                d_msm_test::<ark_bls12_377::G1Projective, _>(
+                    &x_pub,
+                    &y_pub,
+                    &x_shares[idx],
+                    &y_shares[idx],
+                    &msm_masks[idx],
+                    &pp,
+                    &net,
+                )
+                .await;
+            },
+        )
         .await;
 }
@@ -0,0 +1,256 @@
+// Distributed bit decomposition
+// Given packed shares of x1, x2, .., xl, all less than 2^n_bits,
+// output packed shares of their bits, without revealing them
+
+use crate::utils::deg_red::{deg_red, DegRedMask};
+use crate::utils::pack::transpose_rows;
+use crate::utils::PackedSharingParamsExt;
+use ark_ff::{BigInteger, PrimeField};
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::{MpcNetError, MultiplexedStreamID};
+use rand::Rng;
+use secret_sharing::pss::PackedSharingParams;
+
+/// Statistical security parameter, the opened x + r hides x up to 2^-STAT_SEC
+pub const STAT_SEC: usize = 40;
+
+/// Masks used in d_bit_decompose
+/// Note that this only contains one share of the mask
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "zeroize",
+    derive(zeroize::Zeroize, zeroize::ZeroizeOnDrop),
+    zeroize(bound = "")
+)]
+pub struct BitsMask<F: PrimeField> {
+    /// Share of r, a random integer of n_bits + STAT_SEC bits
+    pub r: F,
+    /// Shares of the n_bits low bits of r
+    pub r_bits: Vec<F>,
+    /// Reduces the products of r_bits with the opened bits
+    pub bits_mask: DegRedMask<F, F>,
+    /// One mask per carried bit, two products each
+    pub round_masks: Vec<DegRedMask<F, F>>,
+}
+
+impl<F: PrimeField> BitsMask<F> {
+    /// Samples a random BitsMask and returns the shares of n parties
+    pub fn sample(
+        n_bits: usize,
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Vec<Self> {
+        // r[k] is the value of the k-th slot, bits[i][k] its i-th bit
+        let mut r = vec![F::zero(); pp.l];
+        let mut bits = vec![vec![F::zero(); pp.l]; n_bits];
+        for (k, r) in r.iter_mut().enumerate() {
+            let r_bits = (0..n_bits + STAT_SEC)
+                .map(|_| F::from(rng.gen::<bool>()))
+                .collect::<Vec<_>>();
+            *r = r_bits
+                .iter()
+                .rev()
+                .fold(F::zero(), |acc, b| acc.double() + b);
+            for (bit, b) in bits.iter_mut().zip(r_bits) {
+                bit[k] = b;
+            }
+        }
+
+        let r_shares = pp.pack(r, rng);
+        let bit_shares = bits
+            .into_iter()
+            .map(|bit| pp.pack(bit, rng))
+            .collect::<Vec<_>>();
+        let bits_masks = DegRedMask::sample(pp, F::one(), n_bits, rng);
+        let round_masks = transpose_rows(
+            (1..n_bits).map(|_| DegRedMask::sample(pp, F::one(), 2, rng)),
+        );
+
+        bits_masks
+            .into_iter()
+            .zip(round_masks.into_iter().chain(std::iter::repeat(Vec::new())))
+            .enumerate()
+            .map(|(i, (bits_mask, round_masks))| Self {
+                r: r_shares[i],
+                r_bits: bit_shares.iter().map(|bit| bit[i]).collect(),
+                bits_mask,
+                round_masks,
+            })
+            .collect()
+    }
+
+    /// Number of bits this mask decomposes
+    pub fn n_bits(&self) -> usize {
+        self.r_bits.len()
+    }
+}
+
+/// Decomposes the secrets packed in `share` into bits
+/// Returns n_bits shares, the i-th one packs the i-th bit of every secret.
+/// The king opens c = x + r, where r has STAT_SEC more bits than x, and the
+/// parties subtract the shared bits of r from the public bits of c, one
+/// round of deg_red per bit for the borrow.
+/// The secrets must be less than 2^n_bits, otherwise the bits are those of
+/// x mod 2^n_bits and do not recompose to x, which a range check can detect.
+/// A mask must not be reused across calls.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, n_bits = n_bits)
+)]
+pub async fn d_bit_decompose<F: PrimeField, Net: MpcSerNet>(
+    share: F,
+    n_bits: usize,
+    mask: &BitsMask<F>,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Vec<F>, MpcNetError> {
+    pp.assert_matches(net)?;
+    if mask.n_bits() != n_bits {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "d_bit_decompose: mask is for {} bits, not {}",
+                mask.n_bits(),
+                n_bits
+            ),
+        });
+    }
+    if n_bits + STAT_SEC >= F::MODULUS_BIT_SIZE as usize {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "d_bit_decompose: {} bits do not fit the field",
+                n_bits
+            ),
+        });
+    }
+    if n_bits == 0 {
+        return Ok(Vec::new());
+    }
+    // a king holding no share gets no bits, but still opens c
+    let idx = net.party_index();
+
+    // open c = x + r, no wraparound since x + r < 2^(n_bits + STAT_SEC + 1)
+    let c: Vec<F> = net
+        .king_broadcast_round(
+            &(share + mask.r),
+            |rs| Ok(pp.unpack_missing_shares(&rs.shares, &rs.parties)),
+            sid,
+            pp.t,
+        )
+        .await?;
+    if c.len() != pp.l {
+        return Err(MpcNetError::Protocol {
+            err: format!("d_bit_decompose: king sent {} values", c.len()),
+            party: net.king_id(),
+        });
+    }
+
+    // this party's share of the public packing of f(c_i), for every bit i
+    let c_bits = c
+        .iter()
+        .map(|c| c.into_bigint().to_bits_le())
+        .collect::<Vec<_>>();
+    let public = |i: usize, f: fn(F) -> F| {
+        let values = c_bits
+            .iter()
+            .map(|bits| f(F::from(bits[i] as u64)))
+            .collect::<Vec<_>>();
+        idx.map_or(F::zero(), |idx| pp.pack_from_public(values)[idx])
+    };
+
+    // y_i = c_i r_i
+    let products = (0..n_bits)
+        .map(|i| public(i, |c| c) * mask.r_bits[i])
+        .collect();
+    let y = deg_red(products, &mask.bits_mask, pp, net, sid).await?;
+
+    // x_i = c_i xor r_i xor b_i = c_i + e_i + b_i f_i, e_i = r_i - 2 y_i
+    // b_(i+1) = (1 - c_i)(r_i + b_i - r_i b_i) + c_i r_i b_i
+    //          = r_i - y_i + b_i w_i
+    let r = &mask.r_bits;
+    let mut bits = Vec::with_capacity(n_bits);
+    bits.push(public(0, |c| c) + r[0] - y[0].double());
+    let mut borrow = r[0] - y[0];
+    for i in 1..n_bits {
+        let e = r[i] - y[i].double();
+        let f = public(i, |c| F::one() - c.double()) - e.double();
+        let w = public(i, |c| F::one() - c) + y[i].double() - r[i];
+        let reduced = deg_red(
+            vec![borrow * f, borrow * w],
+            &mask.round_masks[i - 1],
+            pp,
+            net,
+            sid,
+        )
+        .await?;
+        bits.push(public(i, |c| c) + e + reduced[0]);
+        borrow = r[i] - y[i] + reduced[1];
+    }
+
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_ff::{Field, One, Zero};
+    use mpc_net::{LocalTestNet, MpcNet, MultiplexedStreamID};
+    use rand::Rng;
+    use secret_sharing::pss::PackedSharingParams;
+
+    use crate::dbits::{d_bit_decompose, BitsMask};
+    use crate::utils::pack::transpose;
+
+    const L: usize = 2;
+    const N_BITS: usize = 16;
+
+    #[tokio::test]
+    async fn d_bit_decompose_works() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let mut values = (0..L)
+            .map(|_| rng.gen_range(0..1u64 << N_BITS))
+            .collect::<Vec<_>>();
+        values[0] = (1 << N_BITS) - 1;
+        let secrets = values.iter().map(|v| F::from(*v)).collect();
+        let shares = pp.pack(secrets, rng);
+        let masks = BitsMask::sample(N_BITS, &pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (shares, masks, pp),
+                |net, (shares, masks, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    d_bit_decompose(
+                        shares[idx],
+                        N_BITS,
+                        &masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        // bits[i] packs the i-th bit of every value
+        let bits = transpose(result)
+            .into_iter()
+            .map(|bit_shares| pp.unpack(bit_shares))
+            .collect::<Vec<_>>();
+        for (k, value) in values.iter().enumerate() {
+            let mut recomposed = F::zero();
+            for i in (0..N_BITS).rev() {
+                let bit = bits[i][k];
+                assert!(bit.is_zero() || bit.is_one());
+                recomposed = recomposed.double() + bit;
+            }
+            assert_eq!(recomposed, F::from(*value));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// Distributed witness commitment
+// The king opens the packed assignment and publishes its Merkle root, so a
+// client can check that the proof was computed on the witness it submitted
+
+use crate::utils::pack::columns;
+use ark_ff::FftField;
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::{MpcNetError, MultiplexedStreamID};
+use secret_sharing::pss::PackedSharingParams;
+use sha2::{Digest, Sha256};
+
+const LEAF: u8 = 0;
+const NODE: u8 = 1;
+const ROOT: u8 = 2;
+
+/// Merkle root over the SHA-256 hashes of values, bound to their number so
+/// that appending zeros changes it. An odd node is carried up a level.
+/// This is what a client recomputes from its submission.
+pub fn merkle_root<F: FftField>(values: &[F]) -> [u8; 32] {
+    let mut level = values
+        .iter()
+        .map(|value| {
+            let mut bytes = Vec::new();
+            value
+                .serialize_compressed(&mut bytes)
+                .expect("serializing into a vec cannot fail");
+            hash(LEAF, &[&bytes])
+        })
+        .collect::<Vec<_>>();
+    if level.is_empty() {
+        level.push(hash(LEAF, &[]));
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash(NODE, &[left, right]),
+                [odd] => *odd,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    let len = (values.len() as u64).to_le_bytes();
+    hash(ROOT, &[&len, &level[0]])
+}
+
+fn hash(prefix: u8, parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([prefix]);
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Commits to the first len values of the assignment packed in
+/// assignment_shares, l values per share (e.g WitnessShares::a), and returns
+/// the Merkle root (see merkle_root) to every party.
+/// The king reconstructs the assignment to hash it, so it learns the witness.
+pub async fn d_commit_witness<F: FftField, Net: MpcSerNet>(
+    assignment_shares: &Vec<F>,
+    len: usize,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<[u8; 32], MpcNetError> {
+    if len > assignment_shares.len() * pp.l {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "d_commit_witness: {} values do not fit in {} shares",
+                len,
+                assignment_shares.len()
+            ),
+        });
+    }
+
+    net.king_broadcast_round(
+        assignment_shares,
+        |rs| {
+            let mut assignment = columns(&rs.shares)
+                .flat_map(|column| {
+                    pp.unpack_missing_shares(&column, &rs.parties)
+                })
+                .collect::<Vec<_>>();
+            assignment.truncate(len);
+            Ok(merkle_root(&assignment))
+        },
+        sid,
+        pp.t,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_std::UniformRand;
+    use mpc_net::{LocalTestNet, MpcNet, MultiplexedStreamID};
+    use secret_sharing::pss::PackedSharingParams;
+
+    use crate::dcommit::{d_commit_witness, merkle_root};
+    use crate::utils::pack::{pack_vec, transpose};
+
+    const L: usize = 2;
+
+    #[tokio::test]
+    async fn d_commit_witness_matches_merkle_root() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        // an odd number of values, padded with a zero before packing
+        let assignment = (0..5).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let mut padded = assignment.clone();
+        padded.push(F::from(0u32));
+        let shares = transpose(pack_vec(&padded, &pp));
+
+        let result = network
+            .simulate_network_round(
+                (shares, pp),
+                |net, (shares, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    d_commit_witness(
+                        &shares[idx],
+                        5,
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        let root = merkle_root(&assignment);
+        assert!(result.iter().all(|r| *r == root));
+
+        let mut tampered = assignment.clone();
+        tampered[3] += F::from(1u32);
+        assert_ne!(merkle_root(&tampered), root);
+        assert_ne!(merkle_root(&assignment[..4]), root);
+    }
+
+    #[test]
+    fn merkle_root_binds_the_length() {
+        let rng = &mut ark_std::test_rng();
+        let values = (0..3).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let mut padded = values.clone();
+        padded.push(F::from(0u32));
+
+        assert_ne!(merkle_root(&values), merkle_root(&padded));
+        assert_ne!(merkle_root::<F>(&[]), merkle_root(&[F::from(0u32)]));
+    }
+}
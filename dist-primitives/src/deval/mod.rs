@@ -0,0 +1,196 @@
+// Distributed evaluation of a shared polynomial at a public point
+// Given packed shares of the coefficients, output the evaluation to everyone
+
+use crate::utils::deg_red::{deg_red, DegRedMask};
+use crate::utils::king::king_unpack_fresh;
+use crate::utils::PackedSharingParamsExt;
+use ark_ff::FftField;
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::{MpcNetError, MultiplexedStreamID};
+use rand::Rng;
+use secret_sharing::pss::PackedSharingParams;
+
+/// Masks used in d_eval
+/// Note that this only contains one share of the mask
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "zeroize",
+    derive(zeroize::Zeroize, zeroize::ZeroizeOnDrop),
+    zeroize(bound = "")
+)]
+pub struct EvalMask<F: FftField> {
+    /// Reduces the degree of the partial sums times the powers of point
+    pub degred_mask: DegRedMask<F, F>,
+    /// Packs l random values summing to zero, so the king only learns the
+    /// sum of the partial sums
+    pub sum_mask: F,
+}
+
+impl<F: FftField> EvalMask<F> {
+    /// Samples masks for d_eval and returns the shares of n parties
+    pub fn sample(
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Vec<Self> {
+        let mut mask_values =
+            (0..pp.l - 1).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        mask_values.push(-mask_values.iter().sum::<F>());
+        let sum_masks = pp.pack(mask_values, rng);
+
+        DegRedMask::sample(pp, F::one(), 1, rng)
+            .into_iter()
+            .zip(sum_masks)
+            .map(|(degred_mask, sum_mask)| Self {
+                degred_mask,
+                sum_mask,
+            })
+            .collect()
+    }
+}
+
+/// Evaluates the polynomial whose coefficients are packed in pcoeff_share at point
+/// pcoeff_share[j] packs the coefficients j*l..(j+1)*l, as in pack_vec.
+/// Since point is public the parties fold the shares locally into l partial
+/// sums. Weighting them by the powers of point is a product, whose degree
+/// deg_red brings back to t+l-1 before the king reconstructs them, hidden
+/// by mask.sum_mask, and publishes their sum. Takes two rounds.
+/// A mask must not be reused across calls.
+pub async fn d_eval<F: FftField, Net: MpcSerNet>(
+    pcoeff_share: &[F],
+    point: F,
+    mask: &EvalMask<F>,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<F, MpcNetError> {
+    pp.assert_matches(net)?;
+
+    // the k-th packed value is sum_j c_{jl+k} point^{jl}
+    let point_l = point.pow([pp.l as u64]);
+    let folded = pcoeff_share
+        .iter()
+        .rev()
+        .fold(F::zero(), |acc, share| acc * point_l + share);
+
+    // multiply the k-th packed value by point^k
+    let mut powers = vec![F::one(); pp.l];
+    for k in 1..pp.l {
+        powers[k] = powers[k - 1] * point;
+    }
+    // a king holding no share sends nothing
+    let powers_share = net
+        .party_index()
+        .map_or(F::zero(), |idx| pp.pack_from_public(powers)[idx]);
+    let weighted =
+        deg_red(vec![folded * powers_share], &mask.degred_mask, pp, net, sid)
+            .await?;
+    let share = weighted[0] + mask.sum_mask;
+
+    net.king_broadcast_round(
+        &share,
+        |rs| {
+            let eval = king_unpack_fresh(&rs, pp, net.king_id(), "d_eval")?
+                .iter()
+                .sum();
+            Ok(eval)
+        },
+        sid,
+        pp.t,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_poly::DenseUVPolynomial;
+    use ark_poly::{polynomial::univariate::DensePolynomial, Polynomial};
+    use ark_std::UniformRand;
+    use mpc_net::{LocalTestNet, MpcNet, MultiplexedStreamID};
+    use secret_sharing::pss::PackedSharingParams;
+
+    use crate::deval::{d_eval, EvalMask};
+    use crate::utils::pack::transpose;
+
+    const L: usize = 2;
+    const M: usize = 1 << 6;
+
+    #[tokio::test]
+    async fn d_eval_works() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let coeffs = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let point = F::rand(rng);
+        let expected =
+            DensePolynomial::from_coefficients_slice(&coeffs).evaluate(&point);
+
+        let coeff_shares = transpose(
+            coeffs.chunks(L).map(|c| pp.pack(c.to_vec(), rng)).collect(),
+        );
+        let masks = EvalMask::sample(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (coeff_shares, masks, point, pp),
+                |net, (coeff_shares, masks, point, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    d_eval(
+                        &coeff_shares[idx],
+                        point,
+                        &masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        assert!(result.iter().all(|eval| *eval == expected));
+    }
+
+    #[tokio::test]
+    async fn d_eval_with_a_king_holding_no_share() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet_with_king(pp.n)
+            .await
+            .unwrap();
+
+        let coeffs = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let point = F::rand(rng);
+        let expected =
+            DensePolynomial::from_coefficients_slice(&coeffs).evaluate(&point);
+
+        let coeff_shares = transpose(
+            coeffs.chunks(L).map(|c| pp.pack(c.to_vec(), rng)).collect(),
+        );
+        let masks = EvalMask::sample(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (coeff_shares, masks, point, pp),
+                |net, (coeff_shares, masks, point, pp)| async move {
+                    // the king sends nothing, any share and mask do
+                    let idx = net.party_index().unwrap_or(0);
+                    d_eval(
+                        &coeff_shares[idx],
+                        point,
+                        &masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        assert!(result.iter().all(|eval| *eval == expected));
+    }
+}
@@ -1,34 +1,69 @@
-use crate::utils::pack::{pack_vec, transpose};
+use crate::utils::pack::transpose_rows;
+use crate::utils::{
+    check_mesh_size, dealer_rng, share_index, PackedSharingParamsExt,
+};
 use ark_ff::{FftField, PrimeField};
 use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
 use ark_std::log2;
-use mpc_net::ser_net::MpcSerNet;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use mpc_net::ser_net::{MpcSerNet, ReceivedShares};
 use mpc_net::{MpcNetError, MultiplexedStreamID};
-use secret_sharing::pss::PackedSharingParams;
+use secret_sharing::pss::{PackedSharingParams, UnpackError};
 use std::mem;
 
 #[cfg(test)]
 pub mod tests;
 
+/// Order of the m values packed in the shares a transform takes or outputs.
+/// d_fft and d_ifft take their input BitReversed, see pack_fft_input, and
+/// output either order: BitReversed to feed another transform, Natural
+/// otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FftOrdering {
+    /// In order, l consecutive values per share, as pack_vec packs them
+    Natural,
+    /// Bit-reversed (see fft_in_place_rearrange), share i holding values
+    /// i, i + m/l, i + 2m/l, .. of the bit-reversed vector
+    BitReversed,
+}
+
 /// Masks used in d_fft/d_ifft
 /// Note that this only contains one share of the mask
 #[derive(Clone)]
+#[cfg_attr(
+    feature = "zeroize",
+    derive(zeroize::Zeroize, zeroize::ZeroizeOnDrop),
+    zeroize(bound = "")
+)]
 pub struct FftMask<F: FftField + PrimeField> {
     pub in_mask: Vec<F>,
     pub out_mask: Vec<F>,
+    /// Seeds the randomness the king repacks the output with, sampled by
+    /// the dealer for every party like the masks
+    pub repack_seed: [u8; 32],
 }
 
 impl<F: FftField + PrimeField> FftMask<F> {
-    pub fn new(in_mask: Vec<F>, out_mask: Vec<F>) -> Self {
-        Self { in_mask, out_mask }
+    pub fn new(
+        in_mask: Vec<F>,
+        out_mask: Vec<F>,
+        repack_seed: [u8; 32],
+    ) -> Self {
+        Self {
+            in_mask,
+            out_mask,
+            repack_seed,
+        }
     }
 
     /// Samples a random FftMask and returns the shares of n parties
-    /// Depending on g, gen, and rearrange, this can be used for various
+    /// Depending on g, gen, and output, this can be used for various
     /// configurations of FFT/IFFT.
+    /// output must be the ordering the transform outputs.
     /// m denotes size of the domain
     pub fn sample(
-        rearrange: bool,
+        output: FftOrdering,
         g: F,
         gen: F,
         m: usize,
@@ -40,8 +75,8 @@ impl<F: FftField + PrimeField> FftMask<F> {
             mask_values.push(F::rand(rng));
         }
 
-        let in_mask_values = mask_values.clone();
-        let in_mask_shares = transpose(pack_vec(&in_mask_values, pp));
+        let in_mask_shares =
+            pack_output(mask_values.clone(), FftOrdering::Natural, pp, rng);
 
         fft2_in_place(&mut mask_values, pp, gen); // s1 constrains final output now
 
@@ -49,53 +84,93 @@ impl<F: FftField + PrimeField> FftMask<F> {
             Radix2EvaluationDomain::<F>::distribute_powers(&mut mask_values, g);
         }
 
+        Self::from_values(in_mask_shares, mask_values, output, pp, rng)
+    }
+
+    /// Same as sample, but returns the share of party_id only, see
+    /// MsmMask::sample_for_party. Insecure, see [insecure masks](crate#insecure-masks).
+    pub fn sample_for_party(
+        party_id: u32,
+        shared_seed: u64,
+        output: FftOrdering,
+        g: F,
+        gen: F,
+        m: usize,
+        pp: &PackedSharingParams<F>,
+    ) -> Self {
+        let rng = &mut dealer_rng(shared_seed);
+        Self::sample(output, g, gen, m, pp, rng).swap_remove(party_id as usize)
+    }
+
+    /// Samples a random FftMask for d_ifft_then_fft over dom
+    /// and returns the shares of n parties
+    pub fn sample_ifft_then_fft<D: EvaluationDomain<F>>(
+        output: FftOrdering,
+        g: F,
+        dom: &D,
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl rand::Rng,
+    ) -> Vec<Self> {
+        let mut mask_values = Vec::new();
+        for _ in 0..dom.size() {
+            mask_values.push(F::rand(rng));
+        }
+
+        let in_mask_shares =
+            pack_output(mask_values.clone(), FftOrdering::Natural, pp, rng);
+
+        fft2_in_place(&mut mask_values, pp, dom.group_gen_inv());
+        if g != F::one() {
+            Radix2EvaluationDomain::<F>::distribute_powers(&mut mask_values, g);
+        }
+        let mask_values = local_fft(mask_values, F::one(), pp, dom.group_gen());
+
+        Self::from_values(in_mask_shares, mask_values, output, pp, rng)
+    }
+
+    /// Pairs up the in_mask shares with shares of the negated mask_values,
+    /// the output the king computes when the input is in_mask alone
+    fn from_values(
+        in_mask_shares: Vec<Vec<F>>,
+        mut mask_values: Vec<F>,
+        output: FftOrdering,
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl rand::Rng,
+    ) -> Vec<Self> {
         // negate the mask_values (so that it just needs to be added to output shares)
         mask_values.iter_mut().for_each(|x| *x = -*x);
-
-        // Optionally rearrange to get ready for next FFT/IFFT
-        // Saves one round of communication by doing it at the King in the previous FFT/IFFT
-        let out_mask_shares = if rearrange {
-            fft_in_place_rearrange(&mut mask_values);
-            let mut out_shares: Vec<Vec<F>> = Vec::new();
-            for i in 0..mask_values.len() / pp.l {
-                out_shares.push(
-                    pp.pack(
-                        mask_values
-                            .iter()
-                            .skip(i)
-                            .step_by(mask_values.len() / pp.l)
-                            .cloned()
-                            .collect::<Vec<_>>(),
-                        rng,
-                    ),
-                );
-            }
-            transpose(out_shares)
-        } else {
-            transpose(pack_vec(&mask_values, pp))
-        };
+        let out_mask_shares = pack_output(mask_values, output, pp, rng);
 
         in_mask_shares
             .into_iter()
-            .zip(out_mask_shares.iter())
+            .zip(out_mask_shares)
             .map(|(in_mask_share, out_mask_share)| {
-                Self::new(in_mask_share, out_mask_share.clone())
+                Self::new(in_mask_share, out_mask_share, rng.gen())
             })
             .collect()
     }
 
-    /// Returns a default value for FftMask. Not secure.
-    /// Only to be used for debugging purposes.
+    /// Returns an all-zero FftMask of mbyl shares.
+    /// Insecure, see [insecure masks](crate#insecure-masks).
     pub fn zero(mbyl: usize) -> Self {
+        #[cfg(debug_assertions)]
+        log::warn!("using an insecure zero FftMask");
         Self {
             in_mask: vec![F::zero(); mbyl],
             out_mask: vec![F::zero(); mbyl],
+            repack_seed: [0; 32],
         }
     }
 }
 
-/// Takes as input packed shares of evaluations a polynomial over dom and outputs shares of the FFT of the polynomial
-/// rearrange: whether or not to rearrange output shares in preparation for another fourier transform
+/// Takes as input packed shares of the coefficients of a polynomial, BitReversed,
+/// and outputs shares of its evaluations over dom
+/// output: the ordering of the output, BitReversed to run another transform on it
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = pcoeff_share.len())
+)]
 pub async fn d_fft<
     F: FftField + PrimeField,
     D: EvaluationDomain<F>,
@@ -103,37 +178,86 @@ pub async fn d_fft<
 >(
     mut pcoeff_share: Vec<F>,
     fft_mask: &FftMask<F>,
-    rearrange: bool,
+    output: FftOrdering,
     dom: &D,
     pp: &PackedSharingParams<F>,
     net: &Net,
     sid: MultiplexedStreamID,
 ) -> Result<Vec<F>, MpcNetError> {
-    debug_assert_eq!(
-        pcoeff_share.len() * pp.l,
-        dom.size(),
-        "Mismatch of size in FFT, {}, {}.",
-        pcoeff_share.len() * pp.l,
-        dom.size()
-    );
+    pp.assert_matches(net)?;
+    check_share_size("FFT", &pcoeff_share, dom.size(), pp)?;
 
     // Parties apply FFT1 locally
     fft1_in_place(&mut pcoeff_share, pp, dom.group_gen());
     // King applies FFT2 and parties receive shares of evals
-    fft2_with_rearrange(
-        pcoeff_share,
-        fft_mask,
-        rearrange,
-        F::one(),
-        pp,
-        dom.group_gen(),
-        net,
-        sid,
-    )
+    fft2_with_rearrange(pcoeff_share, fft_mask, output, pp, net, sid, |s1| {
+        fft2_in_place(s1, pp, dom.group_gen())
+    })
     .await
 }
 
+/// Same as d_fft, but without the king: every party broadcasts its masked
+/// shares after fft1, finishes the transform of the masked values itself and
+/// packs them without randomness, out_mask rerandomizes its share.
+/// Only for small networks, see MAX_MESH_PARTIES, and net must connect
+/// every pair of parties.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = pcoeff_share.len())
+)]
+pub async fn d_fft_meshed<
+    F: FftField + PrimeField,
+    D: EvaluationDomain<F>,
+    Net: MpcSerNet,
+>(
+    mut pcoeff_share: Vec<F>,
+    fft_mask: &FftMask<F>,
+    output: FftOrdering,
+    dom: &D,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Vec<F>, MpcNetError> {
+    pp.assert_matches(net)?;
+    check_mesh_size(net)?;
+    check_share_size("FFT", &pcoeff_share, dom.size(), pp)?;
+
+    // Parties apply FFT1 locally
+    fft1_in_place(&mut pcoeff_share, pp, dom.group_gen());
+    let out = pcoeff_share
+        .iter()
+        .zip(fft_mask.in_mask.iter())
+        .map(|(x, m)| *x + *m)
+        .collect::<Vec<_>>();
+
+    // Every party applies FFT2 to the masked values
+    let rs = net.broadcast_serialized(&out, sid).await?;
+    let mut s1 = pp
+        .unpack_missing_shares_many(&rs.shares, &rs.parties, fft_degree(pp))
+        .map_err(|err| MpcNetError::Protocol {
+            err: format!("d_fft_meshed: {err}"),
+            party: net.party_id(),
+        })?
+        .concat();
+    fft2_in_place(&mut s1, pp, dom.group_gen());
+
+    let out_shares = pack_output_public(s1, output, pp);
+    Ok(out_shares[share_index(net)?]
+        .iter()
+        .zip(fft_mask.out_mask.iter())
+        .map(|(x, m)| *x + *m)
+        .collect())
+}
+
+/// Inverse of d_fft: takes packed shares of the evaluations over dom, BitReversed,
+/// and outputs shares of the coefficients in the given ordering,
 /// additionally distribute powers of g over the resulting coefficients
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = peval_share.len())
+)]
 pub async fn d_ifft<
     F: FftField + PrimeField,
     D: EvaluationDomain<F>,
@@ -141,39 +265,324 @@ pub async fn d_ifft<
 >(
     mut peval_share: Vec<F>,
     fft_mask: &FftMask<F>,
-    rearrange: bool,
+    output: FftOrdering,
     dom: &D,
     g: F,
     pp: &PackedSharingParams<F>,
     net: &Net,
     sid: MultiplexedStreamID,
 ) -> Result<Vec<F>, MpcNetError> {
-    debug_assert_eq!(
-        peval_share.len() * pp.l,
-        dom.size(),
-        "Mismatch of size in IFFT, {}, {}.",
-        peval_share.len() * pp.l,
-        dom.size()
-    );
+    pp.assert_matches(net)?;
+    check_share_size("IFFT", &peval_share, dom.size(), pp)?;
 
     peval_share.iter_mut().for_each(|x| *x *= dom.size_inv());
 
     // Parties apply FFT1 locally
     fft1_in_place(&mut peval_share, pp, dom.group_gen_inv());
     // King applies FFT2 and parties receive shares of evals
-    fft2_with_rearrange(
-        peval_share,
-        fft_mask,
-        rearrange,
-        g,
-        pp,
-        dom.group_gen_inv(),
-        net,
-        sid,
-    )
+    fft2_with_rearrange(peval_share, fft_mask, output, pp, net, sid, |s1| {
+        fft2_in_place(s1, pp, dom.group_gen_inv());
+        if g != F::one() {
+            Radix2EvaluationDomain::<F>::distribute_powers(s1, g);
+        }
+    })
     .await
 }
 
+/// Runs d_ifft over dom, distributing powers of g, followed by d_fft over dom
+/// in a single round: the king evaluates the coefficients it reconstructed
+/// itself instead of resharing them for a separate d_fft.
+/// fft_mask must come from FftMask::sample_ifft_then_fft.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = peval_share.len())
+)]
+pub async fn d_ifft_then_fft<
+    F: FftField + PrimeField,
+    D: EvaluationDomain<F>,
+    Net: MpcSerNet,
+>(
+    mut peval_share: Vec<F>,
+    fft_mask: &FftMask<F>,
+    output: FftOrdering,
+    dom: &D,
+    g: F,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Vec<F>, MpcNetError> {
+    pp.assert_matches(net)?;
+    check_share_size("IFFT", &peval_share, dom.size(), pp)?;
+
+    peval_share.iter_mut().for_each(|x| *x *= dom.size_inv());
+
+    // Parties apply FFT1 of the IFFT locally
+    fft1_in_place(&mut peval_share, pp, dom.group_gen_inv());
+    // King finishes the IFFT and runs the whole FFT on the coefficients
+    fft2_with_rearrange(peval_share, fft_mask, output, pp, net, sid, |s1| {
+        ifft2_then_fft(s1, dom, g, pp)
+    })
+    .await
+}
+
+/// d_ifft_then_fft of K vectors over the same dom, the king receiving all of
+/// them at once (see MpcSerNet::client_send_or_king_receive_multi) and
+/// answering each on its own sid.
+/// fft_masks must come from FftMask::sample_ifft_then_fft.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sids = ?sids)
+)]
+pub async fn d_ifft_then_fft_multi<
+    F: FftField + PrimeField,
+    D: EvaluationDomain<F>,
+    Net: MpcSerNet,
+    const K: usize,
+>(
+    peval_shares: [Vec<F>; K],
+    fft_masks: [&FftMask<F>; K],
+    output: FftOrdering,
+    dom: &D,
+    g: F,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sids: [MultiplexedStreamID; K],
+) -> Result<[Vec<F>; K], MpcNetError> {
+    pp.assert_matches(net)?;
+    let mbyl = dom.size() / pp.l;
+    let outs = peval_shares
+        .into_iter()
+        .zip(fft_masks)
+        .map(|(mut peval_share, fft_mask)| {
+            check_share_size("IFFT", &peval_share, dom.size(), pp)?;
+            peval_share.iter_mut().for_each(|x| *x *= dom.size_inv());
+            fft1_in_place(&mut peval_share, pp, dom.group_gen_inv());
+            Ok(add_mask(peval_share, &fft_mask.in_mask))
+        })
+        .collect::<Result<Vec<_>, MpcNetError>>()?;
+
+    let payloads = sids.iter().copied().zip(&outs).collect::<Vec<_>>();
+    let received = net
+        .client_send_or_king_receive_multi(&payloads, pp.t)
+        .await?;
+    drop(payloads);
+    drop(outs);
+
+    // as in try_king_round, the king answers None to every party on a
+    // stream id whose shares do not reconstruct, aborting the round there
+    let mut king_err = None;
+    let answers = match received {
+        Some(received) => received
+            .into_iter()
+            .zip(fft_masks)
+            .map(|(rs, fft_mask)| {
+                let answer = king_fft2(rs, mbyl, fft_mask, output, pp, |s1| {
+                    ifft2_then_fft(s1, dom, g, pp)
+                });
+                Some(match answer {
+                    Ok(answer) => answer.into_iter().map(Some).collect(),
+                    Err(err) => {
+                        king_err.get_or_insert(MpcNetError::Protocol {
+                            err: format!("d_ifft_then_fft_multi: {err}"),
+                            party: net.king_id(),
+                        });
+                        net.party_ids().map(|_| None).collect()
+                    }
+                })
+            })
+            .collect(),
+        None => vec![None; K],
+    };
+
+    // parties wait for the answers in the order the king sends them, and
+    // receive all of them even after an abort
+    let mut out_shares = Vec::with_capacity(K);
+    let mut aborted = false;
+    for ((answer, fft_mask), sid) in
+        answers.into_iter().zip(fft_masks).zip(sids)
+    {
+        let out_share = match net
+            .client_receive_or_king_send_serialized(answer, sid)
+            .await?
+        {
+            Some(Some(out_share)) => out_share,
+            Some(None) => {
+                aborted = true;
+                continue;
+            }
+            None => Vec::new(),
+        };
+        out_shares.push(add_mask(out_share, &fft_mask.out_mask));
+    }
+    if let Some(err) = king_err {
+        return Err(err);
+    }
+    if aborted {
+        return Err(MpcNetError::Protocol {
+            err: "The king aborted d_ifft_then_fft_multi".to_string(),
+            party: net.king_id(),
+        });
+    }
+
+    Ok(out_shares.try_into().expect("one answer per stream id"))
+}
+
+/// What the king of d_ifft_then_fft applies to the reconstructed values: the
+/// rest of the IFFT, powers of g and the whole FFT
+fn ifft2_then_fft<F: FftField + PrimeField, D: EvaluationDomain<F>>(
+    s1: &mut Vec<F>,
+    dom: &D,
+    g: F,
+    pp: &PackedSharingParams<F>,
+) {
+    fft2_in_place(s1, pp, dom.group_gen_inv());
+    if g != F::one() {
+        Radix2EvaluationDomain::<F>::distribute_powers(s1, g);
+    }
+    *s1 = local_fft(mem::take(s1), F::one(), pp, dom.group_gen());
+}
+
+/// Runs d_fft in the clear, i.e without packing or network
+/// Follows the same fft1/fft2 split so the local math can be tested in isolation
+/// Takes coefficients in natural order and outputs the evaluations over dom
+pub fn local_d_fft<F: FftField + PrimeField, D: EvaluationDomain<F>>(
+    coeffs: Vec<F>,
+    dom: &D,
+    pp: &PackedSharingParams<F>,
+) -> Vec<F> {
+    debug_assert_eq!(coeffs.len(), dom.size(), "Mismatch of size in FFT");
+    local_fft(coeffs, F::one(), pp, dom.group_gen())
+}
+
+/// Runs d_ifft in the clear, additionally distributing powers of g
+pub fn local_d_ifft<F: FftField + PrimeField, D: EvaluationDomain<F>>(
+    mut evals: Vec<F>,
+    dom: &D,
+    g: F,
+    pp: &PackedSharingParams<F>,
+) -> Vec<F> {
+    debug_assert_eq!(evals.len(), dom.size(), "Mismatch of size in IFFT");
+    evals.iter_mut().for_each(|x| *x *= dom.size_inv());
+    local_fft(evals, g, pp, dom.group_gen_inv())
+}
+
+/// Evaluations of the vanishing polynomial of base_dom over the coset offset * dom
+/// Lets the quotient be computed by an element-wise multiply with the inverses
+/// Only dom.size() / base_dom.size() of the values are distinct, they repeat cyclically
+pub fn vanishing_evals_over<F: FftField, D: EvaluationDomain<F>>(
+    dom: &D,
+    base_dom: &D,
+    offset: F,
+) -> Vec<F> {
+    debug_assert_eq!(
+        dom.size() % base_dom.size(),
+        0,
+        "base_dom must be a subgroup of dom"
+    );
+    let m = base_dom.size() as u64;
+    let ratio = dom.size() / base_dom.size();
+
+    // (offset * w^i)^m - 1 = offset^m * (w^m)^i - 1
+    let offset_m = offset.pow([m]);
+    let gen_m = dom.group_gen().pow([m]);
+    let mut distinct = Vec::with_capacity(ratio);
+    let mut cur = offset_m;
+    for _ in 0..ratio {
+        distinct.push(cur - F::one());
+        cur *= gen_m;
+    }
+
+    distinct.into_iter().cycle().take(dom.size()).collect()
+}
+
+/// Debugging helper: reconstructs the output of a d_fft/d_ifft at the king
+/// and compares it against `expected`, logging the first divergent index.
+/// output: the ordering the transform was called with
+/// Returns the divergent index at the king and None at the clients.
+/// Reveals the output to the king, never use it outside of tests.
+#[cfg(feature = "debug-fft")]
+pub async fn d_check_against<F: FftField + PrimeField, Net: MpcSerNet>(
+    stage: &str,
+    share: &[F],
+    expected: &[F],
+    output: FftOrdering,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Option<usize>, MpcNetError> {
+    check_share_size(stage, share, expected.len(), pp)?;
+
+    let received_shares = net
+        .client_send_or_king_receive_serialized(&share.to_vec(), sid, pp.t)
+        .await?;
+
+    let Some(rs) = received_shares else {
+        return Ok(None);
+    };
+
+    let unpacked = pp
+        .unpack_missing_shares_many(&rs.shares, &rs.parties, fft_degree(pp))
+        .map_err(|err| MpcNetError::Protocol {
+            err: format!("d_check_against: {err}"),
+            party: net.king_id(),
+        })?;
+    let mbyl = share.len();
+    let mut actual = vec![F::zero(); expected.len()];
+    for (i, secrets) in unpacked.into_iter().enumerate() {
+        for (j, secret) in secrets.into_iter().take(pp.l).enumerate() {
+            // see fft2_with_rearrange for how the output is packed
+            if output == FftOrdering::BitReversed {
+                actual[j * mbyl + i] = secret;
+            } else {
+                actual[i * pp.l + j] = secret;
+            }
+        }
+    }
+    if output == FftOrdering::BitReversed {
+        fft_in_place_rearrange(&mut actual);
+    }
+
+    let divergent = actual.iter().zip(expected).position(|(a, e)| a != e);
+    match divergent {
+        Some(idx) => log::warn!("{stage} diverges at index {idx}"),
+        None => log::debug!("{stage} matches"),
+    }
+
+    Ok(divergent)
+}
+
+fn local_fft<F: FftField + PrimeField>(
+    mut x: Vec<F>,
+    g: F,
+    pp: &PackedSharingParams<F>,
+    gen: F,
+) -> Vec<F> {
+    let mbyl = x.len() / pp.l;
+    fft_in_place_rearrange(&mut x);
+
+    // After rearranging, the j-th secret of the i-th packed share is x[j * mbyl + i]
+    // So each party would apply fft1 to every chunk of size m/l at once
+    let mut s1 = vec![F::zero(); x.len()];
+    for (j, chunk) in x.chunks(mbyl).enumerate() {
+        let mut chunk = chunk.to_vec();
+        fft1_in_place(&mut chunk, pp, gen);
+        for (i, y) in chunk.into_iter().enumerate() {
+            s1[i * pp.l + j] = y;
+        }
+    }
+
+    // King's part
+    fft2_in_place(&mut s1, pp, gen);
+
+    if g != F::one() {
+        Radix2EvaluationDomain::<F>::distribute_powers(&mut s1, g);
+    }
+
+    s1
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 fn fft1_in_place<F: FftField + PrimeField>(
     px: &mut Vec<F>,
@@ -237,88 +646,178 @@ fn fft2_in_place<F: FftField + PrimeField>(
 }
 
 /// Send shares after fft1 to king who finishes the protocol and returns packed shares
+/// king_transform is applied by the king to the reconstructed values, e.g fft2
+/// The king holds the n x m/l received shares, the m values and the n x m/l
+/// shares of the answer at once, see utils::pack::columns.
 async fn fft2_with_rearrange<F: FftField + PrimeField, Net: MpcSerNet>(
     px: Vec<F>,
     fft_mask: &FftMask<F>,
-    rearrange: bool,
-    g: F,
+    output: FftOrdering,
     pp: &PackedSharingParams<F>,
-    gen: F,
     net: &Net,
     sid: MultiplexedStreamID,
+    king_transform: impl FnOnce(&mut Vec<F>) + Send,
 ) -> Result<Vec<F>, MpcNetError> {
     // King applies FFT2 with rearrange
-    let rng = &mut ark_std::test_rng();
     let mbyl = px.len();
+    let out = add_mask(px, &fft_mask.in_mask);
 
-    let out = px
-        .iter()
-        .zip(fft_mask.in_mask.iter())
-        .map(|(x, m)| *x + *m)
-        .collect::<Vec<_>>();
-
-    let received_shares = net
-        .client_send_or_king_receive_serialized(&out, sid, pp.t)
+    let out_share = net
+        .try_king_round(
+            &out,
+            |rs| {
+                king_fft2(rs, mbyl, fft_mask, output, pp, king_transform)
+                    .map_err(|err| MpcNetError::Protocol {
+                        err: format!("fft2_with_rearrange: {err}"),
+                        party: net.king_id(),
+                    })
+            },
+            sid,
+            pp.t,
+        )
         .await?;
 
-    let king_answer = received_shares.map(|rs| {
-        let all_shares = transpose(rs.shares);
-        let mut s1: Vec<F> = vec![F::zero(); out.len() * pp.l];
-
-        for (i, share) in (0..mbyl).zip(all_shares) {
-            let tmp = pp.unpack_missing_shares(&share, &rs.parties);
+    Ok(add_mask(out_share, &fft_mask.out_mask))
+}
 
-            for j in 0..pp.l {
-                s1[i * pp.l + j] = tmp[j];
-            }
+/// The answer of the king of fft2_with_rearrange: reconstructs the m values
+/// from the received shares, applies king_transform and packs the result
+fn king_fft2<F: FftField + PrimeField>(
+    rs: ReceivedShares<Vec<F>>,
+    mbyl: usize,
+    fft_mask: &FftMask<F>,
+    output: FftOrdering,
+    pp: &PackedSharingParams<F>,
+    king_transform: impl FnOnce(&mut Vec<F>),
+) -> Result<Vec<Vec<F>>, UnpackError<F>> {
+    let mut s1: Vec<F> = vec![F::zero(); mbyl * pp.l];
+
+    let secrets =
+        pp.unpack_missing_shares_many(&rs.shares, &rs.parties, fft_degree(pp))?;
+    for (i, tmp) in secrets.into_iter().take(mbyl).enumerate() {
+        for j in 0..pp.l {
+            s1[i * pp.l + j] = tmp[j];
         }
+    }
 
-        fft2_in_place(&mut s1, pp, gen); // s1 constrains final output now
+    king_transform(&mut s1); // s1 constrains final output now
 
-        if g != F::one() {
-            Radix2EvaluationDomain::<F>::distribute_powers(&mut s1, g);
-        }
+    let rng = &mut StdRng::from_seed(fft_mask.repack_seed);
+    Ok(pack_output(s1, output, pp, rng))
+}
 
-        // Optionally rearrange to get ready for next FFT/IFFT
-        // Saves one round of communication by doing it at the King in the previous FFT/IFFT
-        if rearrange {
-            fft_in_place_rearrange(&mut s1);
-            let mut out_shares: Vec<Vec<F>> = Vec::new();
-            for i in 0..s1.len() / pp.l {
-                out_shares.push(
-                    // This will cause issues with memory benchmarking since it assumes everyone creates this instead of receiving it from dealer
-                    pp.pack(
-                        s1.iter()
-                            .skip(i)
-                            .step_by(s1.len() / pp.l)
-                            .cloned()
-                            .collect::<Vec<_>>(),
-                        rng,
-                    ),
-                );
-            }
-            transpose(out_shares)
-        } else {
-            transpose(pack_vec(&s1, pp))
-        }
-    });
+/// The degree the shares the parties send after fft1 lie on: the input of
+/// d_ifft may be a product of shares, e.g the evaluations of h
+fn fft_degree<F: FftField>(pp: &PackedSharingParams<F>) -> usize {
+    2 * (pp.t + pp.l - 1)
+}
 
-    drop(px);
+/// Checks that a share of the input or output of a transform packs size
+/// values, l of them per element
+fn check_share_size<F: FftField>(
+    stage: &str,
+    share: &[F],
+    size: usize,
+    pp: &PackedSharingParams<F>,
+) -> Result<(), MpcNetError> {
+    if share.len() * pp.l != size {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "Mismatch of size in {stage}, {}, {}.",
+                share.len() * pp.l,
+                size
+            ),
+        });
+    }
+    Ok(())
+}
 
-    let out_share = net
-        .client_receive_or_king_send_serialized(king_answer, sid)
-        .await?;
+/// Adds a share of a mask element-wise
+fn add_mask<F: FftField + PrimeField>(mut x: Vec<F>, mask: &[F]) -> Vec<F> {
+    x.iter_mut().zip(mask).for_each(|(x, m)| *x += m);
+    x
+}
 
-    // unmask
-    let out_share = out_share
-        .iter()
-        .zip(fft_mask.out_mask.iter())
-        .map(|(x, m)| *x + *m)
-        .collect::<Vec<_>>();
+/// Packs the m values of the input of d_fft/d_ifft, BitReversed, and returns
+/// the shares of n parties. values are in the given ordering, Natural values
+/// are rearranged first and BitReversed ones are packed as they are.
+pub fn pack_fft_input<F: FftField + PrimeField>(
+    values: Vec<F>,
+    ordering: FftOrdering,
+    pp: &PackedSharingParams<F>,
+    rng: &mut impl rand::Rng,
+) -> Vec<Vec<F>> {
+    transpose_rows(pack_fft_input_rows(values, ordering, pp, rng))
+}
+
+/// Same as pack_fft_input, but yields the m/l packed vectors, each holding
+/// the n shares of one, instead of the shares of each party.
+pub fn pack_fft_input_rows<'a, F: FftField + PrimeField>(
+    mut values: Vec<F>,
+    ordering: FftOrdering,
+    pp: &'a PackedSharingParams<F>,
+    rng: &'a mut impl rand::Rng,
+) -> impl Iterator<Item = Vec<F>> + 'a {
+    if ordering == FftOrdering::Natural {
+        fft_in_place_rearrange(&mut values);
+    }
+    let stride = values.len() / pp.l;
+    (0..stride).map(move |i| {
+        pp.pack(
+            values.iter().skip(i).step_by(stride).cloned().collect(),
+            rng,
+        )
+    })
+}
+
+/// Packs the output of a transform in the given ordering and returns the
+/// shares of n parties. BitReversed gets it ready for the next FFT/IFFT, which
+/// saves one round of communication by doing it at the King in the previous FFT/IFFT
+fn pack_output<F: FftField + PrimeField>(
+    values: Vec<F>,
+    output: FftOrdering,
+    pp: &PackedSharingParams<F>,
+    rng: &mut impl rand::Rng,
+) -> Vec<Vec<F>> {
+    match output {
+        // This will cause issues with memory benchmarking since it assumes everyone creates this instead of receiving it from dealer
+        FftOrdering::BitReversed => {
+            pack_fft_input(values, FftOrdering::Natural, pp, rng)
+        }
+        FftOrdering::Natural => transpose_rows(
+            values
+                .chunks(pp.l)
+                .map(|chunk| pp.pack(chunk.to_vec(), rng)),
+        ),
+    }
+}
 
-    Ok(out_share)
+/// Same as pack_output, but packs without randomness, for values every
+/// party knows, see PackedSharingParams::pack_from_public
+fn pack_output_public<F: FftField + PrimeField>(
+    mut values: Vec<F>,
+    output: FftOrdering,
+    pp: &PackedSharingParams<F>,
+) -> Vec<Vec<F>> {
+    match output {
+        FftOrdering::BitReversed => {
+            fft_in_place_rearrange(&mut values);
+            let stride = values.len() / pp.l;
+            transpose_rows((0..stride).map(|i| {
+                pp.pack_from_public(
+                    values.iter().skip(i).step_by(stride).cloned().collect(),
+                )
+            }))
+        }
+        FftOrdering::Natural => transpose_rows(
+            values
+                .chunks(pp.l)
+                .map(|chunk| pp.pack_from_public(chunk.to_vec())),
+        ),
+    }
 }
 
+/// Bit-reverses the order of data in place, see FftOrdering
 pub fn fft_in_place_rearrange<F: FftField + PrimeField>(data: &mut Vec<F>) {
     let mut target = 0;
     for pos in 0..data.len() {
@@ -1,22 +1,93 @@
 mod tests {
     use ark_bls12_377::Fr as F;
-    use ark_ff::FftField;
+    use ark_ff::{FftField, Field};
     use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
-    use ark_std::{One, UniformRand};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use ark_std::{One, UniformRand, Zero};
     use mpc_net::LocalTestNet;
     use mpc_net::MpcNet;
     use mpc_net::MultiplexedStreamID;
     use secret_sharing::pss::PackedSharingParams;
 
     use crate::dfft::d_fft;
+    use crate::dfft::d_fft_meshed;
     use crate::dfft::d_ifft;
+    use crate::dfft::d_ifft_then_fft;
+    use crate::dfft::d_ifft_then_fft_multi;
     use crate::dfft::fft_in_place_rearrange;
+    use crate::dfft::local_d_fft;
+    use crate::dfft::local_d_ifft;
+    use crate::dfft::pack_fft_input;
+    use crate::dfft::vanishing_evals_over;
     use crate::dfft::FftMask;
+    use crate::dfft::FftOrdering;
     use crate::utils::pack::transpose;
 
     const L: usize = 2;
     const M: usize = L * 4;
 
+    #[test]
+    fn local_d_fft_works() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let constraint = Radix2EvaluationDomain::<F>::new(M).unwrap();
+        let poly_coeffs = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+
+        assert_eq!(
+            constraint.fft(&poly_coeffs),
+            local_d_fft(poly_coeffs, &constraint, &pp)
+        );
+    }
+
+    #[test]
+    fn local_coset_d_ifft_works() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let constraint = Radix2EvaluationDomain::<F>::new(M).unwrap();
+        let constraint_coset = constraint.get_coset(F::GENERATOR).unwrap();
+        let poly_evals = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+
+        // coefficients of the polynomial, ready to be evaluated over the coset
+        let expected = constraint_coset.fft(&constraint.ifft(&poly_evals));
+        let coeffs = local_d_ifft(
+            poly_evals,
+            &constraint,
+            constraint_coset.coset_offset(),
+            &pp,
+        );
+
+        assert_eq!(expected, constraint.fft(&coeffs));
+    }
+
+    #[test]
+    fn vanishing_evals_over_matches_division() {
+        let rng = &mut ark_std::test_rng();
+        let constraint = Radix2EvaluationDomain::<F>::new(M).unwrap();
+        let large = Radix2EvaluationDomain::<F>::new(8 * M).unwrap();
+        let large_coset = large.get_coset(F::GENERATOR).unwrap();
+
+        let z_evals = vanishing_evals_over(&large, &constraint, F::GENERATOR);
+        for (z, x) in z_evals.iter().zip(large_coset.elements()) {
+            assert_eq!(*z, constraint.evaluate_vanishing_polynomial(x));
+        }
+
+        // t = Z_H * q, so dividing the evaluations of t recovers q
+        let q = (0..7 * M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let mut t = vec![F::zero(); M];
+        t.extend_from_slice(&q);
+        q.iter().enumerate().for_each(|(i, c)| t[i] -= c);
+
+        let quotient = large_coset
+            .fft(&t)
+            .into_iter()
+            .zip(z_evals)
+            .map(|(t, z)| t * z.inverse().unwrap())
+            .collect::<Vec<_>>();
+        let mut expected = q;
+        expected.resize(8 * M, F::zero());
+        assert_eq!(large_coset.ifft(&quotient), expected);
+    }
+
     #[tokio::test]
     async fn d_ifft_works() {
         let rng = &mut ark_std::test_rng();
@@ -39,7 +110,7 @@ mod tests {
         }
 
         let ifft_mask = FftMask::<F>::sample(
-            false,
+            FftOrdering::Natural,
             F::one(),
             constraint.group_gen_inv(),
             M,
@@ -57,7 +128,7 @@ mod tests {
                     d_ifft(
                         pack_eval,
                         &ifft_mask[idx],
-                        false,
+                        FftOrdering::Natural,
                         &constraint,
                         F::one(),
                         &pp,
@@ -101,7 +172,7 @@ mod tests {
         }
 
         let fft_mask = FftMask::<F>::sample(
-            false,
+            FftOrdering::Natural,
             F::one(),
             constraint.group_gen(),
             M,
@@ -119,7 +190,147 @@ mod tests {
                     d_fft(
                         pack_coeff,
                         &fft_mask[idx],
-                        false,
+                        FftOrdering::Natural,
+                        &constraint,
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        let computed_poly_evals = transpose(result)
+            .into_iter()
+            .flat_map(|x| pp.unpack(x))
+            .collect::<Vec<_>>();
+
+        assert_eq!(poly_evals, computed_poly_evals);
+    }
+
+    #[test]
+    fn sample_for_party_matches_the_dealt_shares() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let gen = Radix2EvaluationDomain::<F>::new(M).unwrap().group_gen();
+        let sample = |seed| {
+            FftMask::<F>::sample(
+                FftOrdering::BitReversed,
+                F::one(),
+                gen,
+                M,
+                &pp,
+                &mut StdRng::seed_from_u64(seed),
+            )
+        };
+        let for_party = |id, seed| {
+            FftMask::<F>::sample_for_party(
+                id,
+                seed,
+                FftOrdering::BitReversed,
+                F::one(),
+                gen,
+                M,
+                &pp,
+            )
+        };
+
+        for (id, mask) in sample(7).iter().enumerate() {
+            let own = for_party(id as u32, 7);
+            assert_eq!(own.in_mask, mask.in_mask);
+            assert_eq!(own.out_mask, mask.out_mask);
+        }
+        assert_ne!(for_party(0, 8).in_mask, sample(7)[0].in_mask);
+    }
+
+    #[tokio::test]
+    async fn d_fft_repacks_with_the_dealt_randomness() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let constraint = Radix2EvaluationDomain::<F>::new(M).unwrap();
+        let poly_coeffs = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let pack_coeffs =
+            pack_fft_input(poly_coeffs, FftOrdering::Natural, &pp, rng);
+        let fft_mask = FftMask::<F>::sample(
+            FftOrdering::Natural,
+            F::one(),
+            constraint.group_gen(),
+            M,
+            &pp,
+            rng,
+        );
+
+        // the same masks give the same output shares
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+            let result = network
+                .simulate_network_round(
+                    (pack_coeffs.clone(), fft_mask.clone(), pp, constraint),
+                    |net, (pack_coeffs, fft_mask, pp, constraint)| async move {
+                        let idx = net.party_id() as usize;
+                        d_fft(
+                            pack_coeffs[idx].clone(),
+                            &fft_mask[idx],
+                            FftOrdering::Natural,
+                            &constraint,
+                            &pp,
+                            &net,
+                            MultiplexedStreamID::Zero,
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await;
+            runs.push(result);
+        }
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    #[tokio::test]
+    async fn d_fft_meshed_works() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let constraint = Radix2EvaluationDomain::<F>::new(M).unwrap();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let mut poly_coeffs = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let poly_evals = constraint.fft(&poly_coeffs);
+
+        fft_in_place_rearrange(&mut poly_coeffs);
+
+        let mut pack_coeffs: Vec<Vec<F>> = Vec::new();
+        for i in 0..M / pp.l {
+            let secrets = poly_coeffs
+                .iter()
+                .skip(i)
+                .step_by(M / pp.l)
+                .cloned()
+                .collect::<Vec<_>>();
+            pack_coeffs.push(pp.pack(secrets, rng));
+        }
+
+        let fft_mask = FftMask::<F>::sample(
+            FftOrdering::Natural,
+            F::one(),
+            constraint.group_gen(),
+            M,
+            &pp,
+            rng,
+        );
+
+        let result = network
+            .simulate_network_round(
+                (pack_coeffs, fft_mask, pp, constraint),
+                |net, (pack_coeffs, fft_mask, pp, constraint)| async move {
+                    let idx = net.party_id() as usize;
+                    let pack_coeff =
+                        pack_coeffs.iter().map(|x| x[idx]).collect::<Vec<_>>();
+                    d_fft_meshed(
+                        pack_coeff,
+                        &fft_mask[idx],
+                        FftOrdering::Natural,
                         &constraint,
                         &pp,
                         &net,
@@ -161,7 +372,7 @@ mod tests {
         }
 
         let ifft_mask = FftMask::<F>::sample(
-            true,
+            FftOrdering::BitReversed,
             F::one(),
             constraint.group_gen_inv(),
             M,
@@ -170,7 +381,7 @@ mod tests {
         );
 
         let fft_mask = FftMask::<F>::sample(
-            false,
+            FftOrdering::Natural,
             F::one(),
             constraint.group_gen(),
             M,
@@ -188,7 +399,7 @@ mod tests {
                     let p_coeff = d_ifft(
                         pack_eval,
                         &ifft_mask[idx],
-                        true,
+                        FftOrdering::BitReversed,
                         &constraint,
                         F::one(),
                         &pp,
@@ -200,7 +411,7 @@ mod tests {
                     d_fft(
                         p_coeff,
                         &fft_mask[idx],
-                        false,
+                        FftOrdering::Natural,
                         &constraint,
                         &pp,
                         &net,
@@ -243,7 +454,7 @@ mod tests {
 
         let fft_masks = [
             FftMask::<F>::sample(
-                true,
+                FftOrdering::BitReversed,
                 constraint_coset.coset_offset(),
                 constraint.group_gen_inv(),
                 M,
@@ -251,7 +462,7 @@ mod tests {
                 rng,
             ),
             FftMask::<F>::sample(
-                true,
+                FftOrdering::BitReversed,
                 F::one(),
                 constraint_coset.group_gen(),
                 M,
@@ -259,7 +470,7 @@ mod tests {
                 rng,
             ),
             FftMask::<F>::sample(
-                true,
+                FftOrdering::BitReversed,
                 constraint_coset.coset_offset_inv(),
                 constraint.group_gen_inv(),
                 M,
@@ -267,7 +478,7 @@ mod tests {
                 rng,
             ),
             FftMask::<F>::sample(
-                false,
+                FftOrdering::Natural,
                 F::one(),
                 constraint_coset.group_gen(),
                 M,
@@ -298,7 +509,7 @@ mod tests {
                         let p_coeff = d_ifft(
                             peval_share,
                             &fft_masks[0][idx],
-                            true,
+                            FftOrdering::BitReversed,
                             &constraint,
                             constraint_coset.coset_offset(),
                             &pp,
@@ -310,7 +521,7 @@ mod tests {
                         let coset_peval_share = d_fft(
                             p_coeff,
                             &fft_masks[1][idx],
-                            true,
+                            FftOrdering::BitReversed,
                             &constraint,
                             &pp,
                             &net,
@@ -322,7 +533,7 @@ mod tests {
                         let p_coeff = d_ifft(
                             coset_peval_share,
                             &fft_masks[2][idx],
-                            true,
+                            FftOrdering::BitReversed,
                             &constraint,
                             constraint_coset.coset_offset_inv(),
                             &pp,
@@ -334,7 +545,7 @@ mod tests {
                         d_fft(
                             p_coeff,
                             &fft_masks[3][idx],
-                            false,
+                            FftOrdering::Natural,
                             &constraint,
                             &pp,
                             &net,
@@ -355,4 +566,313 @@ mod tests {
 
         assert_eq!(expected_poly_evals, computed_poly_evals);
     }
+
+    #[tokio::test]
+    async fn d_ifft_then_fft_works() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let constraint = Radix2EvaluationDomain::<F>::new(M).unwrap();
+        let constraint_coset = constraint.get_coset(F::GENERATOR).unwrap();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let mut poly_evals = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        // evals over the coset, as computed with a d_ifft and a d_fft
+        let expected = constraint_coset.fft(&constraint.ifft(&poly_evals));
+
+        fft_in_place_rearrange(&mut poly_evals);
+        let mut pack_evals: Vec<Vec<F>> = Vec::new();
+        for i in 0..M / pp.l {
+            let secrets = poly_evals
+                .iter()
+                .skip(i)
+                .step_by(M / pp.l)
+                .cloned()
+                .collect::<Vec<_>>();
+            pack_evals.push(pp.pack(secrets, rng));
+        }
+
+        let fft_mask = FftMask::<F>::sample_ifft_then_fft(
+            FftOrdering::Natural,
+            constraint_coset.coset_offset(),
+            &constraint,
+            &pp,
+            rng,
+        );
+
+        let result =
+            network
+                .simulate_network_round(
+                    (pack_evals, fft_mask, pp, constraint, constraint_coset),
+                    |net,
+                     (
+                        pack_evals,
+                        fft_mask,
+                        pp,
+                        constraint,
+                        constraint_coset,
+                    )| async move {
+                        let idx = net.party_id() as usize;
+                        let peval_share = pack_evals
+                            .iter()
+                            .map(|x| x[idx])
+                            .collect::<Vec<_>>();
+                        d_ifft_then_fft(
+                            peval_share,
+                            &fft_mask[idx],
+                            FftOrdering::Natural,
+                            &constraint,
+                            constraint_coset.coset_offset(),
+                            &pp,
+                            &net,
+                            MultiplexedStreamID::Zero,
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await;
+
+        let computed = transpose(result)
+            .into_iter()
+            .flat_map(|x| pp.unpack(x))
+            .collect::<Vec<_>>();
+
+        assert_eq!(expected, computed);
+    }
+
+    #[tokio::test]
+    async fn d_ifft_then_fft_multi_works() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let constraint = Radix2EvaluationDomain::<F>::new(M).unwrap();
+        let constraint_coset = constraint.get_coset(F::GENERATOR).unwrap();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let poly_evals = (0..2)
+            .map(|_| (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let expected = poly_evals
+            .iter()
+            .map(|evals| constraint_coset.fft(&constraint.ifft(evals)))
+            .collect::<Vec<_>>();
+
+        let pack_evals = poly_evals
+            .into_iter()
+            .map(|evals| pack_fft_input(evals, FftOrdering::Natural, &pp, rng))
+            .collect::<Vec<_>>();
+        let fft_masks = (0..2)
+            .map(|_| {
+                FftMask::<F>::sample_ifft_then_fft(
+                    FftOrdering::Natural,
+                    constraint_coset.coset_offset(),
+                    &constraint,
+                    &pp,
+                    rng,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let result = network
+            .simulate_network_round(
+                (pack_evals, fft_masks, pp, constraint, constraint_coset),
+                |net, (pack_evals, fft_masks, pp, constraint, coset)| async move {
+                    let idx = net.party_id() as usize;
+                    d_ifft_then_fft_multi(
+                        [pack_evals[0][idx].clone(), pack_evals[1][idx].clone()],
+                        [&fft_masks[0][idx], &fft_masks[1][idx]],
+                        FftOrdering::Natural,
+                        &constraint,
+                        coset.coset_offset(),
+                        &pp,
+                        &net,
+                        [MultiplexedStreamID::Zero, MultiplexedStreamID::One],
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        for (k, expected) in expected.into_iter().enumerate() {
+            let shares = result.iter().map(|r| r[k].clone()).collect();
+            let computed = transpose(shares)
+                .into_iter()
+                .flat_map(|x| pp.unpack(x))
+                .collect::<Vec<_>>();
+            assert_eq!(expected, computed);
+        }
+    }
+
+    #[cfg(feature = "debug-fft")]
+    #[tokio::test]
+    async fn d_check_against_finds_divergence() {
+        use crate::dfft::d_check_against;
+
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let constraint = Radix2EvaluationDomain::<F>::new(M).unwrap();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let mut poly_evals = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let poly_coeffs = constraint.ifft(&poly_evals);
+        let mut wrong_coeffs = poly_coeffs.clone();
+        wrong_coeffs[5] += F::one();
+
+        fft_in_place_rearrange(&mut poly_evals);
+        let mut pack_evals: Vec<Vec<F>> = Vec::new();
+        for i in 0..M / pp.l {
+            let secrets = poly_evals
+                .iter()
+                .skip(i)
+                .step_by(M / pp.l)
+                .cloned()
+                .collect::<Vec<_>>();
+            pack_evals.push(pp.pack(secrets, rng));
+        }
+
+        let ifft_mask = FftMask::<F>::sample(
+            FftOrdering::BitReversed,
+            F::one(),
+            constraint.group_gen_inv(),
+            M,
+            &pp,
+            rng,
+        );
+
+        let result = network
+            .simulate_network_round(
+                (
+                    pack_evals,
+                    ifft_mask,
+                    pp,
+                    constraint,
+                    poly_coeffs,
+                    wrong_coeffs,
+                ),
+                |net,
+                 (
+                    pack_evals,
+                    ifft_mask,
+                    pp,
+                    constraint,
+                    poly_coeffs,
+                    wrong_coeffs,
+                )| async move {
+                    let idx = net.party_id() as usize;
+                    let pack_eval =
+                        pack_evals.iter().map(|x| x[idx]).collect::<Vec<_>>();
+                    let p_coeff = d_ifft(
+                        pack_eval,
+                        &ifft_mask[idx],
+                        FftOrdering::BitReversed,
+                        &constraint,
+                        F::one(),
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap();
+                    let sid = MultiplexedStreamID::Zero;
+                    let right = d_check_against(
+                        "ifft",
+                        &p_coeff,
+                        &poly_coeffs,
+                        FftOrdering::BitReversed,
+                        &pp,
+                        &net,
+                        sid,
+                    )
+                    .await
+                    .unwrap();
+                    let wrong = d_check_against(
+                        "ifft",
+                        &p_coeff,
+                        &wrong_coeffs,
+                        FftOrdering::BitReversed,
+                        &pp,
+                        &net,
+                        sid,
+                    )
+                    .await
+                    .unwrap();
+                    (right, wrong)
+                },
+            )
+            .await;
+
+        assert_eq!(result[0], (None, Some(5)));
+        assert!(result[1..].iter().all(|r| *r == (None, None)));
+    }
+
+    #[tokio::test]
+    async fn d_fft_then_d_ifft_round_trips_both_orderings() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let constraint = Radix2EvaluationDomain::<F>::new(M).unwrap();
+
+        for ordering in [FftOrdering::Natural, FftOrdering::BitReversed] {
+            let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+            let poly_coeffs = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+            let mut values = poly_coeffs.clone();
+            if ordering == FftOrdering::BitReversed {
+                fft_in_place_rearrange(&mut values);
+            }
+            let shares = pack_fft_input(values, ordering, &pp, rng);
+
+            // the evaluations stay bit-reversed, ready for d_ifft
+            let fft_mask = FftMask::<F>::sample(
+                FftOrdering::BitReversed,
+                F::one(),
+                constraint.group_gen(),
+                M,
+                &pp,
+                rng,
+            );
+            let ifft_mask = FftMask::<F>::sample(
+                FftOrdering::Natural,
+                F::one(),
+                constraint.group_gen_inv(),
+                M,
+                &pp,
+                rng,
+            );
+
+            let result = network
+                .simulate_network_round(
+                    (shares, fft_mask, ifft_mask, pp, constraint),
+                    |net, (shares, fft_mask, ifft_mask, pp, constraint)| async move {
+                        let idx = net.party_id() as usize;
+                        let sid = MultiplexedStreamID::Zero;
+                        let p_eval = d_fft(
+                            shares[idx].clone(),
+                            &fft_mask[idx],
+                            FftOrdering::BitReversed,
+                            &constraint,
+                            &pp,
+                            &net,
+                            sid,
+                        )
+                        .await
+                        .unwrap();
+                        d_ifft(
+                            p_eval,
+                            &ifft_mask[idx],
+                            FftOrdering::Natural,
+                            &constraint,
+                            F::one(),
+                            &pp,
+                            &net,
+                            sid,
+                        )
+                        .await
+                        .unwrap()
+                    },
+                )
+                .await;
+
+            let computed_poly_coeffs = transpose(result)
+                .into_iter()
+                .flat_map(|x| pp.unpack(x))
+                .collect::<Vec<_>>();
+            assert_eq!(poly_coeffs, computed_poly_coeffs, "{ordering:?}");
+        }
+    }
 }
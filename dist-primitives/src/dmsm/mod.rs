@@ -1,12 +1,22 @@
+use crate::dreduce::d_reduce;
+use crate::utils::{
+    check_mesh_size, dealer_rng, share_index, PackedSharingParamsExt,
+};
 use ark_ec::CurveGroup;
-use ark_ff::UniformRand;
+use ark_ff::{Field, UniformRand};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use mpc_net::ser_net::MpcSerNet;
-use mpc_net::{MpcNetError, MultiplexedStreamID};
+use mpc_net::{MpcNet, MpcNetError, MultiplexedStreamID};
 use secret_sharing::pss::PackedSharingParams;
 
 /// Masks used in dmsm
 /// Note that this only contains one share of the mask
 #[derive(Clone)]
+#[cfg_attr(
+    feature = "zeroize",
+    derive(zeroize::Zeroize, zeroize::ZeroizeOnDrop),
+    zeroize(bound = "")
+)]
 pub struct MsmMask<G: CurveGroup> {
     pub in_mask: G,
     pub out_mask: G,
@@ -46,9 +56,79 @@ impl<G: CurveGroup> MsmMask<G> {
             .collect()
     }
 
-    /// Returns a default value for MsmMask. Not secure.
-    /// Only to be used for debugging purposes.
+    /// Same as sample, but returns the share of party_id only, dealt from
+    /// shared_seed, so that every party derives its share itself and a run
+    /// can be replayed. Insecure, see [insecure masks](crate#insecure-masks).
+    pub fn sample_for_party(
+        party_id: u32,
+        shared_seed: u64,
+        pp: &PackedSharingParams<G::ScalarField>,
+    ) -> Self {
+        Self::sample(pp, &mut dealer_rng(shared_seed))
+            .swap_remove(party_id as usize)
+    }
+
+    /// Samples an MsmMask jointly, without a dealer: every party deals a random
+    /// mask and adds up the shares it receives, so nobody knows the mask as long
+    /// as one party is honest. Returns this party's share.
+    /// Shares are sent directly between parties, so net must connect every pair
+    /// of parties (e.g LocalTestNet), not only the king to the clients.
+    pub async fn jointly_sample<Net: MpcNet>(
+        pp: &PackedSharingParams<G::ScalarField>,
+        net: &Net,
+        sid: MultiplexedStreamID,
+        rng: &mut impl rand::Rng,
+    ) -> Result<Self, MpcNetError> {
+        pp.assert_matches(net)?;
+        let own_id = net.party_id();
+        let own_index = share_index(net)?;
+        let dealt = Self::sample(pp, rng);
+
+        let send = async {
+            for (id, share) in net.party_ids().zip(&dealt) {
+                if id == own_id {
+                    continue;
+                }
+                let mut bytes = Vec::new();
+                (share.in_mask, share.out_mask)
+                    .serialize_compressed(&mut bytes)?;
+                net.send_to(id, bytes.into(), sid).await?;
+            }
+            Ok::<_, MpcNetError>(())
+        };
+
+        let recv = async {
+            let mut received = Vec::new();
+            for id in net.party_ids().filter(|id| *id != own_id) {
+                let bytes = net.recv_from(id, sid).await?;
+                let (in_mask, out_mask) = <(G, G)>::deserialize_compressed(
+                    &bytes[..],
+                )
+                .map_err(|err| MpcNetError::Protocol {
+                    err: format!("Could not deserialize mask: {err}"),
+                    party: id,
+                })?;
+                received.push(Self::new(in_mask, out_mask));
+            }
+            Ok::<_, MpcNetError>(received)
+        };
+
+        let (_, received) = tokio::try_join!(send, recv)?;
+
+        let own = &dealt[own_index];
+        Ok(received.iter().fold(own.clone(), |acc, share| {
+            Self::new(
+                acc.in_mask + share.in_mask,
+                acc.out_mask + share.out_mask,
+            )
+        }))
+    }
+
+    /// Returns an all-zero MsmMask.
+    /// Insecure, see [insecure masks](crate#insecure-masks).
     pub fn zero() -> Self {
+        #[cfg(debug_assertions)]
+        log::warn!("using an insecure zero MsmMask");
         Self {
             in_mask: G::zero(),
             out_mask: G::zero(),
@@ -56,6 +136,15 @@ impl<G: CurveGroup> MsmMask<G> {
     }
 }
 
+/// Computes shares of the MSM of packed bases and scalars
+/// Only needs a CurveGroup, no pairing, so it also serves curves used for
+/// recursion, as long as their scalar field has a radix-2 domain of size n
+/// (e.g not Grumpkin, whose scalar field has 2-adicity 1).
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = scalars.len())
+)]
 pub async fn d_msm<G: CurveGroup, Net: MpcSerNet>(
     bases: &[G::Affine],
     scalars: &[G::ScalarField],
@@ -67,29 +156,269 @@ pub async fn d_msm<G: CurveGroup, Net: MpcSerNet>(
     // Using affine is important because we don't want to create an extra vector for converting Projective to Affine.
     // Eventually we do have to convert to Projective but this will be pp.l group elements instead of m()
 
+    pp.assert_matches(net)?;
+
     // First round of local computation done by parties
-    debug_assert_eq!(bases.len(), scalars.len());
-    log::debug!("bases: {}, scalars: {}", bases.len(), scalars.len());
-    let c_share = G::msm(bases, scalars)?;
+    let c_share = local_msm::<G>("d_msm", bases, scalars)?;
+    d_add_shares(c_share, msm_mask, pp, net, sid).await
+}
+
+/// Same as d_msm, but the output is not sent back: only the king gets it,
+/// saving a round when the king consumes it anyway, e.g to assemble a proof.
+/// Returns Some(output) at the king and None elsewhere, the other parties
+/// hold nothing of the output.
+/// The king unmasks the output with out_mask. As in d_add_shares, in_mask
+/// does not mask the coefficients of the product above degree t+l-1.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = scalars.len())
+)]
+pub async fn d_msm_to_king<G: CurveGroup, Net: MpcSerNet>(
+    bases: &[G::Affine],
+    scalars: &[G::ScalarField],
+    msm_mask: &MsmMask<G>,
+    pp: &PackedSharingParams<G::ScalarField>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Option<G>, MpcNetError> {
+    pp.assert_matches(net)?;
+    let c_share = local_msm::<G>("d_msm_to_king", bases, scalars)?;
+
+    let rs = net
+        .client_send_or_king_receive_serialized(
+            &(c_share + msm_mask.in_mask, msm_mask.out_mask),
+            sid,
+            pp.t,
+        )
+        .await?;
+    Ok(rs.map(|rs| {
+        let (masked, out_masks): (Vec<G>, Vec<G>) =
+            rs.shares.into_iter().unzip();
+        let masked: G = pp
+            .unpack_missing_shares(&masked, &rs.parties)
+            .into_iter()
+            .sum();
+        // out_mask is a repeated sharing, any of its secrets will do
+        masked + pp.unpack_missing_shares(&out_masks, &rs.parties)[0]
+    }))
+}
+
+/// Same as d_msm, but without the king: every party broadcasts its masked
+/// share, reconstructs the masked output itself and derives its share of
+/// the output from it. Only for small networks, see MAX_MESH_PARTIES, and
+/// net must connect every pair of parties.
+/// Every party sees what the king of d_add_shares sees, including the
+/// coefficients of the product that in_mask does not mask.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = scalars.len())
+)]
+pub async fn d_msm_meshed<G: CurveGroup, Net: MpcSerNet>(
+    bases: &[G::Affine],
+    scalars: &[G::ScalarField],
+    msm_mask: &MsmMask<G>,
+    pp: &PackedSharingParams<G::ScalarField>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<G, MpcNetError> {
+    pp.assert_matches(net)?;
+    check_mesh_size(net)?;
+    let c_share = local_msm::<G>("d_msm_meshed", bases, scalars)?;
+
+    let rs = net
+        .broadcast_serialized(&(c_share + msm_mask.in_mask), sid)
+        .await?;
+    let masked: G = pp
+        .unpack_missing_shares(&rs.shares, &rs.parties)
+        .into_iter()
+        .sum();
+
+    // every party packs the same public value, out_mask rerandomizes it
+    let output = pp.pack_from_public(vec![masked; pp.l]);
+    Ok(output[share_index(net)?] + msm_mask.out_mask)
+}
+
+/// Public hint for d_msm_sparse: the positions of the packed scalars that
+/// pack l zeros, known to every party.
+/// The sparsity must be public, e.g fixed by the circuit: computing it from
+/// the witness would leak which witness values are zero.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SparseScalars {
+    /// Sorted positions of the packed scalars known to be zero
+    zeros: Vec<usize>,
+}
+
+impl SparseScalars {
+    pub fn new(zeros: impl IntoIterator<Item = usize>) -> Self {
+        let mut zeros = zeros.into_iter().collect::<Vec<_>>();
+        zeros.sort_unstable();
+        zeros.dedup();
+        Self { zeros }
+    }
+
+    /// Marks the chunks of l public values that are all zero,
+    /// with the chunks packed the way pack_vec does
+    pub fn from_public<F: Field>(values: &[F], l: usize) -> Self {
+        Self::new(
+            values
+                .chunks(l)
+                .enumerate()
+                .filter(|(_, chunk)| chunk.iter().all(F::is_zero))
+                .map(|(i, _)| i),
+        )
+    }
+
+    /// Number of packed scalars known to be zero
+    pub fn len(&self) -> usize {
+        self.zeros.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.zeros.is_empty()
+    }
+}
+
+/// Same as d_msm, but leaves out of the local MSM the bases whose packed
+/// scalars are zero according to the public hint.
+/// Dropping them changes the shares but not the packed secrets, so the
+/// output is the same as d_msm's. An empty hint is exactly d_msm.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = scalars.len(), zeros = sparse.len())
+)]
+pub async fn d_msm_sparse<G: CurveGroup, Net: MpcSerNet>(
+    bases: &[G::Affine],
+    scalars: &[G::ScalarField],
+    sparse: &SparseScalars,
+    msm_mask: &MsmMask<G>,
+    pp: &PackedSharingParams<G::ScalarField>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<G, MpcNetError> {
+    if sparse.is_empty() {
+        return d_msm(bases, scalars, msm_mask, pp, net, sid).await;
+    }
+    pp.assert_matches(net)?;
+    check_lengths("d_msm_sparse", bases.len(), scalars.len())?;
+    if sparse.zeros.last().is_some_and(|i| *i >= scalars.len()) {
+        return Err(MpcNetError::BadInput {
+            err: format!("d_msm_sparse: hint beyond {} scalars", scalars.len()),
+        });
+    }
+
+    let mut zeros = sparse.zeros.iter().peekable();
+    let (bases, scalars): (Vec<_>, Vec<_>) = bases
+        .iter()
+        .zip(scalars)
+        .enumerate()
+        .filter(|(i, _)| zeros.next_if_eq(&i).is_none())
+        .map(|(_, (base, scalar))| (*base, *scalar))
+        .unzip();
+    let c_share = local_msm::<G>("d_msm_sparse", &bases, &scalars)?;
+    d_add_shares(c_share, msm_mask, pp, net, sid).await
+}
+
+/// Same as d_msm, but the bases arrive in chunks, e.g streamed from disk,
+/// so that they never have to be in memory all at once.
+/// The chunks are consumed in order and must cover scalars exactly.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = scalars.len())
+)]
+pub async fn d_msm_chunked<G, B, E, Net>(
+    bases: impl IntoIterator<Item = Result<B, E>>,
+    scalars: &[G::ScalarField],
+    msm_mask: &MsmMask<G>,
+    pp: &PackedSharingParams<G::ScalarField>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<G, MpcNetError>
+where
+    G: CurveGroup,
+    B: AsRef<[G::Affine]>,
+    MpcNetError: From<E>,
+    Net: MpcSerNet,
+{
+    pp.assert_matches(net)?;
+
+    let mut c_share = G::zero();
+    let mut offset = 0;
+    for chunk in bases {
+        let chunk = chunk?;
+        let chunk = chunk.as_ref();
+        let end = offset + chunk.len();
+        if end > scalars.len() {
+            return Err(MpcNetError::BadInput {
+                err: format!(
+                    "d_msm_chunked: more than {} bases",
+                    scalars.len()
+                ),
+            });
+        }
+        c_share +=
+            local_msm::<G>("d_msm_chunked", chunk, &scalars[offset..end])?;
+        offset = end;
+    }
+    check_lengths("d_msm_chunked", offset, scalars.len())?;
+
+    d_add_shares(c_share, msm_mask, pp, net, sid).await
+}
+
+/// Computes a party's share of the MSM, the first round of every d_msm
+/// variant. prefix names the caller in the error.
+fn local_msm<G: CurveGroup>(
+    prefix: &str,
+    bases: &[G::Affine],
+    scalars: &[G::ScalarField],
+) -> Result<G, MpcNetError> {
+    check_lengths(prefix, bases.len(), scalars.len())?;
+    Ok(G::msm_unchecked(bases, scalars))
+}
+
+fn check_lengths(
+    prefix: &str,
+    bases: usize,
+    scalars: usize,
+) -> Result<(), MpcNetError> {
+    if bases != scalars {
+        return Err(MpcNetError::BadInput {
+            err: format!("{prefix}: {bases} bases but {scalars} scalars"),
+        });
+    }
+    Ok(())
+}
+
+/// Sums the l group elements packed in c_share, keeping the sum shared
+/// The output stays a "repeated" packed sharing.
+/// The king opens c_share + msm_mask.in_mask, but c_share is a product of
+/// degree 2(t+l-1) and in_mask only has degree t+l-1, so the coefficients
+/// of c_share above t+l-1 are opened unmasked and the king may learn more
+/// than the sum of the masks (see the TODO below).
+/// A mask must not be reused across calls.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid)
+)]
+pub async fn d_add_shares<G: CurveGroup, Net: MpcSerNet>(
+    c_share: G,
+    msm_mask: &MsmMask<G>,
+    pp: &PackedSharingParams<G::ScalarField>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<G, MpcNetError> {
+    pp.assert_matches(net)?;
+    // TODO: Mask with random values.
+    // in_mask only masks the coefficients of c_share up to degree t+l-1, a
+    // sharing of zero of degree 2(t+l-1) would be needed to mask the rest
     let c_share = c_share + msm_mask.in_mask;
     // Now we do degree reduction -- psstoss
     // Send to king who reduces and sends shamir shares (not packed).
-    // Should be randomized. First convert to projective share.
-    let n_parties = net.n_parties();
-    let king_answer: Option<Vec<G>> = net
-        .client_send_or_king_receive_serialized(&c_share, sid, pp.t)
-        .await?
-        .map(|rs| {
-            // TODO: Mask with random values.
-
-            let result = pp.unpack_missing_shares(&rs.shares, &rs.parties);
-            let output: G = result.iter().sum();
-            vec![output; n_parties]
-        });
-
-    let result = net
-        .client_receive_or_king_send_serialized(king_answer, sid)
-        .await;
+    let result = d_reduce(c_share, |a, b| a + b, pp, net, sid).await;
 
     // At the end all parties hold a packed secret sharing of the output
     // Note that the output is just a single group element and it is shared
@@ -107,9 +436,15 @@ mod tests {
     use ark_ec::CurveGroup;
     use ark_ec::Group;
     use ark_ec::VariableBaseMSM;
+    use ark_std::rand::{rngs::StdRng, Rng, SeedableRng};
     use ark_std::UniformRand;
     use ark_std::Zero;
+    use mpc_net::chaos::{ChaosNet, Fault};
+    use mpc_net::inproc::{InProcNet, InProcTestNet};
+    use mpc_net::ser_net::MpcSerNet;
+    use mpc_net::{LocalTestNet, MpcNet, MpcNetError, MultiplexedStreamID};
     use secret_sharing::pss::PackedSharingParams;
+    use std::time::Duration;
 
     use ark_bls12_377::G1Affine;
     use ark_bls12_377::G1Projective as G1P;
@@ -118,12 +453,65 @@ mod tests {
         <ark_bls12_377::Config as Bls12Config>::G1Config,
     > as Group>::ScalarField;
 
+    use crate::dmsm::{
+        d_add_shares, d_msm, d_msm_chunked, d_msm_meshed, d_msm_sparse,
+        d_msm_to_king, MsmMask, SparseScalars,
+    };
     use crate::utils::pack::transpose;
 
     const L: usize = 2;
     const N: usize = L * 4;
     const M: usize = 1 << 8;
 
+    /// An msm of M random terms and its result, with the bases and scalars
+    /// packed l per share and the masks, as dealt to the n parties
+    #[derive(Clone)]
+    struct MsmFixture<G: CurveGroup> {
+        expected: G,
+        base_shares: Vec<Vec<G::Affine>>,
+        scalar_shares: Vec<Vec<G::ScalarField>>,
+        masks: Vec<MsmMask<G>>,
+    }
+
+    fn msm_fixture<G: CurveGroup>(
+        pp: &PackedSharingParams<G::ScalarField>,
+        rng: &mut impl Rng,
+    ) -> MsmFixture<G> {
+        let scalars = (0..M).map(|_| G::ScalarField::rand(rng)).collect();
+        msm_fixture_with_scalars(scalars, pp, rng)
+    }
+
+    /// msm_fixture with the given M scalars
+    fn msm_fixture_with_scalars<G: CurveGroup>(
+        scalars: Vec<G::ScalarField>,
+        pp: &PackedSharingParams<G::ScalarField>,
+        rng: &mut impl Rng,
+    ) -> MsmFixture<G> {
+        let bases = (0..M).map(|_| G::rand(rng)).collect::<Vec<_>>();
+        let expected = G::msm(&G::normalize_batch(&bases), &scalars).unwrap();
+
+        let base_shares = transpose(
+            bases.chunks(L).map(|s| pp.pack(s.to_vec(), rng)).collect(),
+        )
+        .iter()
+        .map(|shares| G::normalize_batch(shares))
+        .collect();
+        let scalar_shares = transpose(
+            scalars
+                .chunks(L)
+                .map(|s| pp.pack(s.to_vec(), rng))
+                .collect(),
+        );
+        let masks = MsmMask::<G>::sample(pp, rng);
+
+        MsmFixture {
+            expected,
+            base_shares,
+            scalar_shares,
+            masks,
+        }
+    }
+
     #[tokio::test]
     async fn pack_unpack_test() {
         let pp = PackedSharingParams::<F>::new(L);
@@ -178,4 +566,498 @@ mod tests {
         let result: G1P = pp.unpack2(result).iter().sum();
         assert_eq!(expected, result);
     }
+
+    #[tokio::test]
+    async fn d_add_shares_works() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let secrets = (0..L).map(|_| G1P::rand(rng)).collect::<Vec<_>>();
+        let expected: G1P = secrets.iter().sum();
+        let shares = pp.pack(secrets, rng);
+        let masks = MsmMask::<G1P>::sample(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (shares, masks, pp),
+                |net, (shares, masks, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    let output = d_add_shares(
+                        shares[idx],
+                        &masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap();
+
+                    net.client_send_or_king_receive_serialized(
+                        &output,
+                        MultiplexedStreamID::Zero,
+                        pp.t,
+                    )
+                    .await
+                    .unwrap()
+                    .map(|rs| pp.unpack_missing_shares(&rs.shares, &rs.parties))
+                },
+            )
+            .await;
+
+        // a repeated sharing of the sum
+        assert_eq!(result[0], Some(vec![expected; L]));
+    }
+
+    #[tokio::test]
+    async fn d_msm_rejects_length_mismatch() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let bases = vec![G1Affine::rand(rng); 2];
+        let scalars = vec![F::rand(rng); 3];
+        let result = d_msm::<G1P, _>(
+            &bases,
+            &scalars,
+            &MsmMask::zero(),
+            &pp,
+            network.get_king(),
+            MultiplexedStreamID::Zero,
+        )
+        .await;
+
+        assert!(matches!(result, Err(MpcNetError::BadInput { .. })));
+    }
+
+    #[tokio::test]
+    async fn d_msm_to_king_only_reaches_the_king() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let fixture = msm_fixture::<G1P>(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (fixture.clone(), pp),
+                |net, (fixture, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    d_msm_to_king::<G1P, _>(
+                        &fixture.base_shares[idx],
+                        &fixture.scalar_shares[idx],
+                        &fixture.masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        assert_eq!(result[0], Some(fixture.expected));
+        assert!(result[1..].iter().all(Option::is_none));
+    }
+
+    #[tokio::test]
+    async fn d_msm_with_a_king_holding_no_share() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet_with_king(pp.n)
+            .await
+            .unwrap();
+
+        let fixture = msm_fixture::<G1P>(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (fixture.clone(), pp),
+                |net, (fixture, pp)| async move {
+                    // the king has no share and sums an empty msm
+                    let Some(idx) = net.party_index() else {
+                        return d_msm::<G1P, _>(
+                            &[],
+                            &[],
+                            &MsmMask::zero(),
+                            &pp,
+                            &net,
+                            MultiplexedStreamID::One,
+                        )
+                        .await
+                        .unwrap();
+                    };
+                    d_msm::<G1P, _>(
+                        &fixture.base_shares[idx],
+                        &fixture.scalar_shares[idx],
+                        &fixture.masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        assert_eq!(result[0], G1P::zero());
+        assert_eq!(pp.unpack(result[1..].to_vec()), vec![fixture.expected; L]);
+    }
+
+    #[test]
+    fn sample_for_party_matches_the_dealt_shares() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let dealt = MsmMask::<G1P>::sample(&pp, &mut StdRng::seed_from_u64(7));
+
+        for (id, mask) in dealt.iter().enumerate() {
+            let own = MsmMask::<G1P>::sample_for_party(id as u32, 7, &pp);
+            assert_eq!(
+                (own.in_mask, own.out_mask),
+                (mask.in_mask, mask.out_mask)
+            );
+        }
+        let other = MsmMask::<G1P>::sample_for_party(0, 8, &pp);
+        assert_ne!(other.in_mask, dealt[0].in_mask);
+    }
+
+    #[tokio::test]
+    async fn d_msm_with_jointly_sampled_mask() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let fixture = msm_fixture::<G1P>(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (fixture.clone(), pp),
+                |net, (fixture, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    let rng = &mut StdRng::seed_from_u64(idx as u64);
+                    let mask = MsmMask::<G1P>::jointly_sample(
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                        rng,
+                    )
+                    .await
+                    .unwrap();
+
+                    let output = d_msm::<G1P, _>(
+                        &fixture.base_shares[idx],
+                        &fixture.scalar_shares[idx],
+                        &mask,
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap();
+
+                    net.client_send_or_king_receive_serialized(
+                        &output,
+                        MultiplexedStreamID::One,
+                        pp.t,
+                    )
+                    .await
+                    .unwrap()
+                    .map(|rs| {
+                        (
+                            mask.in_mask.is_zero(),
+                            pp.unpack_missing_shares(&rs.shares, &rs.parties)
+                                [0],
+                        )
+                    })
+                },
+            )
+            .await;
+
+        let (mask_is_zero, output) = result[0].unwrap();
+        assert!(!mask_is_zero);
+        assert_eq!(output, fixture.expected);
+    }
+
+    #[tokio::test]
+    async fn d_msm_chunked_matches_msm() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let fixture = msm_fixture::<G1P>(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (fixture.clone(), pp),
+                |net, (fixture, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    // a chunk size that does not divide the number of bases
+                    let chunks = fixture.base_shares[idx]
+                        .chunks(5)
+                        .map(Ok::<_, MpcNetError>);
+                    let output = d_msm_chunked::<G1P, _, _, _>(
+                        chunks,
+                        &fixture.scalar_shares[idx],
+                        &fixture.masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap();
+
+                    net.client_send_or_king_receive_serialized(
+                        &output,
+                        MultiplexedStreamID::Zero,
+                        pp.t,
+                    )
+                    .await
+                    .unwrap()
+                    .map(|rs| {
+                        pp.unpack_missing_shares(&rs.shares, &rs.parties)[0]
+                    })
+                },
+            )
+            .await;
+
+        assert_eq!(result[0], Some(fixture.expected));
+    }
+
+    #[tokio::test]
+    async fn d_msm_meshed_matches_msm() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let fixture = msm_fixture::<G1P>(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (fixture.clone(), pp),
+                |net, (fixture, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    d_msm_meshed::<G1P, _>(
+                        &fixture.base_shares[idx],
+                        &fixture.scalar_shares[idx],
+                        &fixture.masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        // a repeated sharing of the msm
+        assert_eq!(pp.unpack(result), vec![fixture.expected; L]);
+    }
+
+    /// Party 2 is slow to answer the king and party 3 lies to it
+    fn with_faults(
+        net: InProcNet,
+        seed: u64,
+        sid: MultiplexedStreamID,
+    ) -> ChaosNet<InProcNet> {
+        let net = ChaosNet::new(net, seed);
+        match net.party_id() {
+            2 => {
+                let delay = Fault::Delay(Duration::from_millis(20));
+                net.with_fault(0, sid, delay, 1.0)
+            }
+            3 => net.with_fault(0, sid, Fault::Corrupt, 1.0),
+            _ => net,
+        }
+    }
+
+    #[tokio::test]
+    async fn d_msm_survives_or_rejects_faults() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let fixture = msm_fixture(&pp, rng);
+
+        for seed in 0..8 {
+            let result = InProcTestNet::new(pp.n)
+                .simulate_network_round(
+                    (fixture.clone(), pp),
+                    move |net, (fixture, pp)| async move {
+                        let sid = MultiplexedStreamID::One;
+                        let idx = net.party_id() as usize;
+                        let net = with_faults(net, seed, sid);
+                        d_msm::<G1P, _>(
+                            &fixture.base_shares[idx],
+                            &fixture.scalar_shares[idx],
+                            &fixture.masks[idx],
+                            &pp,
+                            &net,
+                            sid,
+                        )
+                        .await
+                    },
+                )
+                .await;
+
+            if result.iter().all(Result::is_ok) {
+                let shares = result.into_iter().map(Result::unwrap).collect();
+                assert_eq!(pp.unpack(shares), vec![fixture.expected; L]);
+            } else {
+                assert!(matches!(
+                    result[0],
+                    Err(MpcNetError::Protocol { party: 3, .. })
+                ));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn d_msm_sparse_matches_msm() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        // every third chunk of l scalars is zero
+        let scalars = (0..M)
+            .map(|i| match (i / L) % 3 {
+                0 => F::zero(),
+                _ => F::rand(rng),
+            })
+            .collect::<Vec<_>>();
+        let sparse = SparseScalars::from_public(&scalars, L);
+        assert_eq!(sparse.len(), (M / L).div_ceil(3));
+        let fixture = msm_fixture_with_scalars::<G1P>(scalars, &pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (fixture.clone(), sparse, pp),
+                |net, (fixture, sparse, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    let output = d_msm_sparse::<G1P, _>(
+                        &fixture.base_shares[idx],
+                        &fixture.scalar_shares[idx],
+                        &sparse,
+                        &fixture.masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap();
+
+                    net.client_send_or_king_receive_serialized(
+                        &output,
+                        MultiplexedStreamID::Zero,
+                        pp.t,
+                    )
+                    .await
+                    .unwrap()
+                    .map(|rs| {
+                        pp.unpack_missing_shares(&rs.shares, &rs.parties)[0]
+                    })
+                },
+            )
+            .await;
+
+        assert_eq!(result[0], Some(fixture.expected));
+    }
+
+    #[tokio::test]
+    async fn d_msm_recovers_from_dropped_party() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let fixture = msm_fixture::<G1P>(&pp, rng);
+
+        // the last party never sends its share to the king, who has to
+        // reconstruct from the others once the round times out
+        let result = network
+            .simulate_network_round(
+                (fixture.clone(), pp),
+                |net, (fixture, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    let output = if idx == pp.n - 1 {
+                        // the king answers through try_king_round, which
+                        // wraps every answer in an Option
+                        net.client_receive_or_king_send_serialized::<
+                            Option<G1P>,
+                        >(None, MultiplexedStreamID::One)
+                        .await
+                        .unwrap()
+                        .flatten()
+                        .unwrap()
+                            + fixture.masks[idx].out_mask
+                    } else {
+                        d_msm::<G1P, _>(
+                            &fixture.base_shares[idx],
+                            &fixture.scalar_shares[idx],
+                            &fixture.masks[idx],
+                            &pp,
+                            &net,
+                            MultiplexedStreamID::One,
+                        )
+                        .await
+                        .unwrap()
+                    };
+
+                    net.client_send_or_king_receive_serialized(
+                        &output,
+                        MultiplexedStreamID::Zero,
+                        pp.t,
+                    )
+                    .await
+                    .unwrap()
+                    .map(|rs| {
+                        pp.unpack_missing_shares(&rs.shares, &rs.parties)[0]
+                    })
+                },
+            )
+            .await;
+
+        assert_eq!(result[0], Some(fixture.expected));
+    }
+
+    #[tokio::test]
+    async fn d_msm_over_non_pairing_curve() {
+        use ark_secp256k1::{Fr, Projective};
+
+        let pp = PackedSharingParams::<Fr>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let fixture = msm_fixture::<Projective>(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (fixture.clone(), pp),
+                |net, (fixture, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    let output = d_msm::<Projective, _>(
+                        &fixture.base_shares[idx],
+                        &fixture.scalar_shares[idx],
+                        &fixture.masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap();
+
+                    net.client_send_or_king_receive_serialized(
+                        &output,
+                        MultiplexedStreamID::Zero,
+                        pp.t,
+                    )
+                    .await
+                    .unwrap()
+                    .map(|rs| {
+                        pp.unpack_missing_shares(&rs.shares, &rs.parties)[0]
+                    })
+                },
+            )
+            .await;
+
+        assert_eq!(result[0], Some(fixture.expected));
+    }
 }
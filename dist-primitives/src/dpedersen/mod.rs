@@ -0,0 +1,224 @@
+// Distributed Pedersen commitment
+// Commits to a shared message vector with a shared blinding, so the
+// commitment can be opened by nobody unless the parties reveal the blinding
+
+use crate::dmsm::{d_add_shares, MsmMask};
+use crate::utils::{share_index, PackedSharingParamsExt};
+use ark_ec::CurveGroup;
+use ark_ff::FftField;
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::{MpcNet, MpcNetError, MultiplexedStreamID};
+use secret_sharing::pss::PackedSharingParams;
+
+/// Samples a blinding for d_pedersen_commit jointly, without a dealer:
+/// every party deals a packing of l random values and adds up the shares it
+/// receives, so nobody knows the blinding as long as one party is honest.
+/// Returns this party's share. As for MsmMask::jointly_sample, net must
+/// connect every pair of parties.
+pub async fn jointly_sample_blinding<F: FftField, Net: MpcNet>(
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+    rng: &mut impl rand::Rng,
+) -> Result<F, MpcNetError> {
+    pp.assert_matches(net)?;
+    let own_id = net.party_id();
+    let own_index = share_index(net)?;
+    let dealt = pp.pack((0..pp.l).map(|_| F::rand(rng)).collect(), rng);
+
+    let send = async {
+        for (id, share) in net.party_ids().zip(&dealt) {
+            if id == own_id {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            share.serialize_compressed(&mut bytes)?;
+            net.send_to(id, bytes.into(), sid).await?;
+        }
+        Ok::<_, MpcNetError>(())
+    };
+
+    let recv = async {
+        let mut received = Vec::new();
+        for id in net.party_ids().filter(|id| *id != own_id) {
+            let bytes = net.recv_from(id, sid).await?;
+            let share =
+                F::deserialize_compressed(&bytes[..]).map_err(|err| {
+                    MpcNetError::Protocol {
+                        err: format!("Could not deserialize blinding: {err}"),
+                        party: id,
+                    }
+                })?;
+            received.push(share);
+        }
+        Ok::<_, MpcNetError>(received)
+    };
+
+    let (_, received) = tokio::try_join!(send, recv)?;
+
+    Ok(dealt[own_index] + received.iter().sum::<F>())
+}
+
+/// Computes shares of the Pedersen commitment sum(m_i * G_i) + r * h to the
+/// messages packed in msg_shares, with bases packed the same way as for
+/// d_msm. The blinding r is the sum of the l values packed in
+/// blinding_share, e.g from jointly_sample_blinding.
+/// Like d_msm's, the output stays a "repeated" packed sharing, and the king
+/// sees the unmasked coefficients of the product described in d_add_shares.
+/// A mask must not be reused across calls.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = msg_shares.len())
+)]
+pub async fn d_pedersen_commit<G: CurveGroup, Net: MpcSerNet>(
+    msg_shares: &[G::ScalarField],
+    blinding_share: G::ScalarField,
+    bases: &[G::Affine],
+    h: G::Affine,
+    msm_mask: &MsmMask<G>,
+    pp: &PackedSharingParams<G::ScalarField>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<G, MpcNetError> {
+    pp.assert_matches(net)?;
+
+    if bases.len() != msg_shares.len() {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "d_pedersen_commit: {} bases but {} messages",
+                bases.len(),
+                msg_shares.len()
+            ),
+        });
+    }
+    let c_share =
+        G::msm(bases, msg_shares).map_err(|_| MpcNetError::BadInput {
+            err: format!(
+                "d_pedersen_commit: msm of {} bases and {} messages failed",
+                bases.len(),
+                msg_shares.len()
+            ),
+        })?;
+    // blinding_share * h shares the l blindings times h with degree t, which
+    // the degree 2t sharing of the msm can absorb
+    let c_share = c_share + h * blinding_share;
+    d_add_shares(c_share, msm_mask, pp, net, sid).await
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::{Fr as F, G1Affine, G1Projective as G1P};
+    use ark_ec::VariableBaseMSM;
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use ark_std::UniformRand;
+    use mpc_net::ser_net::MpcSerNet;
+    use mpc_net::{LocalTestNet, MpcNet, MpcNetError, MultiplexedStreamID};
+    use secret_sharing::pss::PackedSharingParams;
+
+    use crate::dmsm::MsmMask;
+    use crate::dpedersen::{d_pedersen_commit, jointly_sample_blinding};
+    use crate::utils::pack::transpose;
+
+    const L: usize = 2;
+    const M: usize = 16;
+
+    #[tokio::test]
+    async fn d_pedersen_commit_opens_to_messages() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let bases = (0..M).map(|_| G1P::rand(rng)).collect::<Vec<_>>();
+        let msgs = (0..M).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let h = G1Affine::rand(rng);
+        let bases_aff: Vec<G1Affine> =
+            bases.iter().map(|s| (*s).into()).collect();
+        let msm = G1P::msm(&bases_aff, &msgs).unwrap();
+
+        let base_shares = transpose(
+            bases.chunks(L).map(|s| pp.pack(s.to_vec(), rng)).collect(),
+        );
+        let msg_shares = transpose(
+            msgs.chunks(L).map(|s| pp.pack(s.to_vec(), rng)).collect(),
+        );
+        let masks = MsmMask::<G1P>::sample(&pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (base_shares, msg_shares, masks, pp),
+                move |net, (base_shares, msg_shares, masks, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    let rng = &mut StdRng::seed_from_u64(idx as u64);
+                    let blinding = jointly_sample_blinding(
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                        rng,
+                    )
+                    .await
+                    .unwrap();
+
+                    let bases: Vec<G1Affine> =
+                        base_shares[idx].iter().map(|s| (*s).into()).collect();
+                    let commitment = d_pedersen_commit(
+                        &msg_shares[idx],
+                        blinding,
+                        &bases,
+                        h,
+                        &masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap();
+
+                    // open the commitment and the blinding to check them
+                    net.client_send_or_king_receive_serialized(
+                        &(commitment, blinding),
+                        MultiplexedStreamID::Zero,
+                        pp.t,
+                    )
+                    .await
+                    .unwrap()
+                    .map(|rs| {
+                        let (commitments, blindings): (Vec<_>, Vec<_>) =
+                            rs.shares.into_iter().unzip();
+                        (
+                            pp.unpack_missing_shares(&commitments, &rs.parties),
+                            pp.unpack_missing_shares(&blindings, &rs.parties)
+                                .iter()
+                                .sum::<F>(),
+                        )
+                    })
+                },
+            )
+            .await;
+
+        let (commitment, blinding) = result[0].clone().unwrap();
+        assert_ne!(blinding, F::from(0u32));
+        assert_eq!(commitment, vec![msm + h * blinding; L]);
+    }
+
+    #[tokio::test]
+    async fn d_pedersen_commit_rejects_length_mismatch() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let result = d_pedersen_commit::<G1P, _>(
+            &[F::rand(rng); 3],
+            F::rand(rng),
+            &[G1Affine::rand(rng); 2],
+            G1Affine::rand(rng),
+            &MsmMask::zero(),
+            &pp,
+            network.get_king(),
+            MultiplexedStreamID::Zero,
+        )
+        .await;
+
+        assert!(matches!(result, Err(MpcNetError::BadInput { .. })));
+    }
+}
@@ -0,0 +1,286 @@
+// Distributed Poseidon hash
+// Hashes packed shares of field elements without opening them: the linear
+// layers are local and every S-box multiplication goes through deg_red, so
+// the king only ever sees masked values
+
+use crate::utils::deg_red::{deg_red, DegRedMask};
+use crate::utils::pack::transpose_rows;
+use crate::utils::PackedSharingParamsExt;
+use ark_crypto_primitives::sponge::poseidon::PoseidonConfig;
+use ark_ff::PrimeField;
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::{MpcNetError, MultiplexedStreamID};
+use rand::Rng;
+use secret_sharing::pss::PackedSharingParams;
+
+/// Masks used in d_poseidon_hash
+/// Note that this only contains one share of the mask
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "zeroize",
+    derive(zeroize::Zeroize, zeroize::ZeroizeOnDrop),
+    zeroize(bound = "")
+)]
+pub struct PoseidonMask<F: PrimeField> {
+    /// One mask per multiplication of every S-box layer, in order
+    pub sbox_masks: Vec<DegRedMask<F, F>>,
+}
+
+impl<F: PrimeField> PoseidonMask<F> {
+    /// Samples the masks to hash n_inputs elements with params and returns
+    /// the shares of n parties
+    pub fn sample(
+        params: &PoseidonConfig<F>,
+        n_inputs: usize,
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Vec<Self> {
+        let width = params.rate + params.capacity;
+        let rows = (0..n_permutations(params, n_inputs))
+            .flat_map(|_| round_widths(params, width))
+            .flat_map(|num| {
+                (0..n_multiplications(params.alpha)).map(move |_| num)
+            })
+            .map(|num| DegRedMask::sample(pp, F::one(), num, rng))
+            .collect::<Vec<_>>();
+        let mut sbox_masks = transpose_rows(rows);
+        sbox_masks.resize(pp.n, Vec::new());
+
+        sbox_masks
+            .into_iter()
+            .map(|sbox_masks| Self { sbox_masks })
+            .collect()
+    }
+}
+
+/// Permutations of the sponge to absorb n_inputs elements and squeeze one
+fn n_permutations<F: PrimeField>(
+    params: &PoseidonConfig<F>,
+    n_inputs: usize,
+) -> usize {
+    n_inputs.div_ceil(params.rate).max(1)
+}
+
+/// Number of S-boxes of every round of a permutation
+fn round_widths<F: PrimeField>(
+    params: &PoseidonConfig<F>,
+    width: usize,
+) -> impl Iterator<Item = usize> {
+    let (full, partial) = (params.full_rounds, params.partial_rounds);
+    (0..full + partial).map(move |round| {
+        if round < full / 2 || round >= full / 2 + partial {
+            width
+        } else {
+            1
+        }
+    })
+}
+
+/// Multiplications of x^alpha by square and multiply
+fn n_multiplications(alpha: u64) -> usize {
+    let bits = (u64::BITS - alpha.leading_zeros()) as usize;
+    bits.saturating_sub(1) + (alpha.count_ones() as usize).saturating_sub(1)
+}
+
+/// Hashes the elements packed in input_shares with the Poseidon sponge of
+/// params, as ark_crypto_primitives' PoseidonSponge absorbing them all and
+/// squeezing one element. The l slots are hashed in parallel, the output
+/// packs the l hashes.
+/// Takes one deg_red round per multiplication of the S-boxes, e.g 5 per
+/// round for alpha = 17.
+/// A mask must not be reused across calls.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = input_shares.len())
+)]
+pub async fn d_poseidon_hash<F: PrimeField, Net: MpcSerNet>(
+    input_shares: &[F],
+    params: &PoseidonConfig<F>,
+    mask: &PoseidonMask<F>,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<F, MpcNetError> {
+    pp.assert_matches(net)?;
+    let width = params.rate + params.capacity;
+    if params.alpha == 0 || params.rate == 0 {
+        return Err(MpcNetError::BadInput {
+            err: "d_poseidon_hash: invalid Poseidon parameters".to_string(),
+        });
+    }
+    let n_masks = n_permutations(params, input_shares.len())
+        * (params.full_rounds + params.partial_rounds)
+        * n_multiplications(params.alpha);
+    if mask.sbox_masks.len() != n_masks {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "d_poseidon_hash: {} masks for {} inputs, expected {}",
+                mask.sbox_masks.len(),
+                input_shares.len(),
+                n_masks
+            ),
+        });
+    }
+
+    let mut masks = mask.sbox_masks.iter();
+    let mut state = vec![F::zero(); width];
+    for k in 0..n_permutations(params, input_shares.len()) {
+        let chunk = input_shares.chunks(params.rate).nth(k).unwrap_or(&[]);
+        for (s, x) in state[params.capacity..].iter_mut().zip(chunk) {
+            *s += x;
+        }
+
+        for (round, n_sboxes) in round_widths(params, width).enumerate() {
+            // a constant packed in every slot is shared as itself
+            for (s, c) in state.iter_mut().zip(&params.ark[round]) {
+                *s += c;
+            }
+
+            // x^alpha, from the most significant bit of alpha down
+            let x = state[..n_sboxes].to_vec();
+            let mut acc = x.clone();
+            for i in (0..u64::BITS - 1 - params.alpha.leading_zeros()).rev() {
+                acc = d_mul(&acc, &acc, masks.next(), pp, net, sid).await?;
+                if params.alpha >> i & 1 == 1 {
+                    acc = d_mul(&acc, &x, masks.next(), pp, net, sid).await?;
+                }
+            }
+            state[..n_sboxes].copy_from_slice(&acc);
+
+            state = params
+                .mds
+                .iter()
+                .map(|row| row.iter().zip(&state).map(|(m, s)| *m * s).sum())
+                .collect();
+        }
+    }
+
+    Ok(state[params.capacity])
+}
+
+/// Slot-wise product of two vectors of shares
+async fn d_mul<F: PrimeField, Net: MpcSerNet>(
+    a: &[F],
+    b: &[F],
+    mask: Option<&DegRedMask<F, F>>,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Vec<F>, MpcNetError> {
+    let mask = mask.expect("the number of masks is checked upfront");
+    let products = a.iter().zip(b).map(|(a, b)| *a * b).collect();
+    deg_red(products, mask, pp, net, sid).await
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_crypto_primitives::sponge::poseidon::{
+        find_poseidon_ark_and_mds, PoseidonConfig, PoseidonSponge,
+    };
+    use ark_crypto_primitives::sponge::{
+        CryptographicSponge, FieldBasedCryptographicSponge,
+    };
+    use ark_ff::PrimeField;
+    use ark_std::UniformRand;
+    use mpc_net::{LocalTestNet, MpcNet, MpcNetError, MultiplexedStreamID};
+    use secret_sharing::pss::PackedSharingParams;
+
+    use crate::dposeidon::{d_poseidon_hash, PoseidonMask};
+    use crate::utils::pack::transpose;
+
+    const L: usize = 2;
+    const N_INPUTS: usize = 3;
+
+    /// Fewer partial rounds than a secure config, to keep the test fast
+    fn poseidon_config() -> PoseidonConfig<F> {
+        let (rate, full_rounds, partial_rounds) = (2, 8, 7);
+        let (ark, mds) = find_poseidon_ark_and_mds::<F>(
+            F::MODULUS_BIT_SIZE as u64,
+            rate,
+            full_rounds,
+            partial_rounds,
+            0,
+        );
+        PoseidonConfig::new(
+            full_rounds as usize,
+            partial_rounds as usize,
+            17,
+            mds,
+            ark,
+            rate,
+            1,
+        )
+    }
+
+    #[tokio::test]
+    async fn d_poseidon_hash_matches_sponge() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let params = poseidon_config();
+
+        // inputs[k] are the inputs hashed in slot k
+        let inputs = (0..L)
+            .map(|_| (0..N_INPUTS).map(|_| F::rand(rng)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let input_shares = transpose(
+            transpose(inputs.clone())
+                .into_iter()
+                .map(|input| pp.pack(input, rng))
+                .collect(),
+        );
+        let masks = PoseidonMask::sample(&params, N_INPUTS, &pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (input_shares, masks, params.clone(), pp),
+                |net, (input_shares, masks, params, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    d_poseidon_hash(
+                        &input_shares[idx],
+                        &params,
+                        &masks[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        let expected = inputs
+            .iter()
+            .map(|input| {
+                let mut sponge = PoseidonSponge::new(&params);
+                sponge.absorb(input);
+                sponge.squeeze_native_field_elements(1)[0]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(pp.unpack(result), expected);
+    }
+
+    #[tokio::test]
+    async fn d_poseidon_hash_rejects_mask_for_other_length() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let params = poseidon_config();
+        let masks = PoseidonMask::sample(&params, N_INPUTS, &pp, rng);
+
+        // 5 inputs take a permutation more than 3
+        let result = d_poseidon_hash(
+            &[F::rand(rng); 5],
+            &params,
+            &masks[0],
+            &pp,
+            network.get_king(),
+            MultiplexedStreamID::Zero,
+        )
+        .await;
+        assert!(matches!(result, Err(MpcNetError::BadInput { .. })));
+    }
+}
@@ -3,7 +3,8 @@
 
 use crate::utils::{
     deg_red::{deg_red, DegRedMask},
-    pack::{pack_vec, transpose},
+    king::king_repack,
+    PackedSharingParamsExt,
 };
 use ark_ff::{FftField, Field, PrimeField};
 use mpc_net::ser_net::MpcSerNet;
@@ -12,6 +13,13 @@ use secret_sharing::pss::PackedSharingParams;
 
 // Given pre-processed randomness [s], [s^-1]
 // Partial products of [num] and [den] are computed
+// num and den hold the m/l packed shares of m values each, packed as in
+// pack_vec, so they must have the same length
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = num.len())
+)]
 pub async fn d_pp<F: FftField + PrimeField + Field, Net: MpcSerNet>(
     num: Vec<F>,
     den: Vec<F>,
@@ -20,6 +28,17 @@ pub async fn d_pp<F: FftField + PrimeField + Field, Net: MpcSerNet>(
     net: &Net,
     sid: MultiplexedStreamID,
 ) -> Result<Vec<F>, MpcNetError> {
+    pp.assert_matches(net)?;
+    if num.len() != den.len() {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "d_pp: {} shares of num but {} of den",
+                num.len(),
+                den.len()
+            ),
+        });
+    }
+
     // TODO: replace with good randomness
     // using some dummy randomness
     let s = F::from(1_u32);
@@ -34,54 +53,75 @@ pub async fn d_pp<F: FftField + PrimeField + Field, Net: MpcSerNet>(
 
     // Along with degree reduction
     // King recovers secrets, computes partial products and repacks
-    let received_shares = net
-        .client_send_or_king_receive_serialized(&numden_rand, sid, pp.t)
-        .await?;
+    let mut pp_numden_rand = king_repack(
+        &numden_rand,
+        |mut numden| {
+            for i in 0..numden.len() / 2 {
+                let den = numden[i + numden.len() / 2].inverse().unwrap();
+                numden[i] *= den;
+            }
 
-    let king_answer: Option<Vec<Vec<F>>> = received_shares.map(|rs| {
-        // nx(m/l) -> (m/l)xn
-        debug_assert_eq!(rs.shares.len(), pp.n, "Mismatch of size in d_pp");
-        let numden_shares = transpose(rs.shares);
+            numden.truncate(numden.len() / 2);
 
-        // Unpack the secrets
-        // (m/l)xn -> m
-        // iterate over pxss_shares, unpack to get a vector and append all the vectors
-        let mut numden: Vec<F> = numden_shares
-            .into_iter()
-            .flat_map(|x| pp.unpack_missing_shares(&x, &rs.parties))
-            .collect();
+            // Compute the partial products across pxss
+            for i in 1..numden.len() {
+                let last = numden[i - 1];
+                numden[i] *= last;
+            }
+            numden
+        },
+        pp,
+        net,
+        sid,
+    )
+    .await?;
 
-        for i in 0..numden.len() / 2 {
-            let den = numden[i + numden.len() / 2].inverse().unwrap();
-            numden[i] *= den;
-        }
+    // Finally, remove the ranomness in the partial products
+    // multiply all entries of pp_pxss by of s
+    // do degree reduction
+    pp_numden_rand.iter_mut().for_each(|x| *x *= sinv);
+    deg_red(pp_numden_rand, degred_mask, pp, net, sid).await //packed shares of partial products
+}
 
-        numden.truncate(numden.len() / 2);
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_std::UniformRand;
+    use mpc_net::{LocalTestNet, MpcNetError, MultiplexedStreamID};
+    use secret_sharing::pss::PackedSharingParams;
 
-        // Compute the partial products across pxss
-        for i in 1..numden.len() {
-            let last = numden[i - 1];
-            numden[i] *= last;
-        }
+    use crate::dpp::d_pp;
+    use crate::utils::deg_red::DegRedMask;
 
-        // Pack the secrets
-        // m -> (m/l)xn
-        // (m/l)xl -> (m/l)xn
-        let pp_numden_shares = pack_vec(&numden, pp);
-        drop(numden);
+    const L: usize = 2;
 
-        // send shares to parties
-        // (m/l)xn -> nx(m/l)
-        transpose(pp_numden_shares)
-    });
+    #[tokio::test]
+    async fn d_pp_rejects_mismatched_lengths() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let num = (0..4).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let den = num[..3].to_vec();
 
-    let mut pp_numden_rand = net
-        .client_receive_or_king_send_serialized(king_answer, sid)
-        .await?;
+        let result = network
+            .simulate_network_round(
+                (num, den, pp),
+                |net, (num, den, pp)| async move {
+                    d_pp(
+                        num,
+                        den,
+                        &DegRedMask::zero(4),
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                },
+            )
+            .await;
 
-    // Finally, remove the ranomness in the partial products
-    // multiply all entries of pp_pxss by of s
-    // do degree reduction
-    pp_numden_rand.iter_mut().for_each(|x| *x *= sinv);
-    deg_red(pp_numden_rand, degred_mask, pp, net, sid).await //packed shares of partial products
+        assert!(result
+            .iter()
+            .all(|r| matches!(r, Err(MpcNetError::BadInput { .. }))));
+    }
 }
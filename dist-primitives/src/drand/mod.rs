@@ -0,0 +1,133 @@
+// Distributed randomness beacon
+// Every party commits to a random value, then reveals it, and the output is
+// the sum of the revealed values, so it is uniformly random as long as one
+// contribution is
+
+use ark_ff::FftField;
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::{MpcNetError, MultiplexedStreamID};
+use rand::Rng;
+use secret_sharing::pss::PackedSharingParams;
+use sha2::{Digest, Sha256};
+
+/// Jointly samples a random field element, without a dealer
+/// Takes two rounds: every party first commits to its contribution, then
+/// reveals it. The king forwards the commitments and the openings it received
+/// to everyone, and every party checks the openings and sums them itself, so
+/// no party can pick its contribution after seeing the others'.
+/// The king picks which t + 1 or more parties contribute, before seeing any
+/// contribution. A party that commits and does not reveal makes the call fail.
+/// The output is public: use it for public coins, not for values that must
+/// stay secret (see MsmMask::jointly_sample for those).
+pub async fn d_rand<F: FftField, Net: MpcSerNet>(
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+    rng: &mut impl Rng,
+) -> Result<F, MpcNetError> {
+    let contribution = F::rand(rng);
+    let salt: [u8; 32] = rng.gen();
+
+    let commitments: Vec<(u32, [u8; 32])> = net
+        .king_broadcast_round(
+            &commit(&contribution, &salt),
+            |rs| Ok(rs.parties.into_iter().zip(rs.shares).collect()),
+            sid,
+            pp.t,
+        )
+        .await?;
+    let openings: Vec<(u32, (F, [u8; 32]))> = net
+        .king_broadcast_round(
+            &(contribution, salt),
+            |rs| Ok(rs.parties.into_iter().zip(rs.shares).collect()),
+            sid,
+            pp.t,
+        )
+        .await?;
+
+    let mut sum = F::zero();
+    for (party, commitment) in commitments {
+        let opened = openings
+            .iter()
+            .find(|(id, _)| *id == party)
+            .filter(|(_, (value, salt))| commit(value, salt) == commitment);
+        match opened {
+            Some((_, (value, _))) => sum += value,
+            None => {
+                return Err(MpcNetError::Protocol {
+                    err: "d_rand: contribution not opened".to_string(),
+                    party,
+                })
+            }
+        }
+    }
+    Ok(sum)
+}
+
+fn commit<F: FftField>(value: &F, salt: &[u8; 32]) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    value
+        .serialize_compressed(&mut bytes)
+        .expect("serializing into a vec cannot fail");
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use ark_std::UniformRand;
+    use mpc_net::{LocalTestNet, MpcNet, MultiplexedStreamID};
+    use secret_sharing::pss::PackedSharingParams;
+
+    use crate::drand::d_rand;
+
+    const L: usize = 2;
+
+    #[tokio::test]
+    async fn d_rand_sums_the_contributions() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let result = network
+            .simulate_network_round(pp, |net, pp| async move {
+                let rng = &mut StdRng::seed_from_u64(net.party_id() as u64);
+                d_rand(&pp, &net, MultiplexedStreamID::Zero, rng)
+                    .await
+                    .unwrap()
+            })
+            .await;
+
+        assert!(result.iter().all(|r| *r == result[0]));
+        let expected: F = (0..pp.n as u64)
+            .map(|id| F::rand(&mut StdRng::seed_from_u64(id)))
+            .sum();
+        assert_eq!(result[0], expected);
+    }
+
+    #[tokio::test]
+    async fn d_rand_with_a_king_holding_no_share() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let network = LocalTestNet::new_local_testnet_with_king(pp.n)
+            .await
+            .unwrap();
+
+        let result = network
+            .simulate_network_round(pp, |net, pp| async move {
+                let rng = &mut StdRng::seed_from_u64(net.party_id() as u64);
+                d_rand(&pp, &net, MultiplexedStreamID::Zero, rng)
+                    .await
+                    .unwrap()
+            })
+            .await;
+
+        // node 0 is the king, it sends no contribution
+        let expected: F = (1..=pp.n as u64)
+            .map(|id| F::rand(&mut StdRng::seed_from_u64(id)))
+            .sum();
+        assert!(result.iter().all(|r| *r == expected));
+    }
+}
@@ -0,0 +1,144 @@
+// Evaluating a distributed reduction over packed secrets
+// Given packed shares of x1, x2, .., xl, output repeated shares of x1 op x2 op .. op xl
+
+use ark_ff::FftField;
+use ark_poly::domain::DomainCoeff;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::{MpcNetError, MultiplexedStreamID};
+use secret_sharing::pss::PackedSharingParams;
+
+/// Reduces the secrets packed in `share` with `op` with the help of king
+/// The king reconstructs the l secrets, folds them with `op` and sends the result back.
+/// At the end all parties hold a "repeated" packed secret sharing of the output
+/// i.e equivalent to pp.pack(vec![output; pp.l])
+/// The king aborts the round if the shares do not lie on a polynomial of degree 2(t+l-1).
+/// Note that the king learns the output, callers are expected to mask the input if required
+pub async fn d_reduce<
+    F: FftField,
+    T: DomainCoeff<F> + CanonicalSerialize + CanonicalDeserialize + Default,
+    Net: MpcSerNet,
+>(
+    share: T,
+    op: impl Fn(T, T) -> T + Send,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<T, MpcNetError> {
+    let n_parties = net.n_parties();
+    let degree = 2 * (pp.t + pp.l - 1);
+    net.try_king_round(
+        &share,
+        |rs| {
+            let result = pp
+                .unpack_missing_shares_checked(&rs.shares, &rs.parties, degree)
+                .map_err(|err| MpcNetError::Protocol {
+                    err: format!("d_reduce: {err}"),
+                    party: net.king_id(),
+                })?;
+            let output = result
+                .into_iter()
+                .reduce(op)
+                .expect("unpacked at least one secret");
+            Ok(vec![output; n_parties])
+        },
+        sid,
+        pp.t,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_std::UniformRand;
+    use mpc_net::{LocalTestNet, MpcNet, MpcNetError, MultiplexedStreamID};
+    use secret_sharing::pss::PackedSharingParams;
+
+    use crate::dreduce::d_reduce;
+
+    const L: usize = 2;
+
+    #[tokio::test]
+    async fn d_reduce_works() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let secrets: [F; L] = UniformRand::rand(rng);
+        let secrets = secrets.to_vec();
+        // squaring the shares doubles the degree of the sharing
+        let expected_sum: F = secrets.iter().map(|x| x * x).sum();
+        let expected_prod: F = secrets.iter().product();
+
+        let shares = pp.pack(secrets, rng);
+
+        let result = network
+            .simulate_network_round(
+                (shares, pp),
+                |net, (shares, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    let sum = d_reduce(
+                        shares[idx] * shares[idx],
+                        |a, b| a + b,
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap();
+                    let prod = d_reduce(
+                        shares[idx],
+                        |a, b| a * b,
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap();
+                    (sum, prod)
+                },
+            )
+            .await;
+
+        for (sum, prod) in result {
+            assert_eq!(sum, expected_sum);
+            assert_eq!(prod, expected_prod);
+        }
+    }
+
+    #[tokio::test]
+    async fn d_reduce_rejects_a_wrong_share() {
+        const LIAR: usize = 3;
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let secrets: [F; L] = UniformRand::rand(rng);
+        let shares = pp.pack(secrets.to_vec(), rng);
+
+        let result = network
+            .simulate_network_round(
+                (shares, pp),
+                |net, (shares, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    // a well-formed share, off the polynomial of the others
+                    let share = match idx {
+                        LIAR => shares[idx] + F::from(1u64),
+                        _ => shares[idx],
+                    };
+                    d_reduce(
+                        share * share,
+                        |a, b| a + b,
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                },
+            )
+            .await;
+
+        for r in result {
+            assert!(matches!(r, Err(MpcNetError::Protocol { party: 0, .. })));
+        }
+    }
+}
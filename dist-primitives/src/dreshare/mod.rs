@@ -0,0 +1,139 @@
+// Distributed resharing between two committees of the same size
+// Party i of one committee hands its packed shares to party i of the other,
+// rerandomized with a packed sharing of zeros, so the receiving committee
+// holds a fresh sharing of the same secrets
+
+use crate::utils::pack::transpose;
+use ark_ff::FftField;
+use ark_poly::domain::DomainCoeff;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::UniformRand;
+use mpc_net::{MpcNet, MpcNetError, MultiplexedStreamID};
+use rand::Rng;
+use secret_sharing::pss::PackedSharingParams;
+use std::ops::Add;
+
+/// Samples masks for d_reshare_send and returns the shares of n parties
+/// Each party gets num shares, each of a packing of l zeros
+pub fn sample_reshare_mask<F: FftField, T: DomainCoeff<F> + UniformRand>(
+    num: usize,
+    pp: &PackedSharingParams<F>,
+    rng: &mut impl Rng,
+) -> Vec<Vec<T>> {
+    if num == 0 {
+        return vec![Vec::new(); pp.n];
+    }
+    transpose(
+        (0..num)
+            .map(|_| pp.pack(vec![T::zero(); pp.l], rng))
+            .collect(),
+    )
+}
+
+/// Sends this party's shares, masked with mask (see sample_reshare_mask),
+/// to party `to` of the receiving committee, which calls d_reshare_recv.
+/// `to` is an id of net, e.g SubNet::inner_id for committees of a SubNet.
+/// A mask must not be reused across calls.
+pub async fn d_reshare_send<
+    T: CanonicalSerialize + Copy + Add<Output = T>,
+    Net: MpcNet,
+>(
+    share: &[T],
+    mask: &[T],
+    to: u32,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<(), MpcNetError> {
+    if share.len() != mask.len() {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "d_reshare_send: {} shares but {} masks",
+                share.len(),
+                mask.len()
+            ),
+        });
+    }
+    let masked = share
+        .iter()
+        .zip(mask)
+        .map(|(s, m)| *s + *m)
+        .collect::<Vec<_>>();
+    let mut bytes = Vec::new();
+    masked.serialize_compressed(&mut bytes)?;
+    net.send_to(to, bytes.into(), sid).await
+}
+
+/// Receives the shares sent by party `from` of the other committee with
+/// d_reshare_send
+pub async fn d_reshare_recv<T: CanonicalDeserialize, Net: MpcNet>(
+    from: u32,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Vec<T>, MpcNetError> {
+    let bytes = net.recv_from(from, sid).await?;
+    Vec::<T>::deserialize_compressed(&bytes[..]).map_err(|err| {
+        MpcNetError::Protocol {
+            err: format!("d_reshare_recv: {}", err),
+            party: from,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_std::UniformRand;
+    use mpc_net::inproc::InProcTestNet;
+    use mpc_net::{MpcNet, MultiplexedStreamID};
+    use secret_sharing::pss::PackedSharingParams;
+
+    use crate::dreshare::{
+        d_reshare_recv, d_reshare_send, sample_reshare_mask,
+    };
+    use crate::utils::pack::{pack_vec, transpose};
+
+    const L: usize = 2;
+
+    #[tokio::test]
+    async fn d_reshare_works() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let secrets = (0..4 * L).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let shares = transpose(pack_vec(&secrets, &pp));
+        let masks = sample_reshare_mask::<F, F>(4, &pp, rng);
+
+        // parties 0..n send to n..2n
+        let testnet = InProcTestNet::new(2 * pp.n);
+        let result = testnet
+            .simulate_network_round(
+                (shares.clone(), masks, pp),
+                |net, (shares, masks, pp)| async move {
+                    let id = net.party_id() as usize;
+                    let sid = MultiplexedStreamID::One;
+                    if id < pp.n {
+                        let to = (id + pp.n) as u32;
+                        d_reshare_send(&shares[id], &masks[id], to, &net, sid)
+                            .await
+                            .unwrap();
+                        None
+                    } else {
+                        let from = (id - pp.n) as u32;
+                        Some(d_reshare_recv::<F, _>(from, &net, sid).await)
+                    }
+                },
+            )
+            .await;
+
+        let reshared = result
+            .into_iter()
+            .skip(pp.n)
+            .map(|r| r.unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_ne!(reshared, shares);
+        let unpacked = transpose(reshared)
+            .into_iter()
+            .flat_map(|column| pp.unpack(column))
+            .collect::<Vec<_>>();
+        assert_eq!(unpacked, secrets);
+    }
+}
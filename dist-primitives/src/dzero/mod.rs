@@ -0,0 +1,288 @@
+// Distributed zero test
+// Given packed shares of x1, x2, .., xl, output whether all the xi are zero without revealing them
+
+use crate::utils::deg_red::{deg_red, DegRedMask};
+use crate::utils::king::king_unpack_fresh;
+use ark_ff::FftField;
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::{MpcNetError, MultiplexedStreamID};
+use rand::Rng;
+use secret_sharing::pss::PackedSharingParams;
+
+/// Masks used in d_is_zero
+/// Note that this only contains one share of the mask
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "zeroize",
+    derive(zeroize::Zeroize, zeroize::ZeroizeOnDrop),
+    zeroize(bound = "")
+)]
+pub struct ZeroMask<F: FftField> {
+    /// Packs l uniformly random secrets, unknown to everyone
+    pub mask: F,
+    /// Reduces the degree of the product of the share and mask
+    pub degred_mask: DegRedMask<F, F>,
+}
+
+impl<F: FftField> ZeroMask<F> {
+    /// Samples masks for d_is_zero and returns the shares of n parties
+    pub fn sample(
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Vec<Self> {
+        let masks = pp.pack((0..pp.l).map(|_| F::rand(rng)).collect(), rng);
+
+        masks
+            .into_iter()
+            .zip(DegRedMask::sample(pp, F::one(), 1, rng))
+            .map(|(mask, degred_mask)| Self { mask, degred_mask })
+            .collect()
+    }
+}
+
+/// Checks that all the secrets packed in `share` are zero
+/// The parties multiply share by mask.mask and reduce the degree of the
+/// product with deg_red, so that the king opens a sharing of degree t+l-1
+/// of x*r, which is zero iff x is zero (except with probability 1/|F|)
+/// and otherwise uniformly random, so nothing else about x is revealed.
+/// Takes two rounds.
+/// A mask must not be reused across calls.
+pub async fn d_is_zero<F: FftField, Net: MpcSerNet>(
+    share: F,
+    mask: &ZeroMask<F>,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<bool, MpcNetError> {
+    let masked =
+        deg_red(vec![share * mask.mask], &mask.degred_mask, pp, net, sid)
+            .await?;
+
+    net.king_broadcast_round(
+        &masked[0],
+        |rs| {
+            let is_zero =
+                king_unpack_fresh(&rs, pp, net.king_id(), "d_is_zero")?
+                    .iter()
+                    .all(|x| x.is_zero());
+            Ok(is_zero)
+        },
+        sid,
+        pp.t,
+    )
+    .await
+}
+
+/// Checks that share_a and share_b are shares of packings of the same
+/// secrets, e.g to catch shares mixed up between parties while debugging.
+/// Fails if the king gets every share but they do not form a sharing.
+/// The king opens the difference of the sharings, which is zero when they
+/// match: use d_is_zero on the difference instead to keep it hidden when
+/// they might not.
+pub async fn d_assert_equal<F: FftField, Net: MpcSerNet>(
+    share_a: F,
+    share_b: F,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<(), MpcNetError> {
+    let equal = net
+        .king_broadcast_round(
+            &(share_a - share_b),
+            |rs| {
+                // a difference that is not even a sharing (e.g shares
+                // swapped between parties) also fails the degree check
+                let diff = if rs.parties.len() == pp.n {
+                    pp.try_unpack2(rs.shares).ok()
+                } else {
+                    Some(pp.lagrange_unpack(&rs.shares, &rs.parties))
+                };
+                let equal =
+                    diff.is_some_and(|diff| diff.iter().all(|x| x.is_zero()));
+                Ok(equal)
+            },
+            sid,
+            pp.t,
+        )
+        .await?;
+
+    if !equal {
+        return Err(MpcNetError::BadInput {
+            err: "d_assert_equal: the sharings hold different secrets"
+                .to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_std::{UniformRand, Zero};
+    use mpc_net::{LocalTestNet, MpcNet, MpcNetError, MultiplexedStreamID};
+    use secret_sharing::pss::PackedSharingParams;
+
+    use crate::dzero::{d_assert_equal, d_is_zero, ZeroMask};
+
+    const L: usize = 2;
+
+    #[tokio::test]
+    async fn d_is_zero_works() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let zero_shares = pp.pack(vec![F::zero(); L], rng);
+        let nonzero_shares = pp.pack(vec![F::zero(), F::rand(rng)], rng);
+        let masks = (0..2)
+            .map(|_| ZeroMask::sample(&pp, rng))
+            .collect::<Vec<_>>();
+
+        let result = network
+            .simulate_network_round(
+                (zero_shares, nonzero_shares, masks, pp),
+                |net, (zero_shares, nonzero_shares, masks, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    let zero = d_is_zero(
+                        zero_shares[idx],
+                        &masks[0][idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap();
+                    let nonzero = d_is_zero(
+                        nonzero_shares[idx],
+                        &masks[1][idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap();
+                    (zero, nonzero)
+                },
+            )
+            .await;
+
+        for (zero, nonzero) in result {
+            assert!(zero);
+            assert!(!nonzero);
+        }
+    }
+
+    #[tokio::test]
+    async fn d_assert_equal_catches_mixed_up_shares() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+
+        let secrets = (0..L).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let a_shares = pp.pack(secrets.clone(), rng);
+        let b_shares = pp.pack(secrets, rng);
+        // a party picking its share with the wrong index
+        let mut mixed_up = b_shares.clone();
+        mixed_up.swap(1, 2);
+
+        let result = network
+            .simulate_network_round(
+                (a_shares, b_shares, mixed_up, pp),
+                |net, (a_shares, b_shares, mixed_up, pp)| async move {
+                    let idx = net.party_id() as usize;
+                    let sid = MultiplexedStreamID::Zero;
+                    let equal = d_assert_equal(
+                        a_shares[idx],
+                        b_shares[idx],
+                        &pp,
+                        &net,
+                        sid,
+                    )
+                    .await;
+                    let mixed = d_assert_equal(
+                        a_shares[idx],
+                        mixed_up[idx],
+                        &pp,
+                        &net,
+                        sid,
+                    )
+                    .await;
+                    (equal.is_ok(), mixed)
+                },
+            )
+            .await;
+
+        for (equal, mixed) in result {
+            assert!(equal);
+            assert!(matches!(mixed, Err(MpcNetError::BadInput { .. })));
+        }
+    }
+
+    #[tokio::test]
+    async fn public_outputs_reach_a_king_holding_no_share() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet_with_king(pp.n)
+            .await
+            .unwrap();
+
+        let zero_shares = pp.pack(vec![F::zero(); L], rng);
+        let nonzero_shares = pp.pack(vec![F::zero(), F::rand(rng)], rng);
+        let other_nonzero_shares = pp.pack(vec![F::zero(), F::rand(rng)], rng);
+        let masks = (0..2)
+            .map(|_| ZeroMask::sample(&pp, rng))
+            .collect::<Vec<_>>();
+
+        let result = network
+            .simulate_network_round(
+                (zero_shares, nonzero_shares, other_nonzero_shares, masks, pp),
+                |net, (zero, nonzero, other, masks, pp)| async move {
+                    // the king sends nothing, any share and mask do
+                    let idx = net.party_index().unwrap_or(0);
+                    let is_zero = d_is_zero(
+                        zero[idx],
+                        &masks[0][idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap();
+                    let is_nonzero_zero = d_is_zero(
+                        nonzero[idx],
+                        &masks[1][idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::One,
+                    )
+                    .await
+                    .unwrap();
+                    let equal = d_assert_equal(
+                        nonzero[idx],
+                        nonzero[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Two,
+                    )
+                    .await;
+                    let different = d_assert_equal(
+                        nonzero[idx],
+                        other[idx],
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Two,
+                    )
+                    .await;
+                    (is_zero, is_nonzero_zero, equal.is_ok(), different)
+                },
+            )
+            .await;
+
+        for (is_zero, is_nonzero_zero, equal, different) in result {
+            assert!(is_zero);
+            assert!(!is_nonzero_zero);
+            assert!(equal);
+            assert!(matches!(different, Err(MpcNetError::BadInput { .. })));
+        }
+    }
+}
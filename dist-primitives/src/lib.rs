@@ -1,9 +1,35 @@
+//! Distributed primitives over packed secret shares, run by the parties of
+//! an mpc_net network with the help of a king.
+//!
+//! # Insecure masks
+//!
+//! Some masks have constructors for debugging only, which must not be used
+//! with secret inputs:
+//! - the `zero` masks of [`FftMask`](dfft::FftMask),
+//!   [`DegRedMask`](utils::deg_red::DegRedMask) and
+//!   [`MsmMask`](dmsm::MsmMask) leave the values the king opens unmasked,
+//!   so the king sees them;
+//! - the `sample_for_party` masks are dealt from a shared seed, so whoever
+//!   knows the seed knows the mask.
 #![allow(clippy::too_many_arguments)]
+pub mod dbits;
+pub mod dcommit;
+pub mod deval;
 pub mod dfft;
 pub mod dmsm;
+pub mod dpedersen;
+pub mod dposeidon;
 pub mod dpp;
+pub mod drand;
+pub mod dreduce;
+pub mod dreshare;
+pub mod dzero;
+pub mod transcript;
 pub mod utils;
 
+use mpc_net::multi::MuxConfig;
+use mpc_net::prod::NetworkConfig;
+use mpc_net::MpcNetError;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -13,7 +39,7 @@ pub struct Opt {
     /// Id
     pub id: usize,
 
-    /// Input file
+    /// Input file, the address of every party, one per line
     #[structopt(parse(from_os_str))]
     pub input: PathBuf,
 
@@ -26,3 +52,24 @@ pub struct Opt {
     /// FFT size
     pub m: usize,
 }
+
+impl Opt {
+    /// Reads the peer file into a NetworkConfig, see ProdNet::from_config
+    pub fn network_config(&self) -> Result<NetworkConfig, MpcNetError> {
+        let peers = std::fs::read_to_string(&self.input)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| line.parse())
+            .collect::<Result<_, _>>()?;
+
+        Ok(NetworkConfig {
+            id: self.id as u32,
+            king_id: 0,
+            l: self.l,
+            t: self.t,
+            peers,
+            mux: MuxConfig::default(),
+        })
+    }
+}
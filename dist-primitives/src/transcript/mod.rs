@@ -0,0 +1,115 @@
+// Distributed Fiat-Shamir transcript
+// The king absorbs the commitments, derives the next state and broadcasts it,
+// so that every party draws the same challenges
+
+use ark_ff::PrimeField;
+use ark_serialize::CanonicalSerialize;
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::{MpcNetError, MultiplexedStreamID};
+use sha2::{Digest, Sha256};
+
+/// Running hash of everything absorbed so far
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transcript {
+    state: Vec<u8>,
+}
+
+impl Transcript {
+    /// domain_sep separates transcripts of different protocols
+    pub fn new(domain_sep: &[u8]) -> Self {
+        Self {
+            state: Sha256::digest(domain_sep).to_vec(),
+        }
+    }
+
+    /// Absorbs label and commitments and derives a challenge, without any network
+    /// Used by the king, and by a verifier replaying the transcript
+    pub fn challenge<F: PrimeField, C: CanonicalSerialize>(
+        &mut self,
+        label: &[u8],
+        commitments: &C,
+    ) -> F {
+        let mut bytes = Vec::new();
+        commitments
+            .serialize_compressed(&mut bytes)
+            .expect("serializing into a vec cannot fail");
+
+        let mut hasher = Sha256::new();
+        hasher.update(&self.state);
+        hasher.update((label.len() as u64).to_le_bytes());
+        hasher.update(label);
+        hasher.update(&bytes);
+        self.state = hasher.finalize().to_vec();
+
+        F::from_le_bytes_mod_order(&self.state)
+    }
+
+    /// Draws the next challenge, agreed upon by all parties
+    /// Only the king's commitments are absorbed, the others can pass anything
+    /// (e.g the value they received from the king) since the king broadcasts the new state.
+    pub async fn draw_challenge<
+        F: PrimeField,
+        C: CanonicalSerialize,
+        Net: MpcSerNet,
+    >(
+        &mut self,
+        net: &Net,
+        label: &[u8],
+        commitments: &C,
+        sid: MultiplexedStreamID,
+    ) -> Result<F, MpcNetError> {
+        let king_answer = if net.is_king() {
+            self.challenge::<F, C>(label, commitments);
+            Some(vec![self.state.clone(); net.n_parties()])
+        } else {
+            None
+        };
+
+        // a king holding no share keeps the state it computed
+        if let Some(state) = net
+            .client_receive_or_king_send_serialized(king_answer, sid)
+            .await?
+        {
+            self.state = state;
+        }
+
+        Ok(F::from_le_bytes_mod_order(&self.state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use mpc_net::{LocalTestNet, MpcNet, MultiplexedStreamID};
+
+    use crate::transcript::Transcript;
+
+    #[tokio::test]
+    async fn parties_agree_on_challenges() {
+        let network = LocalTestNet::new_local_testnet(8).await.unwrap();
+
+        let result = network
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::Zero;
+                let mut transcript = Transcript::new(b"test");
+                // only the king's commitments matter
+                let commitment = if net.is_king() { 1u64 } else { 2u64 };
+                let beta: F = transcript
+                    .draw_challenge(&net, b"beta", &commitment, sid)
+                    .await
+                    .unwrap();
+                let gamma: F = transcript
+                    .draw_challenge(&net, b"gamma", &commitment, sid)
+                    .await
+                    .unwrap();
+                (beta, gamma)
+            })
+            .await;
+
+        let mut expected = Transcript::new(b"test");
+        let beta: F = expected.challenge(b"beta", &1u64);
+        let gamma: F = expected.challenge(b"gamma", &1u64);
+        assert_ne!(beta, gamma);
+        assert!(result.iter().all(|r| *r == (beta, gamma)));
+    }
+}
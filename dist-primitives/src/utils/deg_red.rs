@@ -1,4 +1,6 @@
-use super::pack::{pack_vec, transpose};
+use super::king::king_repack;
+use super::pack::{columns, pack_vec, transpose};
+use super::PackedSharingParamsExt;
 use ark_ff::FftField;
 use ark_poly::domain::DomainCoeff;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
@@ -21,6 +23,41 @@ where
     _marker: std::marker::PhantomData<F>,
 }
 
+// T need not implement Zeroize, so the buffers are wiped as plain memory
+#[cfg(feature = "zeroize")]
+impl<F, T> zeroize::Zeroize for DegRedMask<F, T>
+where
+    F: FftField,
+    T: DomainCoeff<F> + CanonicalSerialize + CanonicalDeserialize + UniformRand,
+{
+    fn zeroize(&mut self) {
+        // T is Copy, so the elements need no drop before being overwritten
+        for mask in [&mut self.in_mask, &mut self.out_mask] {
+            mask.clear();
+            mask.spare_capacity_mut().zeroize();
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<F, T> Drop for DegRedMask<F, T>
+where
+    F: FftField,
+    T: DomainCoeff<F> + CanonicalSerialize + CanonicalDeserialize + UniformRand,
+{
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<F, T> zeroize::ZeroizeOnDrop for DegRedMask<F, T>
+where
+    F: FftField,
+    T: DomainCoeff<F> + CanonicalSerialize + CanonicalDeserialize + UniformRand,
+{
+}
+
 impl<F, T> DegRedMask<F, T>
 where
     F: FftField,
@@ -65,9 +102,61 @@ where
             .collect()
     }
 
-    /// Returns a default value for DegRedMask. Not secure.
-    /// Only to be used for debugging purposes.
+    /// Checks the shares of all n parties, as dealt by sample, before they are
+    /// handed out: every party must get num values of both masks, and
+    /// in_mask + out_mask must pack zeros so that the masks cancel in deg_red.
+    /// A dealer bug would otherwise only show up as a wrong output.
+    pub fn validate(
+        masks: &[Self],
+        num: usize,
+        pp: &PackedSharingParams<F>,
+    ) -> Result<(), MpcNetError> {
+        let bad_input = |err: String| Err(MpcNetError::BadInput { err });
+        if masks.len() != pp.n {
+            return bad_input(format!(
+                "DegRedMask: {} shares for {} parties",
+                masks.len(),
+                pp.n
+            ));
+        }
+        if let Some((id, mask)) = masks.iter().enumerate().find(|(_, mask)| {
+            mask.in_mask.len() != num || mask.out_mask.len() != num
+        }) {
+            return bad_input(format!(
+                "DegRedMask: party {} got {} in_mask and {} out_mask values, expected {}",
+                id,
+                mask.in_mask.len(),
+                mask.out_mask.len(),
+                num
+            ));
+        }
+
+        let sums = masks
+            .iter()
+            .map(|mask| {
+                mask.in_mask
+                    .iter()
+                    .zip(&mask.out_mask)
+                    .map(|(i, o)| *i + *o)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        if let Some(k) = columns(&sums)
+            .position(|sum| pp.unpack(sum).iter().any(|x| !x.is_zero()))
+        {
+            return bad_input(format!(
+                "DegRedMask: in_mask and out_mask do not cancel at {}",
+                k
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns an all-zero DegRedMask of num shares.
+    /// Insecure, see [insecure masks](crate#insecure-masks).
     pub fn zero(num: usize) -> Self {
+        #[cfg(debug_assertions)]
+        log::warn!("using an insecure zero DegRedMask");
         Self {
             in_mask: vec![T::zero(); num],
             out_mask: vec![T::zero(); num],
@@ -77,6 +166,11 @@ where
 }
 
 /// Reduces the degree of a poylnomial with the help of king
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(party_id = net.party_id(), sid = ?sid, len = x_share.len())
+)]
 pub async fn deg_red<
     F: FftField,
     T: DomainCoeff<F> + CanonicalSerialize + CanonicalDeserialize + UniformRand,
@@ -88,6 +182,7 @@ pub async fn deg_red<
     net: &Net,
     sid: MultiplexedStreamID,
 ) -> Result<Vec<T>, MpcNetError> {
+    pp.assert_matches(net)?;
     debug_assert_eq!(x_share.len(), degred_mask.in_mask.len());
     debug_assert_eq!(x_share.len(), degred_mask.out_mask.len());
 
@@ -96,49 +191,62 @@ pub async fn deg_red<
         .zip(degred_mask.in_mask.iter())
         .map(|(x, m)| x + *m)
         .collect();
-    let received_shares = net
-        .client_send_or_king_receive_serialized(&x_mask, sid, pp.t)
-        .await?;
+    let x_share = king_repack(&x_mask, |secrets| secrets, pp, net, sid).await?;
 
-    let king_answer: Option<Vec<Vec<T>>> = received_shares.map(|rs| {
-        let mut x_shares = transpose(rs.shares);
-
-        for x_share in &mut x_shares {
-            let xi: Vec<T> = pp.unpack_missing_shares(x_share, &rs.parties);
-            *x_share = pp.pack(xi, &mut rand::thread_rng());
-        }
-        transpose(x_shares)
-    });
-
-    let result = net
-        .client_receive_or_king_send_serialized(king_answer, sid)
-        .await;
-
-    if let Ok(x_share) = result {
-        Ok(x_share
-            .into_iter()
-            .zip(degred_mask.out_mask.iter())
-            .map(|(x, m)| x + *m)
-            .collect())
-    } else {
-        result
-    }
+    Ok(x_share
+        .into_iter()
+        .zip(degred_mask.out_mask.iter())
+        .map(|(x, m)| x + *m)
+        .collect())
 }
 
 #[cfg(test)]
 mod tests {
     use ark_bls12_377::Fr as F;
-    use ark_ff::One;
+    use ark_ff::{One, Zero};
     use ark_std::UniformRand;
+    use mpc_net::chaos::{ChaosNet, Fault};
+    use mpc_net::inproc::InProcTestNet;
     use mpc_net::ser_net::ReceivedShares;
     use mpc_net::MpcNet;
     use mpc_net::{LocalTestNet, MultiplexedStreamID};
     use secret_sharing::pss::PackedSharingParams;
+    use std::time::Duration;
 
-    use crate::utils::deg_red::DegRedMask;
-    use crate::utils::{deg_red::deg_red, pack::transpose};
+    use crate::utils::deg_red::{deg_red, DegRedMask};
+    use crate::utils::pack::{pack_vec, transpose};
+    use mpc_net::MpcNetError;
     const L: usize = 4;
 
+    #[test]
+    fn validate_catches_masks_that_do_not_cancel() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let masks = DegRedMask::<F, F>::sample(&pp, F::one(), 3, rng);
+        assert!(DegRedMask::validate(&masks, 3, &pp).is_ok());
+
+        let bad_input = |masks: &[DegRedMask<F, F>], num| {
+            matches!(
+                DegRedMask::validate(masks, num, &pp),
+                Err(MpcNetError::BadInput { .. })
+            )
+        };
+        assert!(bad_input(&masks, 2));
+        assert!(bad_input(&masks[1..], 3));
+
+        // out_mask of another dealing
+        let other = DegRedMask::<F, F>::sample(&pp, F::one(), 3, rng);
+        let mut mixed = masks.clone();
+        for (mask, other) in mixed.iter_mut().zip(other) {
+            mask.out_mask[1] = other.out_mask[1];
+        }
+        assert!(bad_input(&mixed, 3));
+
+        let mut short = masks;
+        short[2].out_mask.pop();
+        assert!(bad_input(&short, 3));
+    }
+
     #[tokio::test]
     async fn test_deg_red() {
         let pp = PackedSharingParams::<F>::new(L);
@@ -180,7 +288,10 @@ mod tests {
                 .flat_map(|x| pp.unpack(x))
                 .collect::<Vec<_>>()
         } else {
-            println!("Using lagrange unpack");
+            tracing::debug!(
+                parties = rs.parties.len(),
+                "using lagrange unpack"
+            );
             shares
                 .into_iter()
                 .flat_map(|x| pp.lagrange_unpack(&x, &rs.parties))
@@ -189,4 +300,170 @@ mod tests {
 
         assert_eq!(computed, expected);
     }
+
+    #[tokio::test]
+    async fn deg_red_with_a_king_holding_no_share() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let network = LocalTestNet::new_local_testnet_with_king(pp.n)
+            .await
+            .unwrap();
+        let secrets = (0..2 * L).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let expected: Vec<F> = secrets.iter().map(|x| (*x) * (*x)).collect();
+
+        let mul_shares: Vec<Vec<F>> = transpose(pack_vec(&secrets, &pp))
+            .into_iter()
+            .map(|shares| shares.iter().map(|x| (*x) * (*x)).collect())
+            .collect();
+        let degred_masks: Vec<DegRedMask<F, F>> =
+            DegRedMask::sample(&pp, F::one(), 2, rng);
+
+        let result = network
+            .simulate_network_round(
+                (mul_shares, degred_masks, pp),
+                |net, (mul_shares, degred_masks, pp)| async move {
+                    let (share, mask) = match net.party_index() {
+                        Some(idx) => {
+                            (mul_shares[idx].clone(), degred_masks[idx].clone())
+                        }
+                        None => (vec![F::zero(); 2], DegRedMask::zero(2)),
+                    };
+                    deg_red(share, &mask, &pp, &net, MultiplexedStreamID::One)
+                        .await
+                        .unwrap()
+                },
+            )
+            .await;
+
+        // node 0 is the king, the others hold the shares in order
+        assert_eq!(result[0], vec![F::zero(); 2]);
+        let computed = transpose(result[1..].to_vec())
+            .into_iter()
+            .flat_map(|x| pp.unpack(x))
+            .collect::<Vec<_>>();
+        assert_eq!(computed, expected);
+    }
+
+    #[tokio::test]
+    async fn deg_red_survives_slow_and_repeating_parties() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let secrets = (0..4 * L).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let expected: Vec<F> = secrets.iter().map(|x| (*x) * (*x)).collect();
+
+        let mul_shares: Vec<Vec<F>> = transpose(pack_vec(&secrets, &pp))
+            .into_iter()
+            .map(|shares| shares.iter().map(|x| (*x) * (*x)).collect())
+            .collect();
+        let degred_masks: Vec<DegRedMask<F, F>> =
+            DegRedMask::sample(&pp, F::one(), 4, rng);
+
+        let result = InProcTestNet::new(pp.n)
+            .simulate_network_round(
+                (mul_shares, degred_masks, pp),
+                |net, (mul_shares, degred_masks, pp)| async move {
+                    let sid = MultiplexedStreamID::One;
+                    let idx = net.party_id() as usize;
+                    // a share sent twice in the last round of the channel
+                    // is never read, so only the first one counts
+                    let delay = Fault::Delay(Duration::from_millis(20));
+                    let net = ChaosNet::new(net, idx as u64)
+                        .with_fault(0, sid, delay, 0.5)
+                        .with_fault(0, sid, Fault::Duplicate, 0.5);
+                    deg_red(
+                        mul_shares[idx].clone(),
+                        &degred_masks[idx],
+                        &pp,
+                        &net,
+                        sid,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        let computed = transpose(result)
+            .into_iter()
+            .flat_map(|x| pp.unpack(x))
+            .collect::<Vec<_>>();
+        assert_eq!(computed, expected);
+    }
+
+    #[tokio::test]
+    async fn deg_red_rejects_a_lying_party() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let secrets = (0..4 * L).map(|_| F::rand(rng)).collect::<Vec<_>>();
+
+        let mul_shares: Vec<Vec<F>> = transpose(pack_vec(&secrets, &pp))
+            .into_iter()
+            .map(|shares| shares.iter().map(|x| (*x) * (*x)).collect())
+            .collect();
+        let degred_masks: Vec<DegRedMask<F, F>> =
+            DegRedMask::sample(&pp, F::one(), 4, rng);
+
+        let data = (mul_shares, degred_masks, pp);
+        for seed in 0..8 {
+            let result = InProcTestNet::new(pp.n)
+                .simulate_network_round(
+                    data.clone(),
+                    move |net, (mul_shares, degred_masks, pp)| async move {
+                        let sid = MultiplexedStreamID::One;
+                        let idx = net.party_id() as usize;
+                        // a flipped bit either fails to deserialize or
+                        // moves the share off the polynomial of the others
+                        let net = match idx {
+                            3 => ChaosNet::new(net, seed).with_fault(
+                                0,
+                                sid,
+                                Fault::Corrupt,
+                                1.0,
+                            ),
+                            _ => ChaosNet::new(net, seed),
+                        };
+                        deg_red(
+                            mul_shares[idx].clone(),
+                            &degred_masks[idx],
+                            &pp,
+                            &net,
+                            sid,
+                        )
+                        .await
+                    },
+                )
+                .await;
+
+            // n shares of degree 2(t+l-1) can only detect a liar, so the
+            // king aborts the round instead of repacking a wrong value
+            for r in &result {
+                assert!(
+                    matches!(r, Err(MpcNetError::Protocol { .. })),
+                    "seed {seed}"
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize_wipes_mask_buffers() {
+        use ark_std::Zero;
+        use zeroize::{Zeroize, ZeroizeOnDrop};
+
+        fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<DegRedMask<F, F>>();
+
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let mut mask =
+            DegRedMask::<F, F>::sample(&pp, F::one(), 4, rng).swap_remove(0);
+        let (ptr, len) = (mask.in_mask.as_ptr(), mask.in_mask.len());
+        assert!((0..len).any(|i| unsafe { !(*ptr.add(i)).is_zero() }));
+
+        // the buffer is still allocated, only its contents are wiped
+        mask.zeroize();
+        assert!(mask.in_mask.is_empty() && mask.out_mask.is_empty());
+        assert!((0..len).all(|i| unsafe { (*ptr.add(i)).is_zero() }));
+    }
 }
@@ -0,0 +1,109 @@
+use super::pack::{pack_vec, transpose_rows};
+use ark_ff::FftField;
+use ark_poly::domain::DomainCoeff;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::UniformRand;
+use mpc_net::ser_net::{MpcSerNet, ReceivedShares};
+use mpc_net::{MpcNetError, MultiplexedStreamID};
+use secret_sharing::pss::PackedSharingParams;
+
+/// king_round for a vector of packed shares: the king unpacks the l secrets
+/// of every share, maps all of them with king_fn and repacks the output,
+/// l secrets per share, with fresh randomness.
+/// The king checks that the shares lie on a polynomial of degree 2(t+l-1),
+/// the largest it can unpack, and otherwise aborts the round, see
+/// MpcSerNet::try_king_round.
+/// Returns this party's shares of the output. A king holding no share gets
+/// zeros, one per input share, so callers can index the output uniformly.
+pub async fn king_repack<
+    F: FftField,
+    T: DomainCoeff<F> + CanonicalSerialize + CanonicalDeserialize + UniformRand,
+    Net: MpcSerNet,
+>(
+    share: &Vec<T>,
+    king_fn: impl FnOnce(Vec<T>) -> Vec<T> + Send,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Vec<T>, MpcNetError> {
+    let degree = 2 * (pp.t + pp.l - 1);
+    let out = net
+        .try_king_round(
+            share,
+            |rs| {
+                let secrets = pp
+                    .unpack_missing_shares_many(&rs.shares, &rs.parties, degree)
+                    .map_err(|err| MpcNetError::Protocol {
+                        err: format!("king_repack: {err}"),
+                        party: net.king_id(),
+                    })?;
+                Ok(transpose_rows(pack_vec(&king_fn(secrets.concat()), pp)))
+            },
+            sid,
+            pp.t,
+        )
+        .await?;
+
+    if net.party_index().is_none() {
+        return Ok(vec![T::zero(); share.len()]);
+    }
+    Ok(out)
+}
+
+/// The king's reconstruction of a fresh sharing of field elements, of degree
+/// t+l-1, e.g the output of deg_red: corrects the shares of up to l lying
+/// parties with unpack_missing_shares_robust and warns about them, and fails
+/// with MpcNetError::Protocol if there are more.
+pub(crate) fn king_unpack_fresh<F: FftField>(
+    rs: &ReceivedShares<F>,
+    pp: &PackedSharingParams<F>,
+    king_id: u32,
+    context: &str,
+) -> Result<Vec<F>, MpcNetError> {
+    let (secrets, corrected) = pp
+        .unpack_missing_shares_robust(&rs.shares, &rs.parties, pp.t + pp.l - 1)
+        .map_err(|err| MpcNetError::Protocol {
+            err: format!("{context}: {err}"),
+            party: king_id,
+        })?;
+    if !corrected.is_empty() {
+        log::warn!("{context}: corrected the shares of parties {corrected:?}");
+    }
+    Ok(secrets)
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use ark_std::UniformRand;
+    use mpc_net::ser_net::ReceivedShares;
+    use mpc_net::MpcNetError;
+    use secret_sharing::pss::PackedSharingParams;
+
+    use super::king_unpack_fresh;
+
+    const L: usize = 2;
+
+    #[test]
+    fn king_unpack_fresh_corrects_up_to_l_liars() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let secrets = (0..L).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let mut rs = ReceivedShares {
+            shares: pp.pack(secrets.clone(), rng),
+            parties: (0..pp.n as u32).collect(),
+            faulty: Vec::new(),
+        };
+
+        for liar in 0..L {
+            rs.shares[2 * liar + 1] += F::from(1u64);
+        }
+        assert_eq!(king_unpack_fresh(&rs, &pp, 0, "test").unwrap(), secrets);
+
+        rs.shares[0] += F::from(1u64);
+        assert!(matches!(
+            king_unpack_fresh(&rs, &pp, 0, "test"),
+            Err(MpcNetError::Protocol { party: 0, .. })
+        ));
+    }
+}
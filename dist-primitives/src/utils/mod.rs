@@ -1,2 +1,103 @@
 pub mod deg_red;
+pub mod king;
 pub mod pack;
+
+use ark_ff::FftField;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use mpc_net::{MpcNet, MpcNetError};
+use secret_sharing::pss::PackedSharingParams;
+
+/// Checks done by the distributed protocols before touching the network
+pub trait PackedSharingParamsExt {
+    /// Errors if the parameters were set up for a different number of parties
+    fn assert_matches<Net: MpcNet>(&self, net: &Net)
+        -> Result<(), MpcNetError>;
+}
+
+impl<F: FftField> PackedSharingParamsExt for PackedSharingParams<F> {
+    fn assert_matches<Net: MpcNet>(
+        &self,
+        net: &Net,
+    ) -> Result<(), MpcNetError> {
+        if self.n != net.n_parties() {
+            return Err(MpcNetError::BadInput {
+                err: format!(
+                    "PackedSharingParams are for {} parties but the network has {}",
+                    self.n,
+                    net.n_parties()
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Index of this party's share (see MpcNet::party_index), for the protocols
+/// a king holding no share cannot take part in
+pub fn share_index<Net: MpcNet>(net: &Net) -> Result<usize, MpcNetError> {
+    net.party_index().ok_or_else(|| MpcNetError::BadInput {
+        err: "a king holding no share has no share index".to_string(),
+    })
+}
+
+/// Largest network the meshed primitives (e.g d_msm_meshed) accept.
+/// They skip the king, so it is neither a bottleneck nor a single point of
+/// failure, but every party sends its share to every other party: a round
+/// costs n(n - 1) messages instead of the 2(n - 1) of a round through the king.
+pub const MAX_MESH_PARTIES: usize = 16;
+
+/// Errors if net has more than MAX_MESH_PARTIES parties
+pub fn check_mesh_size<Net: MpcNet>(net: &Net) -> Result<(), MpcNetError> {
+    if net.n_parties() > MAX_MESH_PARTIES {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "meshed rounds need at most {} parties, the network has {}",
+                MAX_MESH_PARTIES,
+                net.n_parties()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Rng that every party derives alike from shared_seed, to deal the same
+/// masks as a dealer would (see MsmMask::sample_for_party)
+pub(crate) fn dealer_rng(shared_seed: u64) -> StdRng {
+    StdRng::seed_from_u64(shared_seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr as F;
+    use mpc_net::inproc::InProcTestNet;
+    use mpc_net::{LocalTestNet, MpcNetError};
+    use secret_sharing::pss::PackedSharingParams;
+
+    use super::{check_mesh_size, PackedSharingParamsExt, MAX_MESH_PARTIES};
+
+    #[tokio::test]
+    async fn assert_matches_rejects_wrong_network_size() {
+        let network = LocalTestNet::new_local_testnet(8).await.unwrap();
+        let king = network.get_king();
+
+        assert!(PackedSharingParams::<F>::new(2)
+            .assert_matches(king)
+            .is_ok());
+        assert!(matches!(
+            PackedSharingParams::<F>::new(4).assert_matches(king),
+            Err(MpcNetError::BadInput { .. })
+        ));
+    }
+
+    #[test]
+    fn check_mesh_size_rejects_large_networks() {
+        let nodes = InProcTestNet::new(MAX_MESH_PARTIES + 1).into_nodes();
+        assert!(matches!(
+            check_mesh_size(&nodes[0]),
+            Err(MpcNetError::BadInput { .. })
+        ));
+        let nodes = InProcTestNet::new(MAX_MESH_PARTIES).into_nodes();
+        assert!(check_mesh_size(&nodes[0]).is_ok());
+    }
+}
@@ -4,6 +4,9 @@ use ark_std::{cfg_chunks, UniformRand};
 use rand::thread_rng;
 use secret_sharing::pss::PackedSharingParams;
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
 // TODO: maybe make this an impl of pp?
 pub fn pack_vec<F: FftField, T: DomainCoeff<F> + UniformRand>(
     secrets: &Vec<T>,
@@ -11,11 +14,9 @@ pub fn pack_vec<F: FftField, T: DomainCoeff<F> + UniformRand>(
 ) -> Vec<Vec<T>> {
     debug_assert_eq!(secrets.len() % pp.l, 0, "Mismatch of size in pack_vec");
 
-    let rng = &mut thread_rng();
-
     // pack shares
     cfg_chunks!(secrets, pp.l)
-        .map(|x| pp.pack(x.to_vec(), rng))
+        .map(|x| pp.pack(x.to_vec(), &mut thread_rng()))
         .collect::<Vec<_>>()
 }
 
@@ -34,6 +35,40 @@ pub fn transpose<T: Clone>(matrix: Vec<Vec<T>>) -> Vec<Vec<T>> {
     result
 }
 
+/// Lazily yields the columns of matrix, one at a time.
+/// Lets the king process the shares it received column by column
+/// instead of holding a transposed copy of all of them. The king still
+/// holds every received share: a round only returns once all of them
+/// arrived, so this only saves the transposed copy.
+pub fn columns<T: Clone>(
+    matrix: &[Vec<T>],
+) -> impl Iterator<Item = Vec<T>> + '_ {
+    let cols = matrix.first().map_or(0, Vec::len);
+    (0..cols).map(move |c| column(matrix, c))
+}
+
+/// Returns the c-th column of matrix
+pub fn column<T: Clone>(matrix: &[Vec<T>], c: usize) -> Vec<T> {
+    matrix.iter().map(|row| row[c].clone()).collect()
+}
+
+/// Same as transpose(rows.collect()), but consumes the rows as they are
+/// produced so that only the transposed matrix is ever fully in memory.
+pub fn transpose_rows<T: Clone>(
+    rows: impl IntoIterator<Item = Vec<T>>,
+) -> Vec<Vec<T>> {
+    let mut result: Vec<Vec<T>> = Vec::new();
+    for row in rows {
+        if result.is_empty() {
+            result = vec![Vec::new(); row.len()];
+        }
+        for (column, x) in result.iter_mut().zip(row) {
+            column.push(x);
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,4 +81,16 @@ mod tests {
 
         assert_eq!(transpose(matrix), expected);
     }
+
+    #[test]
+    fn test_streaming_transpose() {
+        let matrix = vec![vec![1, 2, 3], vec![4, 5, 6]];
+
+        assert_eq!(
+            columns(&matrix).collect::<Vec<_>>(),
+            transpose(matrix.clone())
+        );
+        assert_eq!(transpose_rows(matrix.clone()), transpose(matrix.clone()));
+        assert_eq!(transpose_rows(columns(&matrix)), matrix);
+    }
 }
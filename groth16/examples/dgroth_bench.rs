@@ -0,0 +1,433 @@
+use ark_bn254::{Bn254, Fr, G1Projective as G1, G2Projective as G2};
+use ark_circom::CircomReduction;
+use ark_crypto_primitives::snark::SNARK;
+use ark_ff::{PrimeField, UniformRand};
+use ark_groth16::Groth16;
+use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+use ark_relations::lc;
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef,
+    SynthesisError,
+};
+use ark_std::rand::{rngs::StdRng, SeedableRng};
+use dist_primitives::dmsm::MsmMask;
+use groth16::ext_wit::DistributedR1CSToQAP;
+use groth16::prove::{
+    king_reconstruct_proof, prove_batch, prove_shares, BInG1, BInG2,
+    BatchStatement, WitnessShares, A, C,
+};
+use groth16::proving_key::PackedProvingKeyShare;
+use groth16::qap::{qap, PackedQAPShare, QAP};
+use mpc_net::stats::{ChannelStats, StatsNet};
+use mpc_net::{LocalTestNet as Net, MpcNet, MultiplexedStreamID};
+use secret_sharing::pss::PackedSharingParams;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// The circuit has about 2^log_size constraints
+    #[structopt(long, default_value = "10")]
+    log_size: usize,
+    /// Also times proving this many statements with prove_batch against
+    /// proving them one after the other
+    #[structopt(long, default_value = "0")]
+    batch: usize,
+}
+
+/// Proves knowledge of w such that x = w^2 and y = w^3 for public x and y,
+/// padded with squarings of w to the requested size
+#[derive(Clone)]
+struct CubeCircuit<F: PrimeField> {
+    w: F,
+    num_squarings: usize,
+}
+
+impl<F: PrimeField> ConstraintSynthesizer<F> for CubeCircuit<F> {
+    fn generate_constraints(
+        self,
+        cs: ConstraintSystemRef<F>,
+    ) -> Result<(), SynthesisError> {
+        let x = cs.new_input_variable(|| Ok(self.w.square()))?;
+        let y = cs.new_input_variable(|| Ok(self.w.square() * self.w))?;
+        let w = cs.new_witness_variable(|| Ok(self.w))?;
+        cs.enforce_constraint(lc!() + w, lc!() + w, lc!() + x)?;
+        cs.enforce_constraint(lc!() + w, lc!() + x, lc!() + y)?;
+
+        let mut cur = w;
+        let mut val = self.w;
+        for _ in 0..self.num_squarings {
+            val.square_in_place();
+            let next_val = val;
+            let next = cs.new_witness_variable(|| Ok(next_val))?;
+            cs.enforce_constraint(lc!() + cur, lc!() + cur, lc!() + next)?;
+            cur = next;
+        }
+        Ok(())
+    }
+}
+
+/// Shares of the inputs of one statement, with masks of its own
+struct Statement {
+    qap_shares: Vec<PackedQAPShare<Fr, Radix2EvaluationDomain<Fr>>>,
+    h_masks: Vec<<CircomReduction as DistributedR1CSToQAP>::Masks<Fr>>,
+    witness_shares: Vec<WitnessShares<Fr>>,
+    r_shares: Vec<Fr>,
+    s_shares: Vec<Fr>,
+    g1_msm_masks: [Vec<MsmMask<G1>>; 4],
+    g2_msm_masks: Vec<MsmMask<G2>>,
+}
+
+impl Statement {
+    fn sample(
+        qap: &QAP<Fr, Radix2EvaluationDomain<Fr>>,
+        full_assignment: &[Fr],
+        num_inputs: usize,
+        pp: &PackedSharingParams<Fr>,
+        rng: &mut StdRng,
+    ) -> Self {
+        let r = Fr::rand(rng);
+        let s = Fr::rand(rng);
+        Self {
+            qap_shares: qap.pss(pp, rng),
+            h_masks: CircomReduction::sample_masks(&qap.domain, pp, rng),
+            witness_shares: WitnessShares::pack(
+                full_assignment,
+                num_inputs,
+                pp,
+                rng,
+            ),
+            r_shares: pp.pack(vec![r; pp.l], rng),
+            s_shares: pp.pack(vec![s; pp.l], rng),
+            g1_msm_masks: core::array::from_fn(|_| MsmMask::sample(pp, rng)),
+            g2_msm_masks: MsmMask::sample(pp, rng),
+        }
+    }
+}
+
+/// Times proving `statements` one after the other against prove_batch,
+/// the first half sequentially and the second half as one batch
+async fn bench_batch(
+    statements: Vec<Statement>,
+    crs_shares: Vec<PackedProvingKeyShare<Bn254>>,
+    pp: PackedSharingParams<Fr>,
+) -> (Duration, Duration) {
+    let network = Net::new_local_testnet(pp.n).await.unwrap();
+    let result = network
+        .simulate_network_round(
+            Arc::new((pp, crs_shares, statements)),
+            |net, data| async move {
+                let (pp, crs_shares, statements) = &*data;
+                let idx = net.party_id() as usize;
+                let crs_share = &crs_shares[idx];
+                let g1_msm_masks = statements
+                    .iter()
+                    .map(|statement| {
+                        core::array::from_fn(|i| {
+                            statement.g1_msm_masks[i][idx].clone()
+                        })
+                    })
+                    .collect::<Vec<[MsmMask<G1>; 4]>>();
+                let (sequential, batch) =
+                    statements.split_at(statements.len() / 2);
+
+                let start = Instant::now();
+                for (statement, g1_msm_mask) in
+                    sequential.iter().zip(&g1_msm_masks)
+                {
+                    let h_share = CircomReduction::d_h(
+                        statement.qap_shares[idx].clone(),
+                        &statement.h_masks[idx],
+                        pp,
+                        &net,
+                    )
+                    .await
+                    .unwrap();
+                    let (a, b, c) = prove_shares(
+                        crs_share,
+                        &statement.witness_shares[idx],
+                        &h_share,
+                        statement.r_shares[idx],
+                        statement.s_shares[idx],
+                        g1_msm_mask,
+                        &statement.g2_msm_masks[idx],
+                        pp,
+                        &net,
+                    )
+                    .await
+                    .unwrap();
+                    king_reconstruct_proof::<Bn254, _>(
+                        a,
+                        b,
+                        c,
+                        pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap();
+                }
+                let sequential_time = start.elapsed();
+
+                let start = Instant::now();
+                let batch = batch
+                    .iter()
+                    .zip(&g1_msm_masks[sequential.len()..])
+                    .map(|(statement, g1_msm_mask)| BatchStatement::<
+                        Bn254,
+                        CircomReduction,
+                        _,
+                    > {
+                        qap_share: statement.qap_shares[idx].clone(),
+                        h_masks: &statement.h_masks[idx],
+                        witness: &statement.witness_shares[idx],
+                        r_share: statement.r_shares[idx],
+                        s_share: statement.s_shares[idx],
+                        g1_msm_mask,
+                        g2_msm_mask: &statement.g2_msm_masks[idx],
+                    })
+                    .collect();
+                prove_batch(batch, crs_share, pp, &net).await.unwrap();
+                (sequential_time, start.elapsed())
+            },
+        )
+        .await;
+    result[0]
+}
+
+/// Wall time and traffic at the king of one phase of the proof
+struct Phase {
+    name: &'static str,
+    time: Duration,
+    traffic: ChannelStats,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::builder().format_timestamp(None).init();
+    let opt = Opt::from_args();
+    let rng = &mut StdRng::seed_from_u64(42);
+
+    // an odd number of squarings keeps a_query[1..] and l_query a multiple
+    // of l = 2 long, as pack_from_arkworks_proving_key expects
+    let circuit = CubeCircuit {
+        w: Fr::from(3u64),
+        num_squarings: (1 << opt.log_size) - 1,
+    };
+    let (pk, vk) = Groth16::<Bn254, CircomReduction>::circuit_specific_setup(
+        circuit.clone(),
+        rng,
+    )
+    .unwrap();
+
+    let cs = ConstraintSystem::<Fr>::new_ref();
+    circuit.generate_constraints(cs.clone()).unwrap();
+    cs.finalize();
+    let matrices = cs.to_matrices().unwrap();
+    let full_assignment = {
+        let cs = cs.borrow().unwrap();
+        [
+            cs.instance_assignment.as_slice(),
+            cs.witness_assignment.as_slice(),
+        ]
+        .concat()
+    };
+    let num_inputs = matrices.num_instance_variables;
+    let qap = qap::<Fr, Radix2EvaluationDomain<_>>(&matrices, &full_assignment)
+        .unwrap();
+    println!("constraints: {}", qap.domain.size());
+
+    let pp = PackedSharingParams::<Fr>::new(2);
+    let r = Fr::rand(rng);
+    let s = Fr::rand(rng);
+    let r_shares = pp.pack(vec![r; pp.l], rng);
+    let s_shares = pp.pack(vec![s; pp.l], rng);
+    let h_masks = CircomReduction::sample_masks(&qap.domain, &pp, rng);
+    let qap_shares = qap.pss(&pp, rng);
+    let crs_shares =
+        PackedProvingKeyShare::<Bn254>::pack_from_arkworks_proving_key(&pk, pp);
+    let witness_shares =
+        WitnessShares::pack(&full_assignment, num_inputs, &pp, rng);
+    let g1_msm_masks: [Vec<MsmMask<G1>>; 4] =
+        core::array::from_fn(|_| MsmMask::sample(&pp, rng));
+    let g2_msm_masks = MsmMask::<G2>::sample(&pp, rng);
+
+    let network = Net::new_local_testnet(pp.n).await.unwrap();
+    let result = network
+        .simulate_network_round(
+            Arc::new((
+                pp,
+                crs_shares,
+                witness_shares,
+                qap_shares,
+                h_masks,
+                r_shares,
+                s_shares,
+                g1_msm_masks,
+                g2_msm_masks,
+            )),
+            |net, data| async move {
+                let (
+                    pp,
+                    crs_shares,
+                    witness_shares,
+                    qap_shares,
+                    h_masks,
+                    r_shares,
+                    s_shares,
+                    g1_msm_masks,
+                    g2_msm_masks,
+                ) = &*data;
+                let net = StatsNet::new(net);
+                let idx = net.party_id() as usize;
+                let crs_share = &crs_shares[idx];
+                let witness = &witness_shares[idx];
+                let g1_msm_mask: [MsmMask<G1>; 4] =
+                    core::array::from_fn(|i| g1_msm_masks[i][idx].clone());
+
+                let mut phases = Vec::new();
+                let mut start = (Instant::now(), net.stats());
+                let mut end_phase = |name| {
+                    let now = (Instant::now(), net.stats());
+                    phases.push(Phase {
+                        name,
+                        time: now.0 - start.0,
+                        traffic: now.1.since(&start.1).total(),
+                    });
+                    start = now;
+                };
+
+                let h_share = CircomReduction::d_h(
+                    qap_shares[idx].clone(),
+                    &h_masks[idx],
+                    pp,
+                    &net,
+                )
+                .await
+                .unwrap();
+                end_phase("ext-wit");
+
+                let a_share = A::<Bn254> {
+                    L: crs_share.a_query0,
+                    N: crs_share.delta_g1,
+                    AG1: crs_share.alpha_g1,
+                    r: r_shares[idx],
+                    pp,
+                    S: &crs_share.s,
+                    a: &witness.a,
+                }
+                .compute(&g1_msm_mask[0], &net, MultiplexedStreamID::Zero)
+                .await
+                .unwrap();
+                end_phase("MSM-A");
+
+                let b_g1_share = BInG1::<Bn254> {
+                    Z: crs_share.b_g1_query0,
+                    K: crs_share.delta_g1,
+                    BG1: crs_share.beta_g1,
+                    r: r_shares[idx],
+                    s: s_shares[idx],
+                    pp,
+                    H: &crs_share.h,
+                    a: &witness.a,
+                }
+                .compute(&g1_msm_mask[1], &net, MultiplexedStreamID::Zero)
+                .await
+                .unwrap();
+                let b_share = BInG2::<Bn254> {
+                    Z: crs_share.b_g2_query0,
+                    K: crs_share.delta_g2,
+                    BG2: crs_share.beta_g2,
+                    s: s_shares[idx],
+                    pp,
+                    V: &crs_share.v,
+                    a: &witness.a,
+                }
+                .compute(&g2_msm_masks[idx], &net, MultiplexedStreamID::Zero)
+                .await
+                .unwrap();
+                end_phase("MSM-B");
+
+                let c_share = C::<Bn254> {
+                    W: &crs_share.w,
+                    U: &crs_share.u,
+                    A: a_share,
+                    B: b_g1_share,
+                    M: crs_share.delta_g1,
+                    r: r_shares[idx],
+                    s: s_shares[idx],
+                    pp,
+                    H: &crs_share.h,
+                    a: &witness.a,
+                    ax: &witness.ax,
+                    h: &h_share,
+                }
+                .compute(
+                    &[g1_msm_mask[2].clone(), g1_msm_mask[3].clone()],
+                    &net,
+                    [MultiplexedStreamID::Zero, MultiplexedStreamID::One],
+                )
+                .await
+                .unwrap();
+                end_phase("MSM-C");
+
+                let proof = king_reconstruct_proof::<Bn254, _>(
+                    a_share,
+                    b_share,
+                    c_share,
+                    pp,
+                    &net,
+                    MultiplexedStreamID::Zero,
+                )
+                .await
+                .unwrap();
+                (proof, phases)
+            },
+        )
+        .await;
+
+    let (proof, phases) = &result[0];
+    println!(
+        "{:<8} {:>12} {:>14} {:>14}",
+        "phase", "time (ms)", "sent (bytes)", "recv (bytes)"
+    );
+    for phase in phases {
+        println!(
+            "{:<8} {:>12} {:>14} {:>14}",
+            phase.name,
+            phase.time.as_millis(),
+            phase.traffic.bytes_sent,
+            phase.traffic.bytes_received
+        );
+    }
+
+    let proof = proof.clone().expect("the king assembles the proof");
+    let public_inputs = &full_assignment[1..num_inputs];
+    assert!(Groth16::<Bn254, CircomReduction>::verify(
+        &vk,
+        public_inputs,
+        &proof
+    )
+    .unwrap());
+
+    if opt.batch > 0 {
+        // every statement needs fresh masks, masks must not be reused
+        let statements = (0..2 * opt.batch)
+            .map(|_| {
+                Statement::sample(&qap, &full_assignment, num_inputs, &pp, rng)
+            })
+            .collect();
+        let crs_shares =
+            PackedProvingKeyShare::<Bn254>::pack_from_arkworks_proving_key(
+                &pk, pp,
+            );
+        let (sequential, batch) = bench_batch(statements, crs_shares, pp).await;
+        println!(
+            "{} statements: sequential {} ms, prove_batch {} ms",
+            opt.batch,
+            sequential.as_millis(),
+            batch.as_millis()
+        );
+    }
+}
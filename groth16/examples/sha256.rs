@@ -2,17 +2,16 @@ use ark_bn254::{Bn254, Fr as Bn254Fr, G1Projective as G1, G2Projective as G2};
 use ark_circom::{CircomBuilder, CircomConfig, CircomReduction};
 use ark_crypto_primitives::snark::SNARK;
 use ark_ec::pairing::Pairing;
-use ark_ec::CurveGroup;
 use ark_ff::BigInt;
 use ark_ff::UniformRand;
 use ark_groth16::{Groth16, Proof};
 use ark_poly::EvaluationDomain;
 use ark_poly::Radix2EvaluationDomain;
 use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
-use ark_std::{cfg_chunks, cfg_into_iter, end_timer, start_timer, One, Zero};
+use ark_std::{end_timer, start_timer};
 use std::sync::Arc;
 
-use dist_primitives::dfft::FftMask;
+use dist_primitives::dfft::{FftMask, FftOrdering};
 use dist_primitives::dmsm::MsmMask;
 use dist_primitives::utils::deg_red::DegRedMask;
 use groth16::qap::qap;
@@ -23,11 +22,9 @@ use mpc_net::{LocalTestNet as Net, MpcNet, MultiplexedStreamID};
 use rand::SeedableRng;
 use secret_sharing::pss::PackedSharingParams;
 
+use groth16::prove::{ProofPart, WitnessShares};
 use groth16::proving_key::PackedProvingKeyShare;
 
-#[cfg(feature = "parallel")]
-use rayon::prelude::*;
-
 #[allow(clippy::too_many_arguments)]
 async fn dsha256<E, Net>(
     pp: &PackedSharingParams<E::ScalarField>,
@@ -36,16 +33,15 @@ async fn dsha256<E, Net>(
         E::ScalarField,
         Radix2EvaluationDomain<E::ScalarField>,
     >,
-    a_share: &[E::ScalarField],
-    ax_share: &[E::ScalarField],
+    witness: &WitnessShares<E::ScalarField>,
     r_share: E::ScalarField,
     s_share: E::ScalarField,
-    fft_mask: &[FftMask<E::ScalarField>; 6],
+    fft_mask: &[FftMask<E::ScalarField>; 3],
     f_degred_mask: &DegRedMask<E::ScalarField, E::ScalarField>,
     g1_msm_mask: &[MsmMask<E::G1>; 4],
     g2_msm_mask: &MsmMask<E::G2>,
     net: &Net,
-) -> (E::G1, E::G2, E::G1)
+) -> Option<Proof<E>>
 where
     E: Pairing,
     Net: MpcNet,
@@ -56,103 +52,47 @@ where
             .await
             .unwrap();
     let msm_section = start_timer!(|| "MSM operations");
-    // Compute msm while dropping the base vectors as they are not used again
-    let compute_a = start_timer!(|| "Compute A");
-    let pi_a_share = groth16::prove::A::<E> {
-        L: crs_share.a_query0,
-        N: crs_share.delta_g1,
-        AG1: crs_share.alpha_g1,
-        r: r_share,
-        pp,
-        S: &crs_share.s,
-        a: a_share,
-    }
-    .compute(&g1_msm_mask[0], net, MultiplexedStreamID::Zero)
-    .await
-    .unwrap();
-    end_timer!(compute_a);
-
-    let compute_b = start_timer!(|| "Compute B in G1");
-    let pi_b_g1_share: E::G1 = groth16::prove::BInG1::<E> {
-        Z: crs_share.b_g1_query0,
-        K: crs_share.delta_g1,
-        BG1: crs_share.beta_g1,
-        r: r_share,
-        s: s_share,
-        pp,
-        H: &crs_share.h,
-        a: a_share,
-    }
-    .compute(&g1_msm_mask[1], net, MultiplexedStreamID::Zero)
-    .await
-    .unwrap();
-    end_timer!(compute_b);
-    let compute_b = start_timer!(|| "Compute B in G2");
-    let pi_b_g2_share: E::G2 = groth16::prove::BInG2::<E> {
-        Z: crs_share.b_g2_query0,
-        K: crs_share.delta_g2,
-        BG2: crs_share.beta_g2,
-        s: s_share,
-        pp,
-        V: &crs_share.v,
-        a: a_share,
-    }
-    .compute(g2_msm_mask, net, MultiplexedStreamID::Zero)
-    .await
-    .unwrap();
-    end_timer!(compute_b);
+    let (parts, mut progress) = tokio::sync::mpsc::unbounded_channel();
+    let prove = async move {
+        groth16::prove::prove_shares_streaming(
+            crs_share,
+            witness,
+            &h_share,
+            r_share,
+            s_share,
+            g1_msm_mask,
+            g2_msm_mask,
+            pp,
+            net,
+            &parts,
+        )
+        .await
+    };
+    let report = async {
+        while let Some(part) = progress.recv().await {
+            let name = match part {
+                ProofPart::A(_) => "A",
+                ProofPart::B(_) => "B",
+                ProofPart::C(_) => "C",
+            };
+            debug!("{} done", name);
+        }
+    };
+    let (shares, ()) = tokio::join!(prove, report);
+    let (pi_a_share, pi_b_g2_share, pi_c_share) = shares.unwrap();
+    end_timer!(msm_section);
 
-    let compute_c = start_timer!(|| "Compute C");
-    let pi_c_share = groth16::prove::C::<E> {
-        W: &crs_share.w,
-        U: &crs_share.u,
-        A: pi_a_share,
-        B: pi_b_g1_share,
-        M: crs_share.delta_g1,
-        r: r_share,
-        s: s_share,
+    // Only the king assembles the proof, to send it to the client
+    groth16::prove::king_reconstruct_proof(
+        pi_a_share,
+        pi_b_g2_share,
+        pi_c_share,
         pp,
-        H: &crs_share.h,
-        a: a_share,
-        ax: ax_share,
-        h: &h_share,
-    }
-    .compute(&[g1_msm_mask[2].clone(), g1_msm_mask[3].clone()], net)
+        net,
+        MultiplexedStreamID::Zero,
+    )
     .await
-    .unwrap();
-    end_timer!(compute_c);
-
-    end_timer!(msm_section);
-
-    // Send pi_a_share, pi_b_share, pi_c_share to client
-    (pi_a_share, pi_b_g2_share, pi_c_share)
-}
-
-fn pack_from_witness<E: Pairing>(
-    pp: &PackedSharingParams<E::ScalarField>,
-    full_assignment: Vec<E::ScalarField>,
-) -> Vec<Vec<E::ScalarField>> {
-    let packed_assignments = cfg_chunks!(full_assignment, pp.l)
-        .map(|chunk| {
-            let rng = &mut ark_std::rand::thread_rng();
-            let secrets = if chunk.len() < pp.l {
-                let mut secrets = chunk.to_vec();
-                secrets.resize(pp.l, E::ScalarField::zero());
-                secrets
-            } else {
-                chunk.to_vec()
-            };
-            pp.pack(secrets, rng)
-        })
-        .collect::<Vec<_>>();
-
-    cfg_into_iter!(0..pp.n)
-        .map(|i| {
-            cfg_into_iter!(0..packed_assignments.len())
-                .map(|j| packed_assignments[j][i])
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>()
+    .unwrap()
 }
 
 #[tokio::main]
@@ -202,15 +142,13 @@ async fn main() {
     let pp = PackedSharingParams::new(2);
     let r_shares = pp.pack(vec![r; pp.n], rng);
     let s_shares = pp.pack(vec![s; pp.n], rng);
-    let qap_shares = qap.pss(&pp);
+    let qap_shares = qap.pss(&pp, rng);
     let crs_shares =
         PackedProvingKeyShare::<Bn254>::pack_from_arkworks_proving_key(&pk, pp);
     let crs_shares = Arc::new(crs_shares);
     let qap_shares = Arc::new(qap_shares);
-    let aux_assignment = &full_assignment[num_inputs..];
-    let ax_shares = pack_from_witness::<Bn254>(&pp, aux_assignment.to_vec());
-    let a_shares =
-        pack_from_witness::<Bn254>(&pp, full_assignment[1..].to_vec());
+    let witness_shares =
+        WitnessShares::pack(&full_assignment, num_inputs, &pp, rng);
     let network = Net::new_local_testnet(pp.n).await.unwrap();
 
     // compute masks
@@ -223,56 +161,15 @@ async fn main() {
         domain_double.element(1)
     };
 
-    let fft_masks = [
-        FftMask::<Bn254Fr>::sample(
-            true,
-            root_of_unity,
-            domain.group_gen_inv(),
-            domain.size(),
-            &pp,
-            rng,
-        ),
-        FftMask::<Bn254Fr>::sample(
-            true,
-            root_of_unity,
-            domain.group_gen_inv(),
-            domain.size(),
-            &pp,
-            rng,
-        ),
-        FftMask::<Bn254Fr>::sample(
-            true,
+    let fft_masks: [_; 3] = core::array::from_fn(|_| {
+        FftMask::<Bn254Fr>::sample_ifft_then_fft(
+            FftOrdering::Natural,
             root_of_unity,
-            domain.group_gen_inv(),
-            domain.size(),
+            &domain,
             &pp,
             rng,
-        ),
-        FftMask::<Bn254Fr>::sample(
-            false,
-            Bn254Fr::one(),
-            domain.group_gen(),
-            domain.size(),
-            &pp,
-            rng,
-        ),
-        FftMask::<Bn254Fr>::sample(
-            false,
-            Bn254Fr::one(),
-            domain.group_gen(),
-            domain.size(),
-            &pp,
-            rng,
-        ),
-        FftMask::<Bn254Fr>::sample(
-            false,
-            Bn254Fr::one(),
-            domain.group_gen(),
-            domain.size(),
-            &pp,
-            rng,
-        ),
-    ];
+        )
+    });
 
     let f_degred_masks = DegRedMask::<Bn254Fr, Bn254Fr>::sample(
         &pp,
@@ -290,13 +187,12 @@ async fn main() {
 
     let g2_msm_masks = MsmMask::<G2>::sample(&pp, rng);
 
-    let result: Vec<(G1, G2, G1)> = network
+    let result: Vec<Option<Proof<Bn254>>> = network
         .simulate_network_round(
             (
                 crs_shares,
                 pp,
-                a_shares,
-                ax_shares,
+                witness_shares,
                 qap_shares,
                 r_shares,
                 s_shares,
@@ -309,8 +205,7 @@ async fn main() {
              (
                 crs_shares,
                 pp,
-                a_shares,
-                ax_shares,
+                witness_shares,
                 qap_shares,
                 r_shares,
                 s_shares,
@@ -321,8 +216,7 @@ async fn main() {
             )| async move {
                 let idx = net.party_id() as usize;
                 let crs_share = crs_shares.get(idx).unwrap();
-                let a_share = &a_shares[idx];
-                let ax_share = &ax_shares[idx];
+                let witness = &witness_shares[idx];
                 let qap_share = qap_shares[idx].clone();
                 let r_share = r_shares[idx];
                 let s_share = s_shares[idx];
@@ -332,9 +226,6 @@ async fn main() {
                     fft_masks[0][idx].clone(),
                     fft_masks[1][idx].clone(),
                     fft_masks[2][idx].clone(),
-                    fft_masks[3][idx].clone(),
-                    fft_masks[4][idx].clone(),
-                    fft_masks[5][idx].clone(),
                 ];
 
                 let g1_msm_mask = [
@@ -348,8 +239,7 @@ async fn main() {
                     &pp,
                     crs_share,
                     qap_share,
-                    a_share,
-                    ax_share,
+                    witness,
                     r_share,
                     s_share,
                     &fft_mask,
@@ -363,18 +253,8 @@ async fn main() {
         )
         .await;
 
-    let mut a_shares = Vec::new();
-    let mut b_shares = Vec::new();
-    let mut c_shares = Vec::new();
-    for (a_share, b_share, c_share) in result.into_iter() {
-        a_shares.push(a_share);
-        b_shares.push(b_share);
-        c_shares.push(c_share);
-    }
-
-    let a = pp.unpack2(a_shares)[0];
-    let b = pp.unpack2(b_shares)[0];
-    let c = pp.unpack2(c_shares)[0];
+    let proof = result[0].clone().expect("the king assembles the proof");
+    let (a, b, c) = (proof.a, proof.b, proof.c);
 
     // These elements are needed to construct the full proof, they are part of the proving key.
     // however, we can just send these values to the client, not the full proving key.
@@ -398,11 +278,6 @@ async fn main() {
     .unwrap();
 
     assert!(verified, "Arkworks Proof verification failed!");
-    let proof = Proof::<Bn254> {
-        a: a.into_affine(),
-        b: b.into_affine(),
-        c: c.into_affine(),
-    };
     let verified = Groth16::<Bn254, CircomReduction>::verify_with_processed_vk(
         &pvk,
         &[BigInt!(
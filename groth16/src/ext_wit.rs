@@ -1,12 +1,25 @@
 use crate::qap::PackedQAPShare;
+use ark_circom::CircomReduction;
 use ark_ff::{FftField, PrimeField};
-use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+use ark_groth16::r1cs_to_qap::{LibsnarkReduction, R1CSToQAP};
+use ark_poly::EvaluationDomain;
 use ark_std::cfg_into_iter;
-use dist_primitives::dfft::{d_fft, d_ifft, FftMask};
+use async_trait::async_trait;
+#[cfg(feature = "debug-fft")]
+use dist_primitives::dfft::d_check_against;
+#[cfg(feature = "debug-fft")]
+use dist_primitives::dfft::d_fft;
+use dist_primitives::dfft::{
+    d_ifft, d_ifft_then_fft_multi, FftMask, FftOrdering,
+};
+use dist_primitives::drand::d_rand;
+use dist_primitives::dzero::{d_is_zero, ZeroMask};
 use dist_primitives::utils::deg_red::{deg_red, DegRedMask};
 use mpc_net::ser_net::MpcSerNet;
 use mpc_net::{MpcNetError, MultiplexedStreamID};
+use rand::Rng;
 use secret_sharing::pss::PackedSharingParams;
+use std::mem;
 
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
@@ -16,8 +29,8 @@ pub async fn libsnark_h<
     D: EvaluationDomain<F>,
     Net: MpcSerNet,
 >(
-    qap_share: PackedQAPShare<F, D>,
-    fft_mask: &[FftMask<F>; 7], // 3 ifft, 3 fft and 1 coset ifft
+    mut qap_share: PackedQAPShare<F, D>,
+    fft_mask: &[FftMask<F>; 4], // 3 ifft then fft and 1 coset ifft
     pp: &PackedSharingParams<F>,
     net: &Net,
 ) -> Result<Vec<F>, MpcNetError> {
@@ -28,50 +41,22 @@ pub async fn libsnark_h<
     let domain = qap_share.domain;
     let coset_dom = domain.get_coset(F::GENERATOR).unwrap();
 
-    let a_coeff_fut = d_ifft(
-        qap_share.a,
-        &fft_mask[0],
-        true,
-        &domain,
-        coset_dom.coset_offset(),
-        pp,
-        net,
-        CHANNEL0,
-    );
-    let b_coeff_fut = d_ifft(
-        qap_share.b,
-        &fft_mask[1],
-        true,
+    // evaluations of a, b, c over the coset, received by the king at once
+    let [a_eval, b_eval, c_eval] = d_ifft_then_fft_multi(
+        [
+            mem::take(&mut qap_share.a),
+            mem::take(&mut qap_share.b),
+            mem::take(&mut qap_share.c),
+        ],
+        [&fft_mask[0], &fft_mask[1], &fft_mask[2]],
+        FftOrdering::BitReversed,
         &domain,
         coset_dom.coset_offset(),
         pp,
         net,
-        CHANNEL1,
-    );
-    let c_coeff_fut = d_ifft(
-        qap_share.c,
-        &fft_mask[2],
-        true,
-        &domain,
-        coset_dom.coset_offset(),
-        pp,
-        net,
-        CHANNEL2,
-    );
-
-    let (a_coeff, b_coeff, c_coeff) =
-        tokio::try_join!(a_coeff_fut, b_coeff_fut, c_coeff_fut)?;
-
-    let a_eval_fut =
-        d_fft(a_coeff, &fft_mask[3], true, &domain, pp, net, CHANNEL0);
-    let b_eval_fut =
-        d_fft(b_coeff, &fft_mask[4], true, &domain, pp, net, CHANNEL1);
-    let c_eval_fut =
-        d_fft(c_coeff, &fft_mask[5], true, &domain, pp, net, CHANNEL2);
-
-    // evaluations of a, b, c over the coset
-    let (a_eval, b_eval, c_eval) =
-        tokio::try_join!(a_eval_fut, b_eval_fut, c_eval_fut)?;
+        [CHANNEL0, CHANNEL1, CHANNEL2],
+    )
+    .await?;
 
     // compute (ab-c)/z
     let vanishing_polynomial_over_coset = domain
@@ -88,8 +73,8 @@ pub async fn libsnark_h<
     // run coset_ifft to get back coefficients of h
     let h_coeff = d_ifft(
         h_eval,
-        &fft_mask[6],
-        false,
+        &fft_mask[3],
+        FftOrdering::Natural,
         &domain,
         coset_dom.coset_offset_inv(),
         pp,
@@ -101,33 +86,80 @@ pub async fn libsnark_h<
     Ok(h_coeff)
 }
 
+/// The evaluations of a, b and c are shifted by a root of unity of twice
+/// the size of domain
+fn circom_shift<F: FftField, D: EvaluationDomain<F>>(domain: &D) -> F {
+    D::new(2 * domain.size()).unwrap().element(1)
+}
+
 pub async fn circom_h<
     F: FftField + PrimeField,
     D: EvaluationDomain<F>,
     Net: MpcSerNet,
 >(
-    qap_share: PackedQAPShare<F, D>,
-    fft_mask: &[FftMask<F>; 6], // 3 ifft and 3 fft
+    mut qap_share: PackedQAPShare<F, D>,
+    fft_mask: &[FftMask<F>; 3], // ifft then fft of a, b and c
+    degred_mask: &DegRedMask<F, F>,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+) -> Result<Vec<F>, MpcNetError> {
+    const CHANNEL0: MultiplexedStreamID = MultiplexedStreamID::Zero;
+    const CHANNEL1: MultiplexedStreamID = MultiplexedStreamID::One;
+    const CHANNEL2: MultiplexedStreamID = MultiplexedStreamID::Two;
+
+    let domain = qap_share.domain;
+    let root_of_unity = circom_shift(&domain);
+
+    // evaluations of a, b, c over the coset, received by the king at once
+    let [a_eval, b_eval, c_eval] = d_ifft_then_fft_multi(
+        [
+            mem::take(&mut qap_share.a),
+            mem::take(&mut qap_share.b),
+            mem::take(&mut qap_share.c),
+        ],
+        [&fft_mask[0], &fft_mask[1], &fft_mask[2]],
+        FftOrdering::Natural,
+        &domain,
+        root_of_unity,
+        pp,
+        net,
+        [CHANNEL0, CHANNEL1, CHANNEL2],
+    )
+    .await?;
+
+    circom_h_from_evals(a_eval, b_eval, c_eval, degred_mask, pp, net).await
+}
+
+/// Same as circom_h but runs the ifft and the fft of a, b and c as separate
+/// rounds, so that their outputs can be reconstructed at the king, and logs
+/// the first index where each diverges from `expected`.
+/// fft_mask: ifft a, ifft b, ifft c, fft a, fft b, fft c, sampled with
+/// FftMask::sample. expected: the same transforms in the clear.
+/// Reveals the intermediate values to the king, only to be used for debugging.
+#[cfg(feature = "debug-fft")]
+pub async fn circom_h_checked<
+    F: FftField + PrimeField,
+    D: EvaluationDomain<F>,
+    Net: MpcSerNet,
+>(
+    mut qap_share: PackedQAPShare<F, D>,
+    fft_mask: &[FftMask<F>; 6],
     degred_mask: &DegRedMask<F, F>,
     pp: &PackedSharingParams<F>,
     net: &Net,
+    expected: &[Vec<F>; 6],
 ) -> Result<Vec<F>, MpcNetError> {
     const CHANNEL0: MultiplexedStreamID = MultiplexedStreamID::Zero;
     const CHANNEL1: MultiplexedStreamID = MultiplexedStreamID::One;
     const CHANNEL2: MultiplexedStreamID = MultiplexedStreamID::Two;
 
     let domain = qap_share.domain;
-    let root_of_unity = {
-        let domain_size_double = 2 * domain.size();
-        let domain_double =
-            Radix2EvaluationDomain::<F>::new(domain_size_double).unwrap();
-        domain_double.element(1)
-    };
+    let root_of_unity = circom_shift(&domain);
 
     let a_coeff_fut = d_ifft(
-        qap_share.a,
+        mem::take(&mut qap_share.a),
         &fft_mask[0],
-        true,
+        FftOrdering::BitReversed,
         &domain,
         root_of_unity,
         pp,
@@ -135,9 +167,9 @@ pub async fn circom_h<
         CHANNEL0,
     );
     let b_coeff_fut = d_ifft(
-        qap_share.b,
+        mem::take(&mut qap_share.b),
         &fft_mask[1],
-        true,
+        FftOrdering::BitReversed,
         &domain,
         root_of_unity,
         pp,
@@ -145,9 +177,9 @@ pub async fn circom_h<
         CHANNEL1,
     );
     let c_coeff_fut = d_ifft(
-        qap_share.c,
+        mem::take(&mut qap_share.c),
         &fft_mask[2],
-        true,
+        FftOrdering::BitReversed,
         &domain,
         root_of_unity,
         pp,
@@ -158,26 +190,272 @@ pub async fn circom_h<
     let (a_coeff, b_coeff, c_coeff) =
         tokio::try_join!(a_coeff_fut, b_coeff_fut, c_coeff_fut)?;
 
-    let a_eval_fut =
-        d_fft(a_coeff, &fft_mask[3], false, &domain, pp, net, CHANNEL0);
-    let b_eval_fut =
-        d_fft(b_coeff, &fft_mask[4], false, &domain, pp, net, CHANNEL1);
-    let c_eval_fut =
-        d_fft(c_coeff, &fft_mask[5], false, &domain, pp, net, CHANNEL2);
+    tokio::try_join!(
+        d_check_against(
+            "stage 0 (ifft a)",
+            &a_coeff,
+            &expected[0],
+            FftOrdering::BitReversed,
+            pp,
+            net,
+            CHANNEL0
+        ),
+        d_check_against(
+            "stage 1 (ifft b)",
+            &b_coeff,
+            &expected[1],
+            FftOrdering::BitReversed,
+            pp,
+            net,
+            CHANNEL1
+        ),
+        d_check_against(
+            "stage 2 (ifft c)",
+            &c_coeff,
+            &expected[2],
+            FftOrdering::BitReversed,
+            pp,
+            net,
+            CHANNEL2
+        ),
+    )?;
+
+    let a_eval_fut = d_fft(
+        a_coeff,
+        &fft_mask[3],
+        FftOrdering::Natural,
+        &domain,
+        pp,
+        net,
+        CHANNEL0,
+    );
+    let b_eval_fut = d_fft(
+        b_coeff,
+        &fft_mask[4],
+        FftOrdering::Natural,
+        &domain,
+        pp,
+        net,
+        CHANNEL1,
+    );
+    let c_eval_fut = d_fft(
+        c_coeff,
+        &fft_mask[5],
+        FftOrdering::Natural,
+        &domain,
+        pp,
+        net,
+        CHANNEL2,
+    );
 
     // evaluations of a, b, c over the coset
     let (a_eval, b_eval, c_eval) =
         tokio::try_join!(a_eval_fut, b_eval_fut, c_eval_fut)?;
 
-    // compute (ab-c)
+    tokio::try_join!(
+        d_check_against(
+            "stage 3 (fft a)",
+            &a_eval,
+            &expected[3],
+            FftOrdering::Natural,
+            pp,
+            net,
+            CHANNEL0
+        ),
+        d_check_against(
+            "stage 4 (fft b)",
+            &b_eval,
+            &expected[4],
+            FftOrdering::Natural,
+            pp,
+            net,
+            CHANNEL1
+        ),
+        d_check_against(
+            "stage 5 (fft c)",
+            &c_eval,
+            &expected[5],
+            FftOrdering::Natural,
+            pp,
+            net,
+            CHANNEL2
+        ),
+    )?;
+
+    circom_h_from_evals(a_eval, b_eval, c_eval, degred_mask, pp, net).await
+}
+
+/// h = ab-c from the evaluations of a, b and c, brought back to degree t+l-1
+async fn circom_h_from_evals<F: FftField + PrimeField, Net: MpcSerNet>(
+    a_eval: Vec<F>,
+    b_eval: Vec<F>,
+    c_eval: Vec<F>,
+    degred_mask: &DegRedMask<F, F>,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+) -> Result<Vec<F>, MpcNetError> {
     let h_eval = cfg_into_iter!(a_eval)
         .zip(b_eval)
         .zip(c_eval)
-        .map(|((a, b), c)| (a * b - c))
+        .map(|((a, b), c)| a * b - c)
         .collect::<Vec<_>>();
 
-    let h_eval_red = deg_red(h_eval, degred_mask, pp, net, CHANNEL0).await?;
-    Ok(h_eval_red)
+    deg_red(h_eval, degred_mask, pp, net, MultiplexedStreamID::Zero).await
+}
+
+/// Checks that a o b = c on the whole constraint domain, i.e that the witness
+/// satisfies the R1CS, without reconstructing it. Cheap compared to the MSMs,
+/// so the king can reject a bad assignment before proving.
+/// The evaluations are batched with powers of a jointly sampled challenge,
+/// brought back to degree t+l-1 with degred_mask (sampled with num = 1)
+/// and tested with d_is_zero and zero_mask. Neither mask may be reused.
+pub async fn d_check_satisfied<
+    F: FftField + PrimeField,
+    D: EvaluationDomain<F>,
+    Net: MpcSerNet,
+>(
+    qap_share: &PackedQAPShare<F, D>,
+    degred_mask: &DegRedMask<F, F>,
+    zero_mask: &ZeroMask<F>,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+    rng: &mut (impl Rng + Send),
+) -> Result<bool, MpcNetError> {
+    let challenge = d_rand(pp, net, sid, rng).await?;
+
+    let batched = qap_share
+        .a
+        .iter()
+        .zip(&qap_share.b)
+        .zip(&qap_share.c)
+        .rev()
+        .fold(F::zero(), |acc, ((a, b), c)| acc * challenge + (*a * b - c));
+
+    let reduced = deg_red(vec![batched], degred_mask, pp, net, sid).await?;
+    d_is_zero(reduced[0], zero_mask, pp, net, sid).await
+}
+
+/// Distributed counterpart of an arkworks R1CS to QAP reduction
+/// Lets the prover compute shares of h the way the verifier's reduction expects
+#[async_trait]
+pub trait DistributedR1CSToQAP: R1CSToQAP {
+    /// Preprocessed masks consumed by a single h computation
+    type Masks<F: FftField + PrimeField>: Send + Sync;
+
+    async fn d_h<
+        F: FftField + PrimeField,
+        D: EvaluationDomain<F> + Send,
+        Net: MpcSerNet,
+    >(
+        qap_share: PackedQAPShare<F, D>,
+        masks: &Self::Masks<F>,
+        pp: &PackedSharingParams<F>,
+        net: &Net,
+    ) -> Result<Vec<F>, MpcNetError>;
+
+    /// Samples the masks of d_h for a QAP over domain and returns those of
+    /// n parties, with the coset offsets the reduction expects
+    fn sample_masks<F: FftField + PrimeField, D: EvaluationDomain<F>>(
+        domain: &D,
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Vec<Self::Masks<F>>;
+}
+
+/// Hands out the i-th mask of every one of the K sampled vectors to party i
+fn masks_per_party<F: FftField + PrimeField, const K: usize>(
+    masks: [Vec<FftMask<F>>; K],
+) -> impl Iterator<Item = [FftMask<F>; K]> {
+    let n = masks[0].len();
+    let mut masks = masks.map(Vec::into_iter);
+    (0..n).map(move |_| {
+        core::array::from_fn(|k| masks[k].next().expect("n masks each"))
+    })
+}
+
+#[async_trait]
+impl DistributedR1CSToQAP for LibsnarkReduction {
+    type Masks<F: FftField + PrimeField> = [FftMask<F>; 4];
+
+    async fn d_h<
+        F: FftField + PrimeField,
+        D: EvaluationDomain<F> + Send,
+        Net: MpcSerNet,
+    >(
+        qap_share: PackedQAPShare<F, D>,
+        masks: &Self::Masks<F>,
+        pp: &PackedSharingParams<F>,
+        net: &Net,
+    ) -> Result<Vec<F>, MpcNetError> {
+        libsnark_h(qap_share, masks, pp, net).await
+    }
+
+    fn sample_masks<F: FftField + PrimeField, D: EvaluationDomain<F>>(
+        domain: &D,
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Vec<Self::Masks<F>> {
+        let coset_dom = domain.get_coset(F::GENERATOR).unwrap();
+        let masks: [Vec<FftMask<F>>; 4] = core::array::from_fn(|i| match i {
+            // ifft of a, b, c then fft over the coset
+            0..=2 => FftMask::sample_ifft_then_fft(
+                FftOrdering::BitReversed,
+                coset_dom.coset_offset(),
+                domain,
+                pp,
+                rng,
+            ),
+            // coset ifft of h
+            _ => FftMask::sample(
+                FftOrdering::Natural,
+                coset_dom.coset_offset_inv(),
+                domain.group_gen_inv(),
+                domain.size(),
+                pp,
+                rng,
+            ),
+        });
+        masks_per_party(masks).collect()
+    }
+}
+
+#[async_trait]
+impl DistributedR1CSToQAP for CircomReduction {
+    type Masks<F: FftField + PrimeField> = ([FftMask<F>; 3], DegRedMask<F, F>);
+
+    async fn d_h<
+        F: FftField + PrimeField,
+        D: EvaluationDomain<F> + Send,
+        Net: MpcSerNet,
+    >(
+        qap_share: PackedQAPShare<F, D>,
+        masks: &Self::Masks<F>,
+        pp: &PackedSharingParams<F>,
+        net: &Net,
+    ) -> Result<Vec<F>, MpcNetError> {
+        circom_h(qap_share, &masks.0, &masks.1, pp, net).await
+    }
+
+    fn sample_masks<F: FftField + PrimeField, D: EvaluationDomain<F>>(
+        domain: &D,
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Vec<Self::Masks<F>> {
+        let fft_masks: [Vec<FftMask<F>>; 3] = core::array::from_fn(|_| {
+            FftMask::sample_ifft_then_fft(
+                FftOrdering::Natural,
+                circom_shift(domain),
+                domain,
+                pp,
+                rng,
+            )
+        });
+        let degred_masks =
+            DegRedMask::sample(pp, F::one(), domain.size() / pp.l, rng);
+
+        masks_per_party(fft_masks).zip(degred_masks).collect()
+    }
 }
 
 #[cfg(test)]
@@ -187,10 +465,14 @@ mod tests {
     use ark_circom::{CircomBuilder, CircomConfig, CircomReduction};
     use ark_groth16::r1cs_to_qap::R1CSToQAP;
     use ark_poly::Radix2EvaluationDomain;
+    use ark_relations::lc;
     use ark_relations::r1cs::ConstraintSynthesizer;
     use ark_relations::r1cs::ConstraintSystem;
+    use ark_relations::r1cs::ConstraintSystemRef;
+    use ark_relations::r1cs::SynthesisError;
     use ark_std::cfg_iter_mut;
-    use ark_std::One;
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use ark_std::{One, UniformRand};
     use dist_primitives::utils::deg_red::DegRedMask;
     use dist_primitives::utils::pack::transpose;
     use mpc_net::LocalTestNet;
@@ -201,6 +483,43 @@ mod tests {
     use super::*;
     use mpc_net::MpcNet;
 
+    /// Proves knowledge of the `num_steps` successive squarings of a public x.
+    /// Chosen so that `num_constraints + num_inputs` is not a power of two.
+    #[derive(Clone)]
+    struct SquaringCircuit<F: PrimeField> {
+        x: F,
+        num_steps: usize,
+    }
+
+    impl<F: PrimeField> ConstraintSynthesizer<F> for SquaringCircuit<F> {
+        fn generate_constraints(
+            self,
+            cs: ConstraintSystemRef<F>,
+        ) -> Result<(), SynthesisError> {
+            let x = cs.new_input_variable(|| Ok(self.x))?;
+            let mut cur_val = self.x;
+            let mut cur_var = x;
+            for _ in 0..self.num_steps {
+                let next_val = cur_val.square();
+                let next_var = cs.new_witness_variable(|| Ok(next_val))?;
+                cs.enforce_constraint(
+                    lc!() + cur_var,
+                    lc!() + cur_var,
+                    lc!() + next_var,
+                )?;
+                cur_val = next_val;
+                cur_var = next_var;
+            }
+            let out = cs.new_input_variable(|| Ok(cur_val))?;
+            cs.enforce_constraint(
+                lc!() + cur_var,
+                lc!() + ConstraintSystem::<F>::one(),
+                lc!() + out,
+            )?;
+            Ok(())
+        }
+    }
+
     fn libsnark_ref<F: PrimeField>(
         mut a: Vec<F>,
         mut b: Vec<F>,
@@ -310,87 +629,20 @@ mod tests {
             c,
             domain,
         };
-        let qap_shares = qap.pss(&pp);
+        let qap_shares = qap.pss(&pp, &mut thread_rng());
 
-        let coset_dom = domain.get_coset(Bn254Fr::GENERATOR).unwrap();
-        let fft_masks = [
-            FftMask::<Bn254Fr>::sample(
-                true,
-                coset_dom.coset_offset(),
-                domain.group_gen_inv(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                true,
-                coset_dom.coset_offset(),
-                domain.group_gen_inv(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                true,
-                coset_dom.coset_offset(),
-                domain.group_gen_inv(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                true,
-                Bn254Fr::one(),
-                domain.group_gen(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                true,
-                Bn254Fr::one(),
-                domain.group_gen(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                true,
-                Bn254Fr::one(),
-                domain.group_gen(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                false,
-                coset_dom.coset_offset_inv(),
-                domain.group_gen_inv(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-        ];
+        let masks = LibsnarkReduction::sample_masks(&domain, &pp, rng);
 
         let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
 
         let result = network
             .simulate_network_round(
-                (pp.clone(), qap_shares, fft_masks),
-                |net, (pp, qap_shares, fft_masks)| async move {
-                    let fft_mask = [
-                        fft_masks[0][net.party_id() as usize].clone(),
-                        fft_masks[1][net.party_id() as usize].clone(),
-                        fft_masks[2][net.party_id() as usize].clone(),
-                        fft_masks[3][net.party_id() as usize].clone(),
-                        fft_masks[4][net.party_id() as usize].clone(),
-                        fft_masks[5][net.party_id() as usize].clone(),
-                        fft_masks[6][net.party_id() as usize].clone(),
-                    ];
-
-                    libsnark_h(
-                        qap_shares[net.party_id() as usize].clone(),
-                        &fft_mask,
+                (pp.clone(), qap_shares, masks),
+                |net, (pp, qap_shares, masks)| async move {
+                    let idx = net.party_id() as usize;
+                    LibsnarkReduction::d_h(
+                        qap_shares[idx].clone(),
+                        &masks[idx],
                         &pp,
                         &net,
                     )
@@ -433,68 +685,21 @@ mod tests {
             c,
             domain,
         };
-        let qap_shares = qap.pss(&pp);
+        let qap_shares = qap.pss(&pp, &mut thread_rng());
         let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
         let rng = &mut thread_rng();
 
-        let root_of_unity = {
-            let domain_size_double = 2 * domain.size();
-            let domain_double =
-                Radix2EvaluationDomain::<Bn254Fr>::new(domain_size_double)
-                    .unwrap();
-            domain_double.element(1)
-        };
+        let root_of_unity = circom_shift(&domain);
 
-        let fft_masks = [
-            FftMask::<Bn254Fr>::sample(
-                true,
+        let fft_masks: [_; 3] = core::array::from_fn(|_| {
+            FftMask::<Bn254Fr>::sample_ifft_then_fft(
+                FftOrdering::Natural,
                 root_of_unity,
-                domain.group_gen_inv(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                true,
-                root_of_unity,
-                domain.group_gen_inv(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                true,
-                root_of_unity,
-                domain.group_gen_inv(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                false,
-                Bn254Fr::one(),
-                domain.group_gen(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                false,
-                Bn254Fr::one(),
-                domain.group_gen(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                false,
-                Bn254Fr::one(),
-                domain.group_gen(),
-                domain.size(),
+                &domain,
                 &pp,
                 rng,
-            ),
-        ];
+            )
+        });
 
         let degred_masks = DegRedMask::<Bn254Fr, Bn254Fr>::sample(
             &pp,
@@ -511,9 +716,6 @@ mod tests {
                         fft_masks[0][net.party_id() as usize].clone(),
                         fft_masks[1][net.party_id() as usize].clone(),
                         fft_masks[2][net.party_id() as usize].clone(),
-                        fft_masks[3][net.party_id() as usize].clone(),
-                        fft_masks[4][net.party_id() as usize].clone(),
-                        fft_masks[5][net.party_id() as usize].clone(),
                     ];
 
                     circom_h(
@@ -571,69 +773,22 @@ mod tests {
         .unwrap();
         let pp = PackedSharingParams::new(2);
         let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
-        let qap_shares = qap.pss(&pp);
+        let qap_shares = qap.pss(&pp, &mut thread_rng());
 
         let domain = qap_shares[0].domain;
         let rng = &mut thread_rng();
 
-        let root_of_unity = {
-            let domain_size_double = 2 * domain.size();
-            let domain_double =
-                Radix2EvaluationDomain::<Bn254Fr>::new(domain_size_double)
-                    .unwrap();
-            domain_double.element(1)
-        };
+        let root_of_unity = circom_shift(&domain);
 
-        let fft_masks = [
-            FftMask::<Bn254Fr>::sample(
-                true,
+        let fft_masks: [_; 3] = core::array::from_fn(|_| {
+            FftMask::<Bn254Fr>::sample_ifft_then_fft(
+                FftOrdering::Natural,
                 root_of_unity,
-                domain.group_gen_inv(),
-                domain.size(),
+                &domain,
                 &pp,
                 rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                true,
-                root_of_unity,
-                domain.group_gen_inv(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                true,
-                root_of_unity,
-                domain.group_gen_inv(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                false,
-                Bn254Fr::one(),
-                domain.group_gen(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                false,
-                Bn254Fr::one(),
-                domain.group_gen(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-            FftMask::<Bn254Fr>::sample(
-                false,
-                Bn254Fr::one(),
-                domain.group_gen(),
-                domain.size(),
-                &pp,
-                rng,
-            ),
-        ];
+            )
+        });
 
         let degred_masks = DegRedMask::<Bn254Fr, Bn254Fr>::sample(
             &pp,
@@ -650,9 +805,6 @@ mod tests {
                         fft_masks[0][net.party_id() as usize].clone(),
                         fft_masks[1][net.party_id() as usize].clone(),
                         fft_masks[2][net.party_id() as usize].clone(),
-                        fft_masks[3][net.party_id() as usize].clone(),
-                        fft_masks[4][net.party_id() as usize].clone(),
-                        fft_masks[5][net.party_id() as usize].clone(),
                     ];
 
                     circom_h(
@@ -675,4 +827,137 @@ mod tests {
 
         assert_eq!(h, computed_h);
     }
+
+    #[tokio::test]
+    async fn ext_witness_padded_domain() {
+        let circuit = SquaringCircuit {
+            x: Bn254Fr::from(3u64),
+            num_steps: 36,
+        };
+        let cs = ConstraintSystem::<Bn254Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.finalize();
+        assert!(cs.is_satisfied().unwrap());
+        let matrices = cs.to_matrices().unwrap();
+        let full_assignment = {
+            let cs = cs.borrow().unwrap();
+            [
+                cs.instance_assignment.as_slice(),
+                cs.witness_assignment.as_slice(),
+            ]
+            .concat()
+        };
+
+        let num_inputs = matrices.num_instance_variables;
+        let num_constraints = matrices.num_constraints;
+        // the domain is rounded up and the tail rows are zero padding
+        assert!(!(num_constraints + num_inputs).is_power_of_two());
+
+        let h = CircomReduction::witness_map_from_matrices::<
+            Bn254Fr,
+            Radix2EvaluationDomain<_>,
+        >(
+            &matrices, num_inputs, num_constraints, &full_assignment
+        )
+        .unwrap();
+
+        let qap = crate::qap::qap::<Bn254Fr, Radix2EvaluationDomain<_>>(
+            &matrices,
+            &full_assignment,
+        )
+        .unwrap();
+        assert!(qap.domain.size() > num_constraints + num_inputs);
+
+        let pp = PackedSharingParams::new(2);
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let qap_shares = qap.pss(&pp, &mut thread_rng());
+
+        let domain = qap_shares[0].domain;
+        let rng = &mut thread_rng();
+
+        let masks = CircomReduction::sample_masks(&domain, &pp, rng);
+
+        let result = network
+            .simulate_network_round(
+                (pp, qap_shares, masks),
+                |net, (pp, qap_shares, masks)| async move {
+                    let idx = net.party_id() as usize;
+                    CircomReduction::d_h(
+                        qap_shares[idx].clone(),
+                        &masks[idx],
+                        &pp,
+                        &net,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        let computed_h = transpose(result)
+            .into_iter()
+            .flat_map(|x| pp.unpack(x))
+            .collect::<Vec<_>>();
+
+        assert_eq!(h, computed_h);
+    }
+
+    #[tokio::test]
+    async fn d_check_satisfied_rejects_bad_witness() {
+        let m = 32usize;
+        let rng = &mut thread_rng();
+        let pp = PackedSharingParams::<Bn254Fr>::new(2);
+        let domain = Radix2EvaluationDomain::<Bn254Fr>::new(m).unwrap();
+
+        let a = (0..m).map(|_| Bn254Fr::rand(rng)).collect::<Vec<_>>();
+        let b = (0..m).map(|_| Bn254Fr::rand(rng)).collect::<Vec<_>>();
+        let c = a.iter().zip(&b).map(|(a, b)| a * b).collect::<Vec<_>>();
+        let mut bad_c = c.clone();
+        bad_c[3] += Bn254Fr::one();
+
+        let qap_shares = [c, bad_c].map(|c| {
+            QAP::<Bn254Fr, Radix2EvaluationDomain<_>> {
+                num_inputs: 0,
+                num_constraints: 0,
+                a: a.clone(),
+                b: b.clone(),
+                c,
+                domain,
+            }
+            .pss(&pp, rng)
+        });
+        let degred_masks =
+            [(); 2].map(|_| DegRedMask::sample(&pp, Bn254Fr::one(), 1, rng));
+        let zero_masks = [(); 2].map(|_| ZeroMask::sample(&pp, rng));
+
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let result = network
+            .simulate_network_round(
+                (pp, qap_shares, degred_masks, zero_masks),
+                |net, (pp, qap_shares, degred_masks, zero_masks)| async move {
+                    let idx = net.party_id() as usize;
+                    let rng = &mut StdRng::seed_from_u64(idx as u64);
+                    let mut satisfied = Vec::new();
+                    for i in 0..2 {
+                        satisfied.push(
+                            d_check_satisfied(
+                                &qap_shares[i][idx],
+                                &degred_masks[i][idx],
+                                &zero_masks[i][idx],
+                                &pp,
+                                &net,
+                                MultiplexedStreamID::Zero,
+                                rng,
+                            )
+                            .await
+                            .unwrap(),
+                        );
+                    }
+                    satisfied
+                },
+            )
+            .await;
+
+        assert!(result.iter().all(|r| *r == vec![true, false]));
+    }
 }
@@ -1,10 +1,23 @@
 #![allow(non_snake_case, clippy::too_many_arguments)]
 
+use crate::ext_wit::DistributedR1CSToQAP;
+use crate::proving_key::PackedProvingKeyShare;
+use crate::qap::PackedQAPShare;
 use ark_ec::pairing::Pairing;
-use ark_std::Zero;
+use ark_ec::{CurveGroup, VariableBaseMSM};
+use ark_ff::FftField;
+use ark_groth16::{prepare_verifying_key, Groth16, Proof, VerifyingKey};
+use ark_poly::EvaluationDomain;
+use ark_std::{rand::Rng, One, Zero};
 use dist_primitives::dmsm::{d_msm, MsmMask};
+use dist_primitives::dreshare::{d_reshare_recv, d_reshare_send};
+use dist_primitives::utils::pack::transpose;
+use dist_primitives::utils::PackedSharingParamsExt;
+use mpc_net::ser_net::MpcSerNet;
+use mpc_net::subnet::SubNet;
 use mpc_net::{MpcNet, MpcNetError, MultiplexedStreamID};
 use secret_sharing::pss::PackedSharingParams;
+use tokio::sync::mpsc;
 
 /// A = L.(N)^r.∏{i∈[0,m]}(S_i)^a_i
 #[derive(Debug, Clone, Copy)]
@@ -182,7 +195,7 @@ pub struct C<'a, E: Pairing> {
     /// H is `b_g1_query[1..]`
     pub H: &'a [E::G1Affine],
     pub pp: &'a PackedSharingParams<E::ScalarField>,
-    /// a is `input_assignment`
+    /// a is `full_assignment[1..]`, see [`WitnessShares`]
     pub a: &'a [E::ScalarField],
     /// ax is `aux_assignment`
     pub ax: &'a [E::ScalarField],
@@ -191,19 +204,17 @@ pub struct C<'a, E: Pairing> {
 }
 
 impl<'a, E: Pairing> C<'a, E> {
-    /// Computes C
+    /// Computes C, running its two MSMs concurrently on sids
     pub async fn compute<Net: MpcNet>(
         self,
         msm_mask: &[MsmMask<E::G1>; 2],
         net: &Net,
+        sids: [MultiplexedStreamID; 2],
     ) -> Result<E::G1, MpcNetError> {
         // We use variables (A, M, ∏{i∈[l+1,m]}(W_i)^a_i, ∏{i∈[0,Q−2]}(U_i)h_i, ∏{i∈[0,m]}(H_i)^a_i)
         // to denote elements in G1. We also assume that all the servers computing the proof
         // get A, M, s, r and h in the clear and only receive packed shares of the remaining elements.
 
-        const CHANNEL0: MultiplexedStreamID = MultiplexedStreamID::Zero;
-        const CHANNEL1: MultiplexedStreamID = MultiplexedStreamID::One;
-
         // Calculate ∏{i∈[l+1,m]}(W_i)^a_i using dmsm
         // NOTE: this `l_aux_acc`
         let w = d_msm::<E::G1, _>(
@@ -212,7 +223,7 @@ impl<'a, E: Pairing> C<'a, E> {
             &msm_mask[0],
             self.pp,
             net,
-            CHANNEL0,
+            sids[0],
         );
         // Calculate ∏{i∈[0,Q−2]}(U_i)^h_i using dmsm
         // NOTE: this `h_acc`
@@ -222,7 +233,7 @@ impl<'a, E: Pairing> C<'a, E> {
             &msm_mask[1],
             self.pp,
             net,
-            CHANNEL1,
+            sids[1],
         );
         let (w, u) = tokio::try_join!(w, u)?;
 
@@ -236,3 +247,1355 @@ impl<'a, E: Pairing> C<'a, E> {
         Ok(C)
     }
 }
+
+/// One party's packed shares of the assignment,
+/// split the way the proving key queries are indexed
+#[derive(Debug, Clone, PartialEq)]
+pub struct WitnessShares<F> {
+    /// a is `full_assignment[1..]`, used with `a_query[1..]` and `b_query[1..]`
+    pub a: Vec<F>,
+    /// ax is `aux_assignment = full_assignment[num_inputs..]`, used with `l_query`
+    pub ax: Vec<F>,
+}
+
+impl<F: FftField> WitnessShares<F> {
+    /// Packs the full assignment (instance then witness) and returns the shares of each party
+    /// num_inputs counts the instance variables, including the leading one
+    pub fn pack(
+        full_assignment: &[F],
+        num_inputs: usize,
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Vec<Self> {
+        assert!(
+            num_inputs >= 1 && num_inputs <= full_assignment.len(),
+            "num_inputs out of range"
+        );
+        let a = Self::pack_padded(&full_assignment[1..], pp, rng);
+        let ax = Self::pack_padded(&full_assignment[num_inputs..], pp, rng);
+
+        a.into_iter()
+            .zip(ax)
+            .map(|(a, ax)| Self { a, ax })
+            .collect()
+    }
+
+    /// Packs chunks of l values, padding the last one with zeros
+    fn pack_padded(
+        values: &[F],
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Vec<Vec<F>> {
+        let packed = values
+            .chunks(pp.l)
+            .map(|chunk| {
+                let mut secrets = chunk.to_vec();
+                secrets.resize(pp.l, F::zero());
+                pp.pack(secrets, rng)
+            })
+            .collect::<Vec<_>>();
+
+        if packed.is_empty() {
+            return vec![Vec::new(); pp.n];
+        }
+        transpose(packed)
+    }
+}
+
+/// A share of one element of the proof, see prove_shares_streaming
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofPart<E: Pairing> {
+    A(E::G1),
+    B(E::G2),
+    C(E::G1),
+}
+
+/// Computes shares of the proof (A, B, C) from one party's share of the proving key,
+/// its witness shares and its share of h
+pub async fn prove_shares<E: Pairing, Net: MpcNet>(
+    crs_share: &PackedProvingKeyShare<E>,
+    witness: &WitnessShares<E::ScalarField>,
+    h_share: &[E::ScalarField],
+    r_share: E::ScalarField,
+    s_share: E::ScalarField,
+    g1_msm_mask: &[MsmMask<E::G1>; 4],
+    g2_msm_mask: &MsmMask<E::G2>,
+    pp: &PackedSharingParams<E::ScalarField>,
+    net: &Net,
+) -> Result<(E::G1, E::G2, E::G1), MpcNetError> {
+    prove_shares_inner(
+        crs_share,
+        witness,
+        h_share,
+        r_share,
+        s_share,
+        g1_msm_mask,
+        g2_msm_mask,
+        pp,
+        net,
+        None,
+    )
+    .await
+}
+
+/// Same as prove_shares, but also sends each share of A, B and C to `parts`
+/// as soon as it is computed, e.g to report progress.
+/// The channel is unbounded so that a slow or gone receiver never stalls
+/// the remaining MSMs, which the other parties need this one to take part
+/// in. At most three parts are sent.
+pub async fn prove_shares_streaming<E: Pairing, Net: MpcNet>(
+    crs_share: &PackedProvingKeyShare<E>,
+    witness: &WitnessShares<E::ScalarField>,
+    h_share: &[E::ScalarField],
+    r_share: E::ScalarField,
+    s_share: E::ScalarField,
+    g1_msm_mask: &[MsmMask<E::G1>; 4],
+    g2_msm_mask: &MsmMask<E::G2>,
+    pp: &PackedSharingParams<E::ScalarField>,
+    net: &Net,
+    parts: &mpsc::UnboundedSender<ProofPart<E>>,
+) -> Result<(E::G1, E::G2, E::G1), MpcNetError> {
+    prove_shares_inner(
+        crs_share,
+        witness,
+        h_share,
+        r_share,
+        s_share,
+        g1_msm_mask,
+        g2_msm_mask,
+        pp,
+        net,
+        Some(parts),
+    )
+    .await
+}
+
+async fn prove_shares_inner<E: Pairing, Net: MpcNet>(
+    crs_share: &PackedProvingKeyShare<E>,
+    witness: &WitnessShares<E::ScalarField>,
+    h_share: &[E::ScalarField],
+    r_share: E::ScalarField,
+    s_share: E::ScalarField,
+    g1_msm_mask: &[MsmMask<E::G1>; 4],
+    g2_msm_mask: &MsmMask<E::G2>,
+    pp: &PackedSharingParams<E::ScalarField>,
+    net: &Net,
+    parts: Option<&mpsc::UnboundedSender<ProofPart<E>>>,
+) -> Result<(E::G1, E::G2, E::G1), MpcNetError> {
+    let send = |part| {
+        if let Some(parts) = parts {
+            // a dropped receiver only means nobody watches the progress
+            let _ = parts.send(part);
+        }
+    };
+
+    let pi_a_share = A::<E> {
+        L: crs_share.a_query0,
+        N: crs_share.delta_g1,
+        AG1: crs_share.alpha_g1,
+        r: r_share,
+        pp,
+        S: &crs_share.s,
+        a: &witness.a,
+    }
+    .compute(&g1_msm_mask[0], net, MultiplexedStreamID::Zero)
+    .await?;
+    send(ProofPart::A(pi_a_share));
+
+    let pi_b_g1_share = BInG1::<E> {
+        Z: crs_share.b_g1_query0,
+        K: crs_share.delta_g1,
+        BG1: crs_share.beta_g1,
+        r: r_share,
+        s: s_share,
+        pp,
+        H: &crs_share.h,
+        a: &witness.a,
+    }
+    .compute(&g1_msm_mask[1], net, MultiplexedStreamID::Zero)
+    .await?;
+
+    let pi_b_g2_share = BInG2::<E> {
+        Z: crs_share.b_g2_query0,
+        K: crs_share.delta_g2,
+        BG2: crs_share.beta_g2,
+        s: s_share,
+        pp,
+        V: &crs_share.v,
+        a: &witness.a,
+    }
+    .compute(g2_msm_mask, net, MultiplexedStreamID::Zero)
+    .await?;
+    send(ProofPart::B(pi_b_g2_share));
+
+    let pi_c_share = C::<E> {
+        W: &crs_share.w,
+        U: &crs_share.u,
+        A: pi_a_share,
+        B: pi_b_g1_share,
+        M: crs_share.delta_g1,
+        r: r_share,
+        s: s_share,
+        pp,
+        H: &crs_share.h,
+        a: &witness.a,
+        ax: &witness.ax,
+        h: h_share,
+    }
+    .compute(
+        &[g1_msm_mask[2].clone(), g1_msm_mask[3].clone()],
+        net,
+        [MultiplexedStreamID::Zero, MultiplexedStreamID::One],
+    )
+    .await?;
+    send(ProofPart::C(pi_c_share));
+
+    Ok((pi_a_share, pi_b_g2_share, pi_c_share))
+}
+
+/// Sends the shares of (A, B, C) to the king, who assembles the proof
+/// The king gets the proof and the clients get None, they never learn it
+pub async fn king_reconstruct_proof<E: Pairing, Net: MpcSerNet>(
+    a_share: E::G1,
+    b_share: E::G2,
+    c_share: E::G1,
+    pp: &PackedSharingParams<E::ScalarField>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Option<Proof<E>>, MpcNetError> {
+    king_reconstruct_and_verify_proof(
+        a_share, b_share, c_share, None, pp, net, sid,
+    )
+    .await
+}
+
+/// Assembles a proof from the shares of (A, B, C) of the given parties
+fn unpack_proof<E: Pairing>(
+    shares: Vec<(E::G1, E::G2, E::G1)>,
+    parties: &[u32],
+    pp: &PackedSharingParams<E::ScalarField>,
+) -> Proof<E> {
+    let mut a_shares = Vec::new();
+    let mut b_shares = Vec::new();
+    let mut c_shares = Vec::new();
+    for (a_share, b_share, c_share) in shares {
+        a_shares.push(a_share);
+        b_shares.push(b_share);
+        c_shares.push(c_share);
+    }
+
+    Proof {
+        a: pp.unpack_missing_shares(&a_shares, parties)[0].into_affine(),
+        b: pp.unpack_missing_shares(&b_shares, parties)[0].into_affine(),
+        c: pp.unpack_missing_shares(&c_shares, parties)[0].into_affine(),
+    }
+}
+
+/// What the king checks the proof against in king_reconstruct_and_verify_proof
+#[derive(Debug, Clone, Copy)]
+pub struct SelfVerify<'a, E: Pairing> {
+    pub vk: &'a VerifyingKey<E>,
+    /// The public inputs, without the leading one
+    pub public_inputs: &'a [E::ScalarField],
+}
+
+/// Runs the pairing check of Groth16 on a proof, true if it is valid
+pub fn king_self_verify<E: Pairing>(
+    proof: &Proof<E>,
+    vk: &VerifyingKey<E>,
+    public_inputs: &[E::ScalarField],
+) -> bool {
+    let pvk = prepare_verifying_key(vk);
+    Groth16::<E>::verify_proof(&pvk, proof, public_inputs).unwrap_or(false)
+}
+
+/// Same as king_reconstruct_proof, but with self_verify set the king also
+/// verifies the proof it assembled, so a bad proof is never handed out.
+/// If the proof does not verify the king returns a Protocol error instead,
+/// e.g to retry the job.
+pub async fn king_reconstruct_and_verify_proof<E: Pairing, Net: MpcSerNet>(
+    a_share: E::G1,
+    b_share: E::G2,
+    c_share: E::G1,
+    self_verify: Option<SelfVerify<'_, E>>,
+    pp: &PackedSharingParams<E::ScalarField>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Option<Proof<E>>, MpcNetError> {
+    let received_shares = net
+        .client_send_or_king_receive_serialized(
+            &(a_share, b_share, c_share),
+            sid,
+            pp.t,
+        )
+        .await?;
+
+    let proof =
+        received_shares.map(|rs| unpack_proof(rs.shares, &rs.parties, pp));
+
+    if let (Some(proof), Some(check)) = (&proof, self_verify) {
+        if !king_self_verify(proof, check.vk, check.public_inputs) {
+            return Err(MpcNetError::Protocol {
+                err: "self-verification failed".into(),
+                party: net.king_id(),
+            });
+        }
+    }
+    Ok(proof)
+}
+
+/// Packs vk.gamma_abc_g1 like the proving key, l points per share padded
+/// with zeros, and returns the shares of each party for d_public_input_msm
+pub fn pack_gamma_abc<E: Pairing>(
+    vk: &VerifyingKey<E>,
+    pp: &PackedSharingParams<E::ScalarField>,
+) -> Vec<Vec<E::G1Affine>> {
+    let packed = vk
+        .gamma_abc_g1
+        .chunks(pp.l)
+        .map(|chunk| {
+            let mut points =
+                chunk.iter().map(|p| (*p).into()).collect::<Vec<E::G1>>();
+            points.resize(pp.l, E::G1::zero());
+            E::G1::normalize_batch(&pp.det_pack(points))
+        })
+        .collect::<Vec<_>>();
+    transpose(packed)
+}
+
+/// Computes the public input term of the verifier,
+/// vk_x = gamma_abc_g1[0] + sum_i public_inputs[i] * gamma_abc_g1[i + 1],
+/// from this party's shares of gamma_abc_g1 (see pack_gamma_abc).
+/// The public inputs (without the leading one) are known to everyone, so
+/// they are packed without randomness and the king opens vk_x unmasked.
+/// The king gets vk_x, e.g to hand out with the proof for
+/// Groth16::verify_proof_with_prepared_inputs, the clients get None.
+pub async fn d_public_input_msm<E: Pairing, Net: MpcSerNet>(
+    gamma_abc_shares: &[E::G1Affine],
+    public_inputs: &[E::ScalarField],
+    pp: &PackedSharingParams<E::ScalarField>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Option<E::G1>, MpcNetError> {
+    pp.assert_matches(net)?;
+    // a king holding no share only receives, it may pass no shares
+    let share = match net.party_index() {
+        Some(idx) => {
+            public_input_share::<E>(gamma_abc_shares, public_inputs, idx, pp)?
+        }
+        None => E::G1::zero(),
+    };
+
+    // both operands are packed without randomness, so the product has
+    // degree 2(t + l - 1) and the king needs every share
+    let received_shares = net
+        .client_send_or_king_receive_serialized(&share, sid, pp.n)
+        .await?;
+    Ok(received_shares.map(|rs| pp.unpack2(rs.shares).into_iter().sum()))
+}
+
+/// The share of vk_x of the party with share index idx, see
+/// d_public_input_msm
+fn public_input_share<E: Pairing>(
+    gamma_abc_shares: &[E::G1Affine],
+    public_inputs: &[E::ScalarField],
+    idx: usize,
+    pp: &PackedSharingParams<E::ScalarField>,
+) -> Result<E::G1, MpcNetError> {
+    let num_scalars = gamma_abc_shares.len() * pp.l;
+    if public_inputs.len() + 1 > num_scalars {
+        return Err(MpcNetError::BadInput {
+            err: format!(
+                "d_public_input_msm: {} public inputs but {} bases",
+                public_inputs.len(),
+                num_scalars.saturating_sub(1)
+            ),
+        });
+    }
+
+    let mut scalars = vec![E::ScalarField::one()];
+    scalars.extend_from_slice(public_inputs);
+    scalars.resize(num_scalars, E::ScalarField::zero());
+    let scalar_shares = scalars
+        .chunks(pp.l)
+        .map(|chunk| pp.pack_from_public(chunk.to_vec())[idx])
+        .collect::<Vec<_>>();
+    E::G1::msm(gamma_abc_shares, &scalar_shares).map_err(|_| {
+        MpcNetError::BadInput {
+            err: format!(
+                "d_public_input_msm: msm of {} bases and {} scalars failed",
+                gamma_abc_shares.len(),
+                scalar_shares.len()
+            ),
+        }
+    })
+}
+
+/// One party's inputs for one statement of prove_batch.
+/// Every statement needs its own masks, they must not be reused.
+pub struct BatchStatement<'a, E, R, D>
+where
+    E: Pairing,
+    R: DistributedR1CSToQAP,
+    D: EvaluationDomain<E::ScalarField>,
+{
+    pub qap_share: PackedQAPShare<E::ScalarField, D>,
+    pub h_masks: &'a R::Masks<E::ScalarField>,
+    pub witness: &'a WitnessShares<E::ScalarField>,
+    pub r_share: E::ScalarField,
+    pub s_share: E::ScalarField,
+    pub g1_msm_mask: &'a [MsmMask<E::G1>; 4],
+    pub g2_msm_mask: &'a MsmMask<E::G2>,
+}
+
+/// Proves many statements of the same circuit, e.g the witnesses of many
+/// clients, and has the king assemble all the proofs in a single round.
+/// The statements are pipelined: h of the next statement is computed on
+/// channels Zero to Two while the MSMs of the current one run on channels
+/// Three to Five, those of A and B concurrently.
+/// The king gets the proofs in the order of statements, the clients None.
+pub async fn prove_batch<E, R, D, Net>(
+    statements: Vec<BatchStatement<'_, E, R, D>>,
+    crs_share: &PackedProvingKeyShare<E>,
+    pp: &PackedSharingParams<E::ScalarField>,
+    net: &Net,
+) -> Result<Option<Vec<Proof<E>>>, MpcNetError>
+where
+    E: Pairing,
+    R: DistributedR1CSToQAP,
+    D: EvaluationDomain<E::ScalarField> + Send,
+    Net: MpcSerNet,
+{
+    let num_statements = statements.len();
+    let (h_inputs, msm_inputs): (Vec<_>, Vec<_>) = statements
+        .into_iter()
+        .map(|statement| {
+            (
+                (statement.qap_share, statement.h_masks),
+                BatchMsmInputs {
+                    witness: statement.witness,
+                    r_share: statement.r_share,
+                    s_share: statement.s_share,
+                    g1_msm_mask: statement.g1_msm_mask,
+                    g2_msm_mask: statement.g2_msm_mask,
+                },
+            )
+        })
+        .unzip();
+
+    let mut h_inputs = h_inputs.into_iter();
+    let mut next_h = || {
+        let next = h_inputs.next();
+        async move {
+            match next {
+                Some((qap_share, h_masks)) => {
+                    R::d_h(qap_share, h_masks, pp, net).await.map(Some)
+                }
+                None => Ok(None),
+            }
+        }
+    };
+
+    let mut proof_shares = Vec::with_capacity(num_statements);
+    let mut h_share = next_h().await?;
+    for inputs in msm_inputs {
+        let h = h_share.expect("one h per statement");
+        let msms = batch_msms::<E, _>(inputs, &h, crs_share, pp, net);
+        let (shares, h) = tokio::try_join!(msms, next_h())?;
+        proof_shares.push(shares);
+        h_share = h;
+    }
+
+    let Some(rs) = net
+        .client_send_or_king_receive_serialized(
+            &proof_shares,
+            MultiplexedStreamID::Zero,
+            pp.t,
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+    if let Some((_, party)) = rs
+        .shares
+        .iter()
+        .zip(&rs.parties)
+        .find(|(shares, _)| shares.len() != num_statements)
+    {
+        return Err(MpcNetError::Protocol {
+            err: format!("expected the shares of {num_statements} proofs"),
+            party: *party,
+        });
+    }
+
+    let proofs = (0..num_statements)
+        .map(|i| {
+            let shares = rs.shares.iter().map(|shares| shares[i]).collect();
+            unpack_proof(shares, &rs.parties, pp)
+        })
+        .collect();
+    Ok(Some(proofs))
+}
+
+/// The inputs of a statement of prove_batch besides those of h
+struct BatchMsmInputs<'a, E: Pairing> {
+    witness: &'a WitnessShares<E::ScalarField>,
+    r_share: E::ScalarField,
+    s_share: E::ScalarField,
+    g1_msm_mask: &'a [MsmMask<E::G1>; 4],
+    g2_msm_mask: &'a MsmMask<E::G2>,
+}
+
+/// Computes the shares of (A, B, C) of a statement of prove_batch on
+/// channels Three to Five, leaving the others to the next h
+async fn batch_msms<E: Pairing, Net: MpcNet>(
+    inputs: BatchMsmInputs<'_, E>,
+    h_share: &[E::ScalarField],
+    crs_share: &PackedProvingKeyShare<E>,
+    pp: &PackedSharingParams<E::ScalarField>,
+    net: &Net,
+) -> Result<(E::G1, E::G2, E::G1), MpcNetError> {
+    let witness = inputs.witness;
+    let a = A::<E> {
+        L: crs_share.a_query0,
+        N: crs_share.delta_g1,
+        AG1: crs_share.alpha_g1,
+        r: inputs.r_share,
+        pp,
+        S: &crs_share.s,
+        a: &witness.a,
+    }
+    .compute(&inputs.g1_msm_mask[0], net, MultiplexedStreamID::Three);
+    let b_g1 = BInG1::<E> {
+        Z: crs_share.b_g1_query0,
+        K: crs_share.delta_g1,
+        BG1: crs_share.beta_g1,
+        r: inputs.r_share,
+        s: inputs.s_share,
+        pp,
+        H: &crs_share.h,
+        a: &witness.a,
+    }
+    .compute(&inputs.g1_msm_mask[1], net, MultiplexedStreamID::Four);
+    let b_g2 = BInG2::<E> {
+        Z: crs_share.b_g2_query0,
+        K: crs_share.delta_g2,
+        BG2: crs_share.beta_g2,
+        s: inputs.s_share,
+        pp,
+        V: &crs_share.v,
+        a: &witness.a,
+    }
+    .compute(inputs.g2_msm_mask, net, MultiplexedStreamID::Five);
+    let (a, b_g1, b_g2) = tokio::try_join!(a, b_g1, b_g2)?;
+
+    let c = C::<E> {
+        W: &crs_share.w,
+        U: &crs_share.u,
+        A: a,
+        B: b_g1,
+        M: crs_share.delta_g1,
+        r: inputs.r_share,
+        s: inputs.s_share,
+        pp,
+        H: &crs_share.h,
+        a: &witness.a,
+        ax: &witness.ax,
+        h: h_share,
+    }
+    .compute(
+        &[inputs.g1_msm_mask[2].clone(), inputs.g1_msm_mask[3].clone()],
+        net,
+        [MultiplexedStreamID::Three, MultiplexedStreamID::Four],
+    )
+    .await?;
+    Ok((a, b_g2, c))
+}
+
+/// Work a party does in prove_with_roles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Computes h, FFT heavy
+    Fft,
+    /// Computes the shares of A, B and C, MSM heavy
+    Msm,
+}
+
+impl Role {
+    /// With roles the network has 2n parties, parties 0..n compute h and
+    /// hand it over to parties n..2n, which compute the MSMs.
+    /// Returns the role of party_id and the first party of its committee
+    pub fn of<F: FftField>(
+        party_id: u32,
+        pp: &PackedSharingParams<F>,
+    ) -> (Self, u32) {
+        if (party_id as usize) < pp.n {
+            (Role::Fft, 0)
+        } else {
+            (Role::Msm, pp.n as u32)
+        }
+    }
+}
+
+/// One party's inputs to prove_with_roles, depending on its role
+pub enum RoleInput<'a, E, R, D>
+where
+    E: Pairing,
+    R: DistributedR1CSToQAP,
+    D: EvaluationDomain<E::ScalarField>,
+{
+    Fft {
+        qap_share: PackedQAPShare<E::ScalarField, D>,
+        h_masks: &'a R::Masks<E::ScalarField>,
+        /// Rerandomizes h for the MSM committee, see sample_reshare_mask
+        reshare_mask: &'a [E::ScalarField],
+    },
+    Msm {
+        crs_share: &'a PackedProvingKeyShare<E>,
+        witness: &'a WitnessShares<E::ScalarField>,
+        r_share: E::ScalarField,
+        s_share: E::ScalarField,
+        g1_msm_mask: &'a [MsmMask<E::G1>; 4],
+        g2_msm_mask: &'a MsmMask<E::G2>,
+    },
+}
+
+/// Same as R::d_h followed by prove_shares, but split by work type across
+/// two committees of n parties (see Role), e.g CPU machines for the FFTs
+/// and GPU machines for the MSMs. The FFT committee computes h and reshares
+/// it to the MSM committee, which computes the shares of (A, B, C).
+/// net connects the 2n parties of both committees. Returns the shares to
+/// the MSM committee and None to the FFT committee. The MSM committee can
+/// assemble the proof with king_reconstruct_proof on its SubNet.
+pub async fn prove_with_roles<E, R, D, Net>(
+    input: RoleInput<'_, E, R, D>,
+    pp: &PackedSharingParams<E::ScalarField>,
+    net: &Net,
+) -> Result<Option<(E::G1, E::G2, E::G1)>, MpcNetError>
+where
+    E: Pairing,
+    R: DistributedR1CSToQAP,
+    D: EvaluationDomain<E::ScalarField> + Send,
+    Net: MpcNet,
+{
+    let (role, offset) = Role::of(net.party_id(), pp);
+    let committee = SubNet::new(net, offset, pp.n)?;
+    // party i of the FFT committee hands h over to party i of the MSM one
+    let sid = MultiplexedStreamID::Zero;
+
+    match (role, input) {
+        (
+            Role::Fft,
+            RoleInput::Fft {
+                qap_share,
+                h_masks,
+                reshare_mask,
+            },
+        ) => {
+            let h_share = R::d_h(qap_share, h_masks, pp, &committee).await?;
+            let to = net.party_id() + pp.n as u32;
+            d_reshare_send(&h_share, reshare_mask, to, net, sid).await?;
+            Ok(None)
+        }
+        (
+            Role::Msm,
+            RoleInput::Msm {
+                crs_share,
+                witness,
+                r_share,
+                s_share,
+                g1_msm_mask,
+                g2_msm_mask,
+            },
+        ) => {
+            let from = net.party_id() - pp.n as u32;
+            let h_share = d_reshare_recv(from, net, sid).await?;
+            prove_shares(
+                crs_share,
+                witness,
+                &h_share,
+                r_share,
+                s_share,
+                g1_msm_mask,
+                g2_msm_mask,
+                pp,
+                &committee,
+            )
+            .await
+            .map(Some)
+        }
+        (role, _) => Err(MpcNetError::BadInput {
+            err: format!(
+                "prove_with_roles: party {} has role {:?} but got the input of the other",
+                net.party_id(),
+                role
+            ),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ark_bn254::{Bn254, Fr, G1Projective as G1, G2Projective as G2};
+    use ark_circom::CircomReduction;
+    use ark_crypto_primitives::snark::SNARK;
+    use ark_ff::{One, PrimeField, UniformRand};
+    use ark_groth16::{Groth16, ProvingKey};
+    use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+    use ark_relations::lc;
+    use ark_relations::r1cs::{
+        ConstraintMatrices, ConstraintSynthesizer, ConstraintSystem,
+        ConstraintSystemRef, SynthesisError,
+    };
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use dist_primitives::dfft::{FftMask, FftOrdering};
+    use dist_primitives::dreshare::sample_reshare_mask;
+    use dist_primitives::utils::deg_red::DegRedMask;
+    use mpc_net::{LocalTestNet, MpcNet};
+
+    use super::*;
+    use crate::ext_wit::DistributedR1CSToQAP;
+    use crate::qap::{qap, QAP};
+
+    /// Proves knowledge of w such that x = w^2 and y = w^3 for public x and y.
+    /// The extra squarings of w only grow the constraint system.
+    #[derive(Clone)]
+    struct CubeCircuit<F: PrimeField> {
+        w: F,
+        num_squarings: usize,
+    }
+
+    impl<F: PrimeField> ConstraintSynthesizer<F> for CubeCircuit<F> {
+        fn generate_constraints(
+            self,
+            cs: ConstraintSystemRef<F>,
+        ) -> Result<(), SynthesisError> {
+            let x = cs.new_input_variable(|| Ok(self.w.square()))?;
+            let y = cs.new_input_variable(|| Ok(self.w.square() * self.w))?;
+            let w = cs.new_witness_variable(|| Ok(self.w))?;
+            cs.enforce_constraint(lc!() + w, lc!() + w, lc!() + x)?;
+            cs.enforce_constraint(lc!() + w, lc!() + x, lc!() + y)?;
+
+            let mut cur = w;
+            let mut val = self.w;
+            for _ in 0..self.num_squarings {
+                val.square_in_place();
+                let next_val = val;
+                let next = cs.new_witness_variable(|| Ok(next_val))?;
+                cs.enforce_constraint(lc!() + cur, lc!() + cur, lc!() + next)?;
+                cur = next;
+            }
+            Ok(())
+        }
+    }
+
+    /// A CubeCircuit for w = 3 and its Groth16 keys
+    fn cube_setup(
+        num_squarings: usize,
+        rng: &mut StdRng,
+    ) -> (CubeCircuit<Fr>, ProvingKey<Bn254>, VerifyingKey<Bn254>) {
+        let circuit = CubeCircuit {
+            w: Fr::from(3u64),
+            num_squarings,
+        };
+        let (pk, vk) =
+            Groth16::<Bn254, CircomReduction>::circuit_specific_setup(
+                circuit.clone(),
+                rng,
+            )
+            .unwrap();
+        (circuit, pk, vk)
+    }
+
+    /// The R1CS of a circuit, its full assignment (instance then witness
+    /// variables) and its QAP
+    struct CubeInstance {
+        matrices: ConstraintMatrices<Fr>,
+        full_assignment: Vec<Fr>,
+        num_inputs: usize,
+        qap: QAP<Fr, Radix2EvaluationDomain<Fr>>,
+    }
+
+    fn cube_instance(circuit: CubeCircuit<Fr>) -> CubeInstance {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.finalize();
+        assert!(cs.is_satisfied().unwrap());
+        let matrices = cs.to_matrices().unwrap();
+        let full_assignment = {
+            let cs = cs.borrow().unwrap();
+            [
+                cs.instance_assignment.as_slice(),
+                cs.witness_assignment.as_slice(),
+            ]
+            .concat()
+        };
+        let qap =
+            qap::<Fr, Radix2EvaluationDomain<_>>(&matrices, &full_assignment)
+                .unwrap();
+
+        CubeInstance {
+            num_inputs: matrices.num_instance_variables,
+            matrices,
+            full_assignment,
+            qap,
+        }
+    }
+
+    #[tokio::test]
+    async fn prove_with_multiple_public_inputs() {
+        let rng = &mut StdRng::seed_from_u64(42);
+        // keeps a_query[1..] and l_query a multiple of l long,
+        // as pack_from_arkworks_proving_key expects
+        let (circuit, pk, vk) = cube_setup(9, rng);
+
+        let CubeInstance {
+            matrices,
+            full_assignment,
+            num_inputs,
+            qap,
+        } = cube_instance(circuit);
+        assert_eq!(num_inputs, 3);
+        let domain = qap.domain;
+
+        let pp = PackedSharingParams::<Fr>::new(2);
+        let r = Fr::rand(rng);
+        let s = Fr::rand(rng);
+        let r_shares = pp.pack(vec![r; pp.l], rng);
+        let s_shares = pp.pack(vec![s; pp.l], rng);
+        let qap_shares = qap.pss(&pp, rng);
+        let crs_shares =
+            PackedProvingKeyShare::<Bn254>::pack_from_arkworks_proving_key(
+                &pk, pp,
+            );
+        let witness_shares =
+            WitnessShares::pack(&full_assignment, num_inputs, &pp, rng);
+
+        let root_of_unity = {
+            let domain_double =
+                Radix2EvaluationDomain::<Fr>::new(2 * domain.size()).unwrap();
+            domain_double.element(1)
+        };
+        let fft_masks: [Vec<FftMask<Fr>>; 3] = core::array::from_fn(|_| {
+            FftMask::sample_ifft_then_fft(
+                FftOrdering::Natural,
+                root_of_unity,
+                &domain,
+                &pp,
+                rng,
+            )
+        });
+        let degred_masks = DegRedMask::<Fr, Fr>::sample(
+            &pp,
+            Fr::one(),
+            domain.size() / pp.l,
+            rng,
+        );
+        let g1_msm_masks: [Vec<MsmMask<G1>>; 4] =
+            core::array::from_fn(|_| MsmMask::sample(&pp, rng));
+        let g2_msm_masks = MsmMask::<G2>::sample(&pp, rng);
+
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let result = network
+            .simulate_network_round(
+                Arc::new((
+                    pp,
+                    crs_shares,
+                    witness_shares,
+                    qap_shares,
+                    r_shares,
+                    s_shares,
+                    fft_masks,
+                    degred_masks,
+                    g1_msm_masks,
+                    g2_msm_masks,
+                )),
+                |net, data| async move {
+                    let (
+                        pp,
+                        crs_shares,
+                        witness_shares,
+                        qap_shares,
+                        r_shares,
+                        s_shares,
+                        fft_masks,
+                        degred_masks,
+                        g1_msm_masks,
+                        g2_msm_masks,
+                    ) = &*data;
+                    let idx = net.party_id() as usize;
+                    let fft_mask: [FftMask<Fr>; 3] =
+                        core::array::from_fn(|i| fft_masks[i][idx].clone());
+                    let h_share = CircomReduction::d_h(
+                        qap_shares[idx].clone(),
+                        &(fft_mask, degred_masks[idx].clone()),
+                        pp,
+                        &net,
+                    )
+                    .await
+                    .unwrap();
+
+                    let g1_msm_mask: [MsmMask<G1>; 4] =
+                        core::array::from_fn(|i| g1_msm_masks[i][idx].clone());
+                    let (a_share, b_share, c_share) = prove_shares(
+                        &crs_shares[idx],
+                        &witness_shares[idx],
+                        &h_share,
+                        r_shares[idx],
+                        s_shares[idx],
+                        &g1_msm_mask,
+                        &g2_msm_masks[idx],
+                        pp,
+                        &net,
+                    )
+                    .await
+                    .unwrap();
+
+                    king_reconstruct_proof::<Bn254, _>(
+                        a_share,
+                        b_share,
+                        c_share,
+                        pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        // only the king learns the proof
+        assert!(result[1..].iter().all(Option::is_none));
+        let proof = result[0].clone().unwrap();
+
+        let expected = Groth16::<Bn254, CircomReduction>::create_proof_with_reduction_and_matrices(
+            &pk,
+            r,
+            s,
+            &matrices,
+            num_inputs,
+            matrices.num_constraints,
+            &full_assignment,
+        )
+        .unwrap();
+        assert_eq!(expected, proof);
+
+        let public_inputs = &full_assignment[1..num_inputs];
+        assert!(Groth16::<Bn254, CircomReduction>::verify(
+            &vk,
+            public_inputs,
+            &proof
+        )
+        .unwrap());
+        assert!(king_self_verify(&proof, &vk, public_inputs));
+        let mut wrong_inputs = public_inputs.to_vec();
+        wrong_inputs[0] += Fr::one();
+        assert!(!king_self_verify(&proof, &vk, &wrong_inputs));
+    }
+
+    #[tokio::test]
+    async fn prove_shares_streaming_sends_each_part() {
+        let rng = &mut StdRng::seed_from_u64(42);
+        let (circuit, pk, _) = cube_setup(9, rng);
+
+        let CubeInstance {
+            full_assignment,
+            num_inputs,
+            qap,
+            ..
+        } = cube_instance(circuit);
+        let domain = qap.domain;
+
+        let pp = PackedSharingParams::<Fr>::new(2);
+        let crs_shares =
+            PackedProvingKeyShare::<Bn254>::pack_from_arkworks_proving_key(
+                &pk, pp,
+            );
+        let witness_shares =
+            WitnessShares::pack(&full_assignment, num_inputs, &pp, rng);
+        // the parts only need to match the returned shares, any h will do
+        let h_shares = transpose(
+            (0..domain.size() / pp.l)
+                .map(|_| {
+                    pp.pack((0..pp.l).map(|_| Fr::rand(rng)).collect(), rng)
+                })
+                .collect(),
+        );
+        let r_shares = pp.pack(vec![Fr::rand(rng); pp.l], rng);
+        let s_shares = pp.pack(vec![Fr::rand(rng); pp.l], rng);
+        let g1_msm_masks: [Vec<MsmMask<G1>>; 4] =
+            core::array::from_fn(|_| MsmMask::sample(&pp, rng));
+        let g2_msm_masks = MsmMask::<G2>::sample(&pp, rng);
+
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        network
+            .simulate_network_round(
+                Arc::new((
+                    pp,
+                    crs_shares,
+                    witness_shares,
+                    h_shares,
+                    r_shares,
+                    s_shares,
+                    g1_msm_masks,
+                    g2_msm_masks,
+                )),
+                |net, data| async move {
+                    let (
+                        pp,
+                        crs_shares,
+                        witness_shares,
+                        h_shares,
+                        r_shares,
+                        s_shares,
+                        g1_msm_masks,
+                        g2_msm_masks,
+                    ) = &*data;
+                    let idx = net.party_id() as usize;
+                    let g1_msm_mask: [MsmMask<G1>; 4] =
+                        core::array::from_fn(|i| g1_msm_masks[i][idx].clone());
+
+                    // nobody reads the parts until the proof is done
+                    let (parts, mut progress) = mpsc::unbounded_channel();
+                    let (a_share, b_share, c_share) = prove_shares_streaming(
+                        &crs_shares[idx],
+                        &witness_shares[idx],
+                        &h_shares[idx],
+                        r_shares[idx],
+                        s_shares[idx],
+                        &g1_msm_mask,
+                        &g2_msm_masks[idx],
+                        pp,
+                        &net,
+                        &parts,
+                    )
+                    .await
+                    .unwrap();
+                    drop(parts);
+
+                    // the parts arrive in order, each as it is computed
+                    let mut streamed = Vec::new();
+                    while let Some(part) = progress.recv().await {
+                        streamed.push(part);
+                    }
+                    assert_eq!(
+                        streamed,
+                        vec![
+                            ProofPart::A(a_share),
+                            ProofPart::B(b_share),
+                            ProofPart::C(c_share)
+                        ]
+                    );
+                },
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn d_public_input_msm_prepares_inputs() {
+        let rng = &mut StdRng::seed_from_u64(42);
+        let (circuit, pk, vk) = cube_setup(1, rng);
+        let proof = Groth16::<Bn254, CircomReduction>::prove(&pk, circuit, rng)
+            .unwrap();
+        let public_inputs = vec![Fr::from(9u64), Fr::from(27u64)];
+
+        let pp = PackedSharingParams::<Fr>::new(2);
+        let gamma_abc_shares = pack_gamma_abc(&vk, &pp);
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let result = network
+            .simulate_network_round(
+                (gamma_abc_shares, public_inputs.clone(), pp),
+                |net, (gamma_abc_shares, public_inputs, pp)| async move {
+                    d_public_input_msm::<Bn254, _>(
+                        &gamma_abc_shares[net.party_id() as usize],
+                        &public_inputs,
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        assert!(result[1..].iter().all(Option::is_none));
+        let vk_x = result[0].unwrap();
+        let pvk = prepare_verifying_key(&vk);
+        assert_eq!(
+            vk_x,
+            Groth16::<Bn254>::prepare_inputs(&pvk, &public_inputs).unwrap()
+        );
+        assert!(Groth16::<Bn254>::verify_proof_with_prepared_inputs(
+            &pvk, &proof, &vk_x
+        )
+        .unwrap());
+    }
+
+    #[tokio::test]
+    async fn d_public_input_msm_with_a_king_holding_no_share() {
+        let rng = &mut StdRng::seed_from_u64(42);
+        let (_, _, vk) = cube_setup(1, rng);
+        let public_inputs = vec![Fr::from(9u64), Fr::from(27u64)];
+
+        let pp = PackedSharingParams::<Fr>::new(2);
+        let gamma_abc_shares = pack_gamma_abc(&vk, &pp);
+        let network = LocalTestNet::new_local_testnet_with_king(pp.n)
+            .await
+            .unwrap();
+        let result = network
+            .simulate_network_round(
+                (gamma_abc_shares, public_inputs.clone(), pp),
+                |net, (gamma_abc_shares, public_inputs, pp)| async move {
+                    let shares = match net.party_index() {
+                        Some(idx) => &gamma_abc_shares[idx][..],
+                        None => &[],
+                    };
+                    d_public_input_msm::<Bn254, _>(
+                        shares,
+                        &public_inputs,
+                        &pp,
+                        &net,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        // node 0 is the king, the others hold the shares in order
+        assert!(result[1..].iter().all(Option::is_none));
+        let pvk = prepare_verifying_key(&vk);
+        assert_eq!(
+            result[0].unwrap(),
+            Groth16::<Bn254>::prepare_inputs(&pvk, &public_inputs).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn prove_with_fft_and_msm_committees() {
+        let rng = &mut StdRng::seed_from_u64(42);
+        let (circuit, pk, vk) = cube_setup(9, rng);
+
+        let CubeInstance {
+            full_assignment,
+            num_inputs,
+            qap,
+            ..
+        } = cube_instance(circuit);
+
+        let pp = PackedSharingParams::<Fr>::new(2);
+        let r_shares = pp.pack(vec![Fr::rand(rng); pp.l], rng);
+        let s_shares = pp.pack(vec![Fr::rand(rng); pp.l], rng);
+        let h_masks = CircomReduction::sample_masks(&qap.domain, &pp, rng);
+        let reshare_masks =
+            sample_reshare_mask::<Fr, Fr>(qap.domain.size() / pp.l, &pp, rng);
+        let qap_shares = qap.pss(&pp, rng);
+        let crs_shares =
+            PackedProvingKeyShare::<Bn254>::pack_from_arkworks_proving_key(
+                &pk, pp,
+            );
+        let witness_shares =
+            WitnessShares::pack(&full_assignment, num_inputs, &pp, rng);
+        let g1_msm_masks: [Vec<MsmMask<G1>>; 4] =
+            core::array::from_fn(|_| MsmMask::sample(&pp, rng));
+        let g2_msm_masks = MsmMask::<G2>::sample(&pp, rng);
+
+        // parties 0..n compute h, n..2n the MSMs
+        let network = LocalTestNet::new_local_testnet(2 * pp.n).await.unwrap();
+        let result = network
+            .simulate_network_round(
+                Arc::new((
+                    pp,
+                    crs_shares,
+                    witness_shares,
+                    qap_shares,
+                    h_masks,
+                    reshare_masks,
+                    r_shares,
+                    s_shares,
+                    g1_msm_masks,
+                    g2_msm_masks,
+                    vk,
+                    full_assignment[1..num_inputs].to_vec(),
+                )),
+                |net, data| async move {
+                    let (
+                        pp,
+                        crs_shares,
+                        witness_shares,
+                        qap_shares,
+                        h_masks,
+                        reshare_masks,
+                        r_shares,
+                        s_shares,
+                        g1_msm_masks,
+                        g2_msm_masks,
+                        vk,
+                        public_inputs,
+                    ) = &*data;
+                    let (role, offset) = Role::of(net.party_id(), pp);
+                    let idx = (net.party_id() - offset) as usize;
+                    let g1_msm_mask: [MsmMask<G1>; 4] =
+                        core::array::from_fn(|i| g1_msm_masks[i][idx].clone());
+                    let input: RoleInput<Bn254, CircomReduction, _> = match role
+                    {
+                        Role::Fft => RoleInput::Fft {
+                            qap_share: qap_shares[idx].clone(),
+                            h_masks: &h_masks[idx],
+                            reshare_mask: &reshare_masks[idx],
+                        },
+                        Role::Msm => RoleInput::Msm {
+                            crs_share: &crs_shares[idx],
+                            witness: &witness_shares[idx],
+                            r_share: r_shares[idx],
+                            s_share: s_shares[idx],
+                            g1_msm_mask: &g1_msm_mask,
+                            g2_msm_mask: &g2_msm_masks[idx],
+                        },
+                    };
+                    let shares =
+                        prove_with_roles(input, pp, &net).await.unwrap();
+
+                    // the FFT committee is done once it handed h over
+                    let (a_share, b_share, c_share) = shares?;
+                    let committee = SubNet::new(&net, offset, pp.n).unwrap();
+                    king_reconstruct_and_verify_proof::<Bn254, _>(
+                        a_share,
+                        b_share,
+                        c_share,
+                        Some(SelfVerify { vk, public_inputs }),
+                        pp,
+                        &committee,
+                        MultiplexedStreamID::Zero,
+                    )
+                    .await
+                    .unwrap()
+                },
+            )
+            .await;
+
+        // the king of the MSM committee assembles and verifies the proof
+        assert!(result[pp.n].is_some());
+        assert!(result
+            .iter()
+            .enumerate()
+            .all(|(i, p)| i == pp.n || p.is_none()));
+    }
+
+    /// The shares of every party of the inputs of one statement of prove_batch
+    struct StatementShares {
+        qap_shares: Vec<PackedQAPShare<Fr, Radix2EvaluationDomain<Fr>>>,
+        h_masks: Vec<<CircomReduction as DistributedR1CSToQAP>::Masks<Fr>>,
+        witnesses: Vec<WitnessShares<Fr>>,
+        r_shares: Vec<Fr>,
+        s_shares: Vec<Fr>,
+        g1_msm_masks: [Vec<MsmMask<G1>>; 4],
+        g2_msm_masks: Vec<MsmMask<G2>>,
+    }
+
+    #[tokio::test]
+    async fn prove_batch_proves_every_statement() {
+        let rng = &mut StdRng::seed_from_u64(42);
+        let (circuit, pk, vk) = cube_setup(9, rng);
+
+        let pp = PackedSharingParams::<Fr>::new(2);
+        let crs_shares =
+            PackedProvingKeyShare::<Bn254>::pack_from_arkworks_proving_key(
+                &pk, pp,
+            );
+
+        let mut public_inputs = Vec::new();
+        let mut statements = Vec::new();
+        for w in [3u64, 5] {
+            let CubeInstance {
+                full_assignment,
+                num_inputs,
+                qap,
+                ..
+            } = cube_instance(CubeCircuit {
+                w: Fr::from(w),
+                ..circuit.clone()
+            });
+            public_inputs.push(full_assignment[1..num_inputs].to_vec());
+
+            let r = Fr::rand(rng);
+            let s = Fr::rand(rng);
+            let g1_msm_masks: [Vec<MsmMask<G1>>; 4] =
+                core::array::from_fn(|_| MsmMask::sample(&pp, rng));
+            statements.push(StatementShares {
+                qap_shares: qap.pss(&pp, rng),
+                h_masks: CircomReduction::sample_masks(&qap.domain, &pp, rng),
+                witnesses: WitnessShares::pack(
+                    &full_assignment,
+                    num_inputs,
+                    &pp,
+                    rng,
+                ),
+                r_shares: pp.pack(vec![r; pp.l], rng),
+                s_shares: pp.pack(vec![s; pp.l], rng),
+                g1_msm_masks,
+                g2_msm_masks: MsmMask::<G2>::sample(&pp, rng),
+            });
+        }
+
+        let network = LocalTestNet::new_local_testnet(pp.n).await.unwrap();
+        let result = network
+            .simulate_network_round(
+                Arc::new((pp, crs_shares, statements)),
+                |net, data| async move {
+                    let (pp, crs_shares, statements) = &*data;
+                    let idx = net.party_id() as usize;
+                    let g1_msm_masks = statements
+                        .iter()
+                        .map(|statement| {
+                            core::array::from_fn(|i| {
+                                statement.g1_msm_masks[i][idx].clone()
+                            })
+                        })
+                        .collect::<Vec<[MsmMask<G1>; 4]>>();
+                    let batch = statements
+                        .iter()
+                        .zip(&g1_msm_masks)
+                        .map(|(statement, g1_msm_mask)| BatchStatement::<
+                            Bn254,
+                            CircomReduction,
+                            _,
+                        > {
+                            qap_share: statement.qap_shares[idx].clone(),
+                            h_masks: &statement.h_masks[idx],
+                            witness: &statement.witnesses[idx],
+                            r_share: statement.r_shares[idx],
+                            s_share: statement.s_shares[idx],
+                            g1_msm_mask,
+                            g2_msm_mask: &statement.g2_msm_masks[idx],
+                        })
+                        .collect();
+                    prove_batch(batch, &crs_shares[idx], pp, &net)
+                        .await
+                        .unwrap()
+                },
+            )
+            .await;
+
+        let proofs = result[0].as_ref().unwrap();
+        assert_eq!(proofs.len(), 2);
+        for (proof, inputs) in proofs.iter().zip(&public_inputs) {
+            assert!(Groth16::<Bn254, CircomReduction>::verify(
+                &vk, inputs, proof
+            )
+            .unwrap());
+        }
+        assert!(!Groth16::<Bn254, CircomReduction>::verify(
+            &vk,
+            &public_inputs[1],
+            &proofs[0]
+        )
+        .unwrap());
+        assert!(result[1..].iter().all(|proofs| proofs.is_none()));
+    }
+}
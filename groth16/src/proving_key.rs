@@ -2,9 +2,15 @@
 
 use ark_ec::{pairing::Pairing, AffineRepr};
 use ark_ff::{FftField, PrimeField};
-use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read,
+    SerializationError, Valid, Validate, Write,
+};
 use ark_std::{cfg_chunks, cfg_into_iter};
+use memmap2::Mmap;
 use secret_sharing::pss::PackedSharingParams;
+use std::fs::File;
+use std::path::Path;
 
 use ark_ff::UniformRand;
 use rand::Rng;
@@ -12,9 +18,13 @@ use rand::Rng;
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
-#[derive(
-    Clone, Debug, Default, PartialEq, CanonicalSerialize, CanonicalDeserialize,
-)]
+/// Version of the serialized format of PackedProvingKeyShare.
+/// Bump it whenever a field is added, removed or reordered, so that shares
+/// cached by an older version are rejected instead of misread.
+pub const PACKED_PROVING_KEY_SHARE_VERSION: u16 = 1;
+
+/// Serialized with a leading PACKED_PROVING_KEY_SHARE_VERSION
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct PackedProvingKeyShare<E: Pairing> {
     /// s = `a_query[1..]`
     pub s: Vec<E::G1Affine>,
@@ -36,6 +46,93 @@ pub struct PackedProvingKeyShare<E: Pairing> {
     pub beta_g2: E::G2Affine,
 }
 
+// Applies $m to every field, in serialization order
+macro_rules! for_each_field {
+    ($m:ident) => {
+        $m!(
+            s,
+            u,
+            w,
+            h,
+            v,
+            a_query0,
+            b_g1_query0,
+            b_g2_query0,
+            delta_g1,
+            delta_g2,
+            alpha_g1,
+            beta_g1,
+            beta_g2
+        )
+    };
+}
+
+impl<E: Pairing> CanonicalSerialize for PackedProvingKeyShare<E> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        PACKED_PROVING_KEY_SHARE_VERSION
+            .serialize_with_mode(&mut writer, compress)?;
+        macro_rules! serialize {
+            ($($field:ident),*) => {
+                $(self.$field.serialize_with_mode(&mut writer, compress)?;)*
+            };
+        }
+        for_each_field!(serialize);
+        Ok(())
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        macro_rules! size {
+            ($($field:ident),*) => {
+                0 $(+ self.$field.serialized_size(compress))*
+            };
+        }
+        PACKED_PROVING_KEY_SHARE_VERSION.serialized_size(compress)
+            + for_each_field!(size)
+    }
+}
+
+impl<E: Pairing> Valid for PackedProvingKeyShare<E> {
+    fn check(&self) -> Result<(), SerializationError> {
+        macro_rules! check {
+            ($($field:ident),*) => {
+                $(self.$field.check()?;)*
+            };
+        }
+        for_each_field!(check);
+        Ok(())
+    }
+}
+
+impl<E: Pairing> CanonicalDeserialize for PackedProvingKeyShare<E> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let version =
+            u16::deserialize_with_mode(&mut reader, compress, validate)?;
+        if version != PACKED_PROVING_KEY_SHARE_VERSION {
+            return Err(SerializationError::InvalidData);
+        }
+        macro_rules! deserialize {
+            ($($field:ident),*) => {
+                Ok(Self {
+                    $($field: CanonicalDeserialize::deserialize_with_mode(
+                        &mut reader,
+                        compress,
+                        validate,
+                    )?,)*
+                })
+            };
+        }
+        for_each_field!(deserialize)
+    }
+}
+
 impl<E: Pairing> PackedProvingKeyShare<E>
 where
     E::ScalarField: FftField + PrimeField,
@@ -176,6 +273,196 @@ where
     }
 }
 
+/// Location of a serialized vector of points inside a mapped file
+#[derive(Clone, Copy, Debug)]
+struct MappedVec {
+    offset: usize,
+    len: usize,
+    point_size: usize,
+}
+
+impl MappedVec {
+    /// Reads the length of the vector and skips over its points
+    fn read(
+        data: &[u8],
+        reader: &mut &[u8],
+        compress: Compress,
+        point_size: usize,
+    ) -> Result<Self, SerializationError> {
+        let len =
+            u64::deserialize_with_mode(&mut *reader, compress, Validate::No)?
+                as usize;
+        let size = len
+            .checked_mul(point_size)
+            .filter(|size| *size <= reader.len())
+            .ok_or(SerializationError::InvalidData)?;
+        let offset = data.len() - reader.len();
+        *reader = &reader[size..];
+        Ok(Self {
+            offset,
+            len,
+            point_size,
+        })
+    }
+}
+
+/// A serialized PackedProvingKeyShare read from a memory mapped file.
+/// s, u, w, h and v stay on disk and are deserialized chunk by chunk on
+/// demand, e.g to feed d_msm_chunked, the other points are read eagerly.
+pub struct MmapProvingKeyShare<E: Pairing> {
+    mmap: Mmap,
+    compress: Compress,
+    s: MappedVec,
+    u: MappedVec,
+    w: MappedVec,
+    h: MappedVec,
+    v: MappedVec,
+    pub a_query0: E::G1Affine,
+    pub b_g1_query0: E::G1Affine,
+    pub b_g2_query0: E::G2Affine,
+    pub delta_g1: E::G1Affine,
+    pub delta_g2: E::G2Affine,
+    pub alpha_g1: E::G1Affine,
+    pub beta_g1: E::G1Affine,
+    pub beta_g2: E::G2Affine,
+}
+
+impl<E: Pairing> MmapProvingKeyShare<E> {
+    /// Maps a PackedProvingKeyShare serialized with the given compression.
+    /// The file must not be modified while it is mapped.
+    pub fn open(
+        path: impl AsRef<Path>,
+        compress: Compress,
+    ) -> Result<Self, SerializationError> {
+        let file = File::open(path)?;
+        // SAFETY: the share files are written once and only read afterwards
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let data = &mmap[..];
+        let mut reader = data;
+        let version =
+            u16::deserialize_with_mode(&mut reader, compress, Validate::Yes)?;
+        if version != PACKED_PROVING_KEY_SHARE_VERSION {
+            return Err(SerializationError::InvalidData);
+        }
+
+        let g1_size = E::G1Affine::zero().serialized_size(compress);
+        let g2_size = E::G2Affine::zero().serialized_size(compress);
+        let s = MappedVec::read(data, &mut reader, compress, g1_size)?;
+        let u = MappedVec::read(data, &mut reader, compress, g1_size)?;
+        let w = MappedVec::read(data, &mut reader, compress, g1_size)?;
+        let h = MappedVec::read(data, &mut reader, compress, g1_size)?;
+        let v = MappedVec::read(data, &mut reader, compress, g2_size)?;
+
+        macro_rules! read {
+            ($($field:ident),*) => {
+                $(let $field = CanonicalDeserialize::deserialize_with_mode(
+                    &mut reader,
+                    compress,
+                    Validate::Yes,
+                )?;)*
+            };
+        }
+        read!(
+            a_query0,
+            b_g1_query0,
+            b_g2_query0,
+            delta_g1,
+            delta_g2,
+            alpha_g1,
+            beta_g1,
+            beta_g2
+        );
+
+        Ok(Self {
+            mmap,
+            compress,
+            s,
+            u,
+            w,
+            h,
+            v,
+            a_query0,
+            b_g1_query0,
+            b_g2_query0,
+            delta_g1,
+            delta_g2,
+            alpha_g1,
+            beta_g1,
+            beta_g2,
+        })
+    }
+
+    /// Deserializes the points of vec, chunk_size at a time.
+    /// Panics if chunk_size is 0.
+    fn chunks<A: CanonicalDeserialize>(
+        &self,
+        vec: MappedVec,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Result<Vec<A>, SerializationError>> + '_ {
+        let end = vec.offset + vec.len * vec.point_size;
+        self.mmap[vec.offset..end]
+            .chunks(chunk_size * vec.point_size)
+            .map(move |chunk| {
+                chunk
+                    .chunks(vec.point_size)
+                    .map(|point| {
+                        A::deserialize_with_mode(
+                            point,
+                            self.compress,
+                            Validate::Yes,
+                        )
+                    })
+                    .collect()
+            })
+    }
+
+    /// s = `a_query[1..]`, chunk_size points at a time
+    pub fn s_chunks(
+        &self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Result<Vec<E::G1Affine>, SerializationError>> + '_
+    {
+        self.chunks(self.s, chunk_size)
+    }
+
+    /// u = `h_query`, chunk_size points at a time
+    pub fn u_chunks(
+        &self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Result<Vec<E::G1Affine>, SerializationError>> + '_
+    {
+        self.chunks(self.u, chunk_size)
+    }
+
+    /// w = `l_query`, chunk_size points at a time
+    pub fn w_chunks(
+        &self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Result<Vec<E::G1Affine>, SerializationError>> + '_
+    {
+        self.chunks(self.w, chunk_size)
+    }
+
+    /// h = `b_g1_query[1..]`, chunk_size points at a time
+    pub fn h_chunks(
+        &self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Result<Vec<E::G1Affine>, SerializationError>> + '_
+    {
+        self.chunks(self.h, chunk_size)
+    }
+
+    /// v = `b_g2_query[1..]`, chunk_size points at a time
+    pub fn v_chunks(
+        &self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Result<Vec<E::G2Affine>, SerializationError>> + '_
+    {
+        self.chunks(self.v, chunk_size)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -187,6 +474,58 @@ mod tests {
 
     const L: usize = 2;
 
+    #[test]
+    fn serialization_round_trip() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::new(L);
+        let share = PackedProvingKeyShare::<Bn254>::rand(rng, 8, &pp);
+
+        let mut bytes = Vec::new();
+        share.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), share.compressed_size());
+        assert_eq!(
+            PackedProvingKeyShare::<Bn254>::deserialize_compressed(&bytes[..])
+                .unwrap(),
+            share
+        );
+
+        // shares written by another version of the format are rejected
+        bytes[0] ^= 1;
+        assert!(matches!(
+            PackedProvingKeyShare::<Bn254>::deserialize_compressed(&bytes[..]),
+            Err(SerializationError::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn mmap_share_matches_share() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::new(L);
+        let share = PackedProvingKeyShare::<Bn254>::rand(rng, 16, &pp);
+
+        let path = std::env::temp_dir()
+            .join(format!("pk_share_{}.bin", std::process::id()));
+        let mut bytes = Vec::new();
+        share.serialize_compressed(&mut bytes).unwrap();
+        std::fs::write(&path, bytes).unwrap();
+
+        let mapped =
+            MmapProvingKeyShare::<Bn254>::open(&path, Compress::Yes).unwrap();
+        macro_rules! collect {
+            ($chunks:expr) => {
+                $chunks.flat_map(Result::unwrap).collect::<Vec<_>>()
+            };
+        }
+        assert_eq!(collect!(mapped.s_chunks(3)), share.s);
+        assert_eq!(collect!(mapped.u_chunks(3)), share.u);
+        assert_eq!(collect!(mapped.w_chunks(3)), share.w);
+        assert_eq!(collect!(mapped.h_chunks(3)), share.h);
+        assert_eq!(collect!(mapped.v_chunks(3)), share.v);
+        assert_eq!(mapped.beta_g2, share.beta_g2);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[test]
     fn packed_pk_from_arkworks_pk() {
         let _ = env_logger::builder()
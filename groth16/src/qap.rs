@@ -2,8 +2,8 @@ use ark_ff::PrimeField;
 use ark_groth16::r1cs_to_qap::evaluate_constraint;
 use ark_poly::EvaluationDomain;
 use ark_relations::r1cs::{ConstraintMatrices, SynthesisError};
-use ark_std::{cfg_into_iter, cfg_iter, cfg_iter_mut, vec};
-use dist_primitives::dfft::fft_in_place_rearrange;
+use ark_std::{cfg_into_iter, cfg_iter, cfg_iter_mut, rand::Rng, vec};
+use dist_primitives::dfft::{pack_fft_input_rows, FftOrdering};
 use secret_sharing::pss::PackedSharingParams;
 
 #[cfg(feature = "parallel")]
@@ -25,7 +25,12 @@ pub struct QAP<F: PrimeField, D: EvaluationDomain<F>> {
     pub domain: D,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "zeroize",
+    derive(zeroize::Zeroize, zeroize::ZeroizeOnDrop),
+    zeroize(bound = "")
+)]
 pub struct PackedQAPShare<F: PrimeField, D: EvaluationDomain<F>> {
     pub num_inputs: usize,
     pub num_constraints: usize,
@@ -36,9 +41,34 @@ pub struct PackedQAPShare<F: PrimeField, D: EvaluationDomain<F>> {
     /// C is also called W in the paper.
     pub c: Vec<F>,
     /// Evaluation domain of the QAP.
+    #[cfg_attr(feature = "zeroize", zeroize(skip))]
     pub domain: D,
 }
 
+impl<F: PrimeField, D: EvaluationDomain<F>> PackedQAPShare<F, D> {
+    /// Returns (field, first differing index) for each of a, b and c that
+    /// differs from other. A length mismatch differs at the shorter length.
+    pub fn debug_diff(&self, other: &Self) -> Vec<(&'static str, usize)> {
+        [
+            ("a", &self.a, &other.a),
+            ("b", &self.b, &other.b),
+            ("c", &self.c, &other.c),
+        ]
+        .into_iter()
+        .filter_map(|(name, x, y)| {
+            x.iter()
+                .zip(y)
+                .position(|(x, y)| x != y)
+                .or_else(|| (x.len() != y.len()).then(|| x.len().min(y.len())))
+                .map(|idx| (name, idx))
+        })
+        .collect()
+    }
+}
+
+/// Evaluates a, b and c of the QAP on the domain. These are the same for
+/// every R1CS to QAP reduction, the reduction only changes how h is computed
+/// from them, i.e `DistributedR1CSToQAP::d_h` on the packed shares.
 pub fn qap<F: PrimeField, D: EvaluationDomain<F>>(
     matrices: &ConstraintMatrices<F>,
     full_assignment: &[F],
@@ -88,28 +118,35 @@ pub fn qap<F: PrimeField, D: EvaluationDomain<F>>(
     })
 }
 
+impl<F: PrimeField, D: EvaluationDomain<F> + Send> PackedQAPShare<F, D> {
+    /// Evaluates the QAP of the circuit and packs it, i.e qap then QAP::pss,
+    /// so the domain the shares assume is the one the QAP was evaluated on.
+    /// The shares suit every reduction, see qap.
+    /// Returns the shares of the n parties, indexed by party id.
+    /// The evaluation runs in parallel with the parallel feature, like qap.
+    pub fn from_circuit(
+        matrices: &ConstraintMatrices<F>,
+        full_assignment: &[F],
+        pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
+    ) -> Result<Vec<Self>, SynthesisError> {
+        Ok(qap::<F, D>(matrices, full_assignment)?.pss(pp, rng))
+    }
+}
+
 impl<F: PrimeField, D: EvaluationDomain<F> + Send> QAP<F, D> {
     pub fn pss(
         &self,
         pp: &PackedSharingParams<F>,
+        rng: &mut impl Rng,
     ) -> Vec<PackedQAPShare<F, D>> {
         let num_inputs = self.num_inputs;
         let num_constraints = self.num_constraints;
         let domain = self.domain;
-        let rng = &mut ark_std::test_rng();
-        let mut pack = |mut x: Vec<F>| {
-            fft_in_place_rearrange(&mut x);
-            let mut pevals: Vec<Vec<F>> = Vec::new();
-            let m = x.len();
-            for i in 0..m / pp.l {
-                let secrets = cfg_iter!(x)
-                    .skip(i)
-                    .step_by(m / pp.l)
-                    .cloned()
-                    .collect::<Vec<_>>();
-                pevals.push(pp.pack(secrets, rng));
-            }
-            pevals
+        // the evaluations are in natural order, d_ifft takes them bit-reversed
+        let mut pack = |x: Vec<F>| {
+            pack_fft_input_rows(x, FftOrdering::Natural, pp, rng)
+                .collect::<Vec<_>>()
         };
 
         let packed_a = pack(self.a.clone());
@@ -142,7 +179,9 @@ mod tests {
     use ark_crypto_primitives::snark::SNARK;
     use ark_groth16::Groth16;
     use ark_poly::Radix2EvaluationDomain;
+    use ark_relations::lc;
     use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
 
     #[test]
     fn creates_qap() {
@@ -167,6 +206,104 @@ mod tests {
         eprintln!("{:?}", qap);
     }
 
+    #[test]
+    fn from_circuit_packs_the_qap_bit_reversed() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let x = Fr::from(3u64);
+        let x_var = cs.new_input_variable(|| Ok(x)).unwrap();
+        let y_var = cs.new_witness_variable(|| Ok(x * x)).unwrap();
+        cs.enforce_constraint(lc!() + x_var, lc!() + x_var, lc!() + y_var)
+            .unwrap();
+        cs.finalize();
+        let matrices = cs.to_matrices().unwrap();
+        let full_assignment = [Fr::from(1u64), x, x * x];
+        let pp = PackedSharingParams::new(2);
+
+        let shares =
+            PackedQAPShare::<Fr, Radix2EvaluationDomain<_>>::from_circuit(
+                &matrices,
+                &full_assignment,
+                &pp,
+                &mut StdRng::seed_from_u64(1),
+            )
+            .unwrap();
+        let qap =
+            qap::<Fr, Radix2EvaluationDomain<_>>(&matrices, &full_assignment)
+                .unwrap();
+        assert_eq!(shares.len(), pp.n);
+
+        // d_ifft takes the evaluations bit-reversed, the i-th share of a
+        // party packs the values at i, i + m/l, i + 2m/l, ...
+        let m = qap.domain.size();
+        let stride = m / pp.l;
+        let bits = m.trailing_zeros();
+        let bit_reversed = |evals: &[Fr]| {
+            (0..m)
+                .map(|j| evals[j.reverse_bits() >> (usize::BITS - bits)])
+                .collect::<Vec<_>>()
+        };
+        let open = |share: fn(&PackedQAPShare<Fr, _>) -> &Vec<Fr>| {
+            let mut values = vec![Fr::from(0u64); m];
+            for i in 0..stride {
+                let secrets =
+                    pp.unpack(shares.iter().map(|s| share(s)[i]).collect());
+                for (k, secret) in secrets.into_iter().enumerate() {
+                    values[i + k * stride] = secret;
+                }
+            }
+            values
+        };
+        assert_eq!(open(|s| &s.a), bit_reversed(&qap.a));
+        assert_eq!(open(|s| &s.b), bit_reversed(&qap.b));
+        assert_eq!(open(|s| &s.c), bit_reversed(&qap.c));
+        assert!(shares.iter().all(|s| s.domain == qap.domain));
+    }
+
+    #[test]
+    fn debug_diff_finds_first_difference() {
+        let share = PackedQAPShare {
+            num_inputs: 1,
+            num_constraints: 4,
+            a: vec![Fr::from(1u64); 4],
+            b: vec![Fr::from(2u64); 4],
+            c: vec![Fr::from(3u64); 4],
+            domain: Radix2EvaluationDomain::<Fr>::new(4).unwrap(),
+        };
+        let mut other = share.clone();
+        assert!(share.debug_diff(&other).is_empty());
+
+        other.b[2] = Fr::from(0u64);
+        other.c.pop();
+        assert_eq!(share.debug_diff(&other), vec![("b", 2), ("c", 3)]);
+        assert_ne!(share, other);
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize_wipes_share_buffers() {
+        use ark_std::{One, Zero};
+        use zeroize::{Zeroize, ZeroizeOnDrop};
+
+        fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<PackedQAPShare<Fr, Radix2EvaluationDomain<Fr>>>(
+        );
+
+        let mut share = PackedQAPShare {
+            num_inputs: 1,
+            num_constraints: 4,
+            a: vec![Fr::one(); 4],
+            b: vec![Fr::one(); 4],
+            c: vec![Fr::one(); 4],
+            domain: Radix2EvaluationDomain::<Fr>::new(4).unwrap(),
+        };
+        let ptr = share.b.as_ptr();
+
+        // the buffer is still allocated, only its contents are wiped
+        share.zeroize();
+        assert!(share.a.is_empty() && share.b.is_empty() && share.c.is_empty());
+        assert!((0..4).all(|i| unsafe { (*ptr.add(i)).is_zero() }));
+    }
+
     #[test]
     fn setup() {
         let cfg = CircomConfig::<Bn254>::new(
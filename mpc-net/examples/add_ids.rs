@@ -1,5 +1,6 @@
 // An example ProdNet that performs the simple task of adding up all transmitted IDs
-use mpc_net::prod::{ProdNet, RustlsCertificate};
+use mpc_net::multi::MuxConfig;
+use mpc_net::prod::{NetworkConfig, ProdNet, RustlsCertificate};
 use mpc_net::ser_net::MpcSerNet;
 use mpc_net::{MpcNet, MultiplexedStreamID};
 use rustls::{Certificate, PrivateKey, RootCertStore};
@@ -7,14 +8,12 @@ use std::error::Error;
 use std::path::PathBuf;
 use std::time::Duration;
 use structopt::StructOpt;
-use tokio::net::TcpStream;
-use tokio_rustls::TlsStream;
 use tokio_util::bytes::Bytes;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "Add IDs Example using ProdNet")]
 struct Opt {
-    /// This node's ID
+    /// This node's ID, the king is 0
     #[structopt(short, long)]
     id: u32,
 
@@ -26,17 +25,17 @@ struct Opt {
     #[structopt(parse(from_os_str))]
     private_key: PathBuf,
 
-    /// The number of parties in the network, including the king
-    #[structopt(short, long)]
-    n_parties: usize,
+    /// The address of every party, one per line. The king binds to the first one
+    #[structopt(parse(from_os_str))]
+    peers: PathBuf,
 
-    /// Bind address for the king (required for the king)
-    #[structopt(short, long)]
-    bind_addr: Option<String>,
+    /// Packing factor, the network has 2(t + l) parties
+    #[structopt(short, long, default_value = "2")]
+    l: usize,
 
-    /// The address of the king (required for the clients)
-    #[structopt(short, long)]
-    king_addr: Option<String>,
+    /// Threshold, the network has 2(t + l) parties
+    #[structopt(short, long, default_value = "2")]
+    t: usize,
 
     /// The king's certificate (required for the clients)
     #[structopt(parse(from_os_str), short, long)]
@@ -50,14 +49,36 @@ struct Opt {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let opts: Opt = Opt::from_args();
-    let n_parties = opts.n_parties;
     let my_id = opts.id;
 
-    let net = if opts.id == 0 {
-        load_king(opts).await?
+    let peers = std::fs::read_to_string(&opts.peers)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse())
+        .collect::<Result<Vec<_>, _>>()?;
+    let config = NetworkConfig {
+        id: opts.id,
+        king_id: 0,
+        l: opts.l,
+        t: opts.t,
+        peers,
+        mux: MuxConfig::default(),
+    };
+    let n_parties = config.peers.len();
+
+    let identity = RustlsCertificate {
+        cert: get_certs(&opts.certificate)?[0].clone(),
+        private_key: load_private_key(&opts.private_key)?,
+    };
+    let certs = if opts.id == 0 {
+        load_client_certs(opts)?
     } else {
-        load_client(opts).await?
+        load_king_cert(opts)?
     };
+    let net = ProdNet::from_config(&config, identity, certs)
+        .await
+        .map_err(|err| format!("Error creating the network: {err:?}"))?;
 
     println!("Loaded net for id {my_id}");
 
@@ -106,29 +127,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-async fn load_king(
-    opts: Opt,
-) -> Result<ProdNet<TlsStream<TcpStream>>, Box<dyn Error>> {
-    if opts.client_cert_dir.is_none() {
-        panic!("Must supply the client cert dir")
-    }
-
-    if opts.bind_addr.is_none() {
-        panic!("Must supply the bind address for the king")
-    }
-
+/// The certificates of the clients, for the king
+fn load_client_certs(opts: Opt) -> Result<RootCertStore, Box<dyn Error>> {
     if opts.king_cert.is_some() {
         panic!("King should not have a king cert set - this is for clients")
     }
 
-    let files_in_client_cert_dir =
-        std::fs::read_dir(opts.client_cert_dir.unwrap())?;
+    let Some(client_cert_dir) = opts.client_cert_dir else {
+        panic!("Must supply the client cert dir")
+    };
     let mut client_certs = RootCertStore::empty();
-
-    let private_key_king = load_private_key(&opts.private_key)?;
-    let king_cert = get_certs(&opts.certificate)?[0].clone();
-
-    for file in files_in_client_cert_dir {
+    for file in std::fs::read_dir(client_cert_dir)? {
         let file = file.unwrap();
         let path = file.path();
         let fname = file.file_name();
@@ -139,51 +148,18 @@ async fn load_king(
     }
 
     println!("King loaded {} certs", client_certs.roots.len());
-
-    let identity = RustlsCertificate {
-        cert: king_cert,
-        private_key: private_key_king,
-    };
-
-    ProdNet::new_king_tls(opts.bind_addr.unwrap(), identity, client_certs)
-        .await
-        .map_err(|err| format!("Error creating king: {err:?}").into())
+    Ok(client_certs)
 }
 
-async fn load_client(
-    opts: Opt,
-) -> Result<ProdNet<TlsStream<TcpStream>>, Box<dyn Error>> {
-    if opts.king_addr.is_none() {
-        panic!("Must supply the king address for the clients")
-    }
-
-    if opts.king_cert.is_none() {
+/// The certificate of the king, for the clients
+fn load_king_cert(opts: Opt) -> Result<RootCertStore, Box<dyn Error>> {
+    let Some(king_cert) = opts.king_cert else {
         panic!("Must supply the king cert for the clients")
-    }
-
-    let king_addr = opts.king_addr.unwrap();
-    let client_identity = get_certs(&opts.certificate)?[0].clone();
-    let private_key_client = load_private_key(&opts.private_key)?;
-
-    // Add the king cert
-    let mut king_store = RootCertStore::empty();
-    let king_cert = get_certs(&opts.king_cert.unwrap())?[0].clone();
-    king_store.add(&king_cert)?;
-
-    let identity = RustlsCertificate {
-        cert: client_identity,
-        private_key: private_key_client,
     };
 
-    ProdNet::new_peer_tls(
-        opts.id,
-        king_addr,
-        identity,
-        king_store,
-        opts.n_parties,
-    )
-    .await
-    .map_err(|err| format!("Error creating client: {err:?}").into())
+    let mut king_store = RootCertStore::empty();
+    load_cert(&king_cert, &mut king_store)?;
+    Ok(king_store)
 }
 
 /// Loads a certificate into a cert store
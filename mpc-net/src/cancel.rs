@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio_util::bytes::Bytes;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    ClientSendOrKingReceiveResult, MpcNet, MpcNetError, MultiplexedStreamID,
+};
+
+/// Wraps a network so that all in flight and future communication fails
+/// with `MpcNetError::Generic("cancelled")` once `token` is cancelled.
+/// Lets a supervisor abort a job, e.g on a deadline, without waiting for
+/// the protocol to run to completion.
+pub struct CancellableNet<N: MpcNet> {
+    inner: N,
+    token: CancellationToken,
+}
+
+impl<N: MpcNet> CancellableNet<N> {
+    pub fn new(inner: N, token: CancellationToken) -> Self {
+        Self { inner, token }
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+
+    async fn run<T>(
+        &self,
+        fut: impl std::future::Future<Output = Result<T, MpcNetError>> + Send,
+    ) -> Result<T, MpcNetError> {
+        tokio::select! {
+            biased;
+            _ = self.token.cancelled() => Err(cancelled()),
+            res = fut => res,
+        }
+    }
+}
+
+fn cancelled() -> MpcNetError {
+    MpcNetError::Generic("cancelled".to_string())
+}
+
+#[async_trait]
+impl<N: MpcNet> MpcNet for CancellableNet<N> {
+    fn n_parties(&self) -> usize {
+        self.inner.n_parties()
+    }
+
+    fn party_id(&self) -> u32 {
+        self.inner.party_id()
+    }
+
+    fn king_id(&self) -> u32 {
+        self.inner.king_id()
+    }
+
+    fn party_ids(&self) -> Box<dyn Iterator<Item = u32> + Send + '_> {
+        self.inner.party_ids()
+    }
+
+    fn is_init(&self) -> bool {
+        self.inner.is_init()
+    }
+
+    fn round_timeout(&self) -> Duration {
+        self.inner.round_timeout()
+    }
+
+    fn record_round_time(&self, elapsed: Duration) {
+        self.inner.record_round_time(elapsed)
+    }
+
+    async fn recv_from(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MpcNetError> {
+        self.run(self.inner.recv_from(id, sid)).await
+    }
+
+    async fn send_to(
+        &self,
+        id: u32,
+        bytes: Bytes,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MpcNetError> {
+        self.run(self.inner.send_to(id, bytes, sid)).await
+    }
+
+    // The king otherwise waits out the timeout and reports missing shares
+    async fn client_send_or_king_receive(
+        &self,
+        bytes: &[u8],
+        sid: MultiplexedStreamID,
+        timeout: Duration,
+    ) -> Result<Option<ClientSendOrKingReceiveResult>, MpcNetError> {
+        self.run(self.inner.client_send_or_king_receive(bytes, sid, timeout))
+            .await
+    }
+
+    async fn client_send_or_king_receive_multi_raw(
+        &self,
+        payloads: &[(MultiplexedStreamID, &[u8])],
+        timeout: Duration,
+    ) -> Result<Option<Vec<ClientSendOrKingReceiveResult>>, MpcNetError> {
+        self.run(
+            self.inner
+                .client_send_or_king_receive_multi_raw(payloads, timeout),
+        )
+        .await
+    }
+
+    async fn client_receive_or_king_send(
+        &self,
+        bytes_out: Option<Vec<Bytes>>,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MpcNetError> {
+        self.run(self.inner.client_receive_or_king_send(bytes_out, sid))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::cancel::CancellableNet;
+    use crate::ser_net::MpcSerNet;
+    use crate::{LocalTestNet, MpcNet, MpcNetError, MultiplexedStreamID};
+
+    #[tokio::test]
+    async fn cancelled_king_round_returns_promptly() {
+        const N_PARTIES: usize = 4;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let results = testnet
+            .simulate_network_round((), |conn, _| async move {
+                let token = CancellationToken::new();
+                let net = CancellableNet::new(conn, token.clone());
+                if !net.is_king() {
+                    // the clients stay connected but never send their share
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    return None;
+                }
+
+                let start = Instant::now();
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    token.cancel();
+                });
+                let res = net
+                    .client_send_or_king_receive_serialized(
+                        &0u32,
+                        MultiplexedStreamID::Zero,
+                        0,
+                    )
+                    .await;
+                Some((res.err(), start.elapsed()))
+            })
+            .await;
+
+        let (err, elapsed) = results[0].clone().unwrap();
+        assert!(
+            matches!(err, Some(MpcNetError::Generic(e)) if e == "cancelled")
+        );
+        assert!(elapsed < Duration::from_secs(5));
+    }
+}
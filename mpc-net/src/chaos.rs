@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::{Rng, SeedableRng};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio_util::bytes::Bytes;
+
+use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+
+/// What ChaosNet does to a message it lets through badly
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The message is never delivered, sending it still succeeds
+    Drop,
+    /// The message is delivered after the given time
+    Delay(Duration),
+    /// The message is delivered twice
+    Duplicate,
+    /// A random bit of the message is flipped
+    Corrupt,
+}
+
+struct FaultRule {
+    party: u32,
+    sid: MultiplexedStreamID,
+    fault: Fault,
+    probability: f64,
+}
+
+/// Wraps a network and injects faults into the messages this party sends,
+/// e.g to check that a protocol recovers from or cleanly rejects a lying or
+/// flaky party. The faults are drawn from a seeded rng, so a failing run
+/// can be replayed. Receiving is left untouched.
+pub struct ChaosNet<N: MpcNet> {
+    inner: N,
+    rules: Vec<FaultRule>,
+    rng: Mutex<StdRng>,
+}
+
+impl<N: MpcNet> ChaosNet<N> {
+    /// Lets every message through until faults are added with with_fault
+    pub fn new(inner: N, seed: u64) -> Self {
+        Self {
+            inner,
+            rules: Vec::new(),
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    /// Applies `fault` with the given probability to the messages sent to
+    /// `party` on `sid`. The rules are tried in the order they were added,
+    /// a message gets the fault of the first one that fires.
+    pub fn with_fault(
+        mut self,
+        party: u32,
+        sid: MultiplexedStreamID,
+        fault: Fault,
+        probability: f64,
+    ) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&probability),
+            "probability must be in [0, 1]"
+        );
+        self.rules.push(FaultRule {
+            party,
+            sid,
+            fault,
+            probability,
+        });
+        self
+    }
+
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+
+    fn draw_fault(
+        &self,
+        party: u32,
+        sid: MultiplexedStreamID,
+    ) -> Option<Fault> {
+        let mut rng = self.rng.lock();
+        self.rules
+            .iter()
+            .filter(|rule| rule.party == party && rule.sid == sid)
+            .find(|rule| rng.gen_bool(rule.probability))
+            .map(|rule| rule.fault)
+    }
+
+    fn corrupt(&self, bytes: Bytes) -> Bytes {
+        if bytes.is_empty() {
+            return bytes;
+        }
+        let mut bytes = bytes.to_vec();
+        let bit = self.rng.lock().gen_range(0..8 * bytes.len());
+        bytes[bit / 8] ^= 1 << (bit % 8);
+        bytes.into()
+    }
+}
+
+#[async_trait]
+impl<N: MpcNet> MpcNet for ChaosNet<N> {
+    fn n_parties(&self) -> usize {
+        self.inner.n_parties()
+    }
+
+    fn party_id(&self) -> u32 {
+        self.inner.party_id()
+    }
+
+    fn king_id(&self) -> u32 {
+        self.inner.king_id()
+    }
+
+    fn party_ids(&self) -> Box<dyn Iterator<Item = u32> + Send + '_> {
+        self.inner.party_ids()
+    }
+
+    fn is_init(&self) -> bool {
+        self.inner.is_init()
+    }
+
+    fn round_timeout(&self) -> Duration {
+        self.inner.round_timeout()
+    }
+
+    fn record_round_time(&self, elapsed: Duration) {
+        self.inner.record_round_time(elapsed)
+    }
+
+    async fn recv_from(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MpcNetError> {
+        self.inner.recv_from(id, sid).await
+    }
+
+    async fn send_to(
+        &self,
+        id: u32,
+        bytes: Bytes,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MpcNetError> {
+        match self.draw_fault(id, sid) {
+            None => self.inner.send_to(id, bytes, sid).await,
+            Some(Fault::Drop) => Ok(()),
+            Some(Fault::Delay(delay)) => {
+                tokio::time::sleep(delay).await;
+                self.inner.send_to(id, bytes, sid).await
+            }
+            Some(Fault::Duplicate) => {
+                self.inner.send_to(id, bytes.clone(), sid).await?;
+                self.inner.send_to(id, bytes, sid).await
+            }
+            Some(Fault::Corrupt) => {
+                let bytes = self.corrupt(bytes);
+                self.inner.send_to(id, bytes, sid).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tokio_util::bytes::Bytes;
+
+    use crate::chaos::{ChaosNet, Fault};
+    use crate::inproc::InProcTestNet;
+    use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+
+    #[tokio::test]
+    async fn faults_hit_their_party_and_stream_id() {
+        let mut nodes = InProcTestNet::new(3).into_nodes();
+        let other = nodes.pop().unwrap();
+        let receiver = nodes.pop().unwrap();
+        let sender = ChaosNet::new(nodes.pop().unwrap(), 42)
+            .with_fault(1, MultiplexedStreamID::Zero, Fault::Drop, 1.0)
+            .with_fault(1, MultiplexedStreamID::One, Fault::Duplicate, 1.0)
+            .with_fault(1, MultiplexedStreamID::Two, Fault::Corrupt, 1.0)
+            .with_fault(2, MultiplexedStreamID::Zero, Fault::Drop, 0.0)
+            .with_fault(
+                2,
+                MultiplexedStreamID::One,
+                Fault::Delay(Duration::from_millis(50)),
+                1.0,
+            );
+        let msg = Bytes::from_static(b"hello");
+
+        for sid in [
+            MultiplexedStreamID::Zero,
+            MultiplexedStreamID::One,
+            MultiplexedStreamID::Two,
+        ] {
+            sender.send_to(1, msg.clone(), sid).await.unwrap();
+        }
+        let recv = |sid| receiver.recv_from(0, sid);
+        assert_eq!(recv(MultiplexedStreamID::One).await.unwrap(), msg);
+        assert_eq!(recv(MultiplexedStreamID::One).await.unwrap(), msg);
+        let corrupted = recv(MultiplexedStreamID::Two).await.unwrap();
+        assert_eq!(corrupted.len(), msg.len());
+        let flipped: u32 = corrupted
+            .iter()
+            .zip(&msg)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        assert_eq!(flipped, 1);
+
+        let sid = MultiplexedStreamID::Zero;
+        sender.send_to(2, msg.clone(), sid).await.unwrap();
+        assert_eq!(other.recv_from(0, sid).await.unwrap(), msg);
+        let start = Instant::now();
+        let sid = MultiplexedStreamID::One;
+        sender.send_to(2, msg.clone(), sid).await.unwrap();
+        assert_eq!(other.recv_from(0, sid).await.unwrap(), msg);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // nothing was delivered on the dropping channel
+        drop(sender);
+        assert!(matches!(
+            recv(MultiplexedStreamID::Zero).await,
+            Err(MpcNetError::NotConnected)
+        ));
+    }
+
+    #[tokio::test]
+    async fn faults_are_seeded() {
+        let delivered =
+            |seed| async move {
+                let mut nodes = InProcTestNet::new(2).into_nodes();
+                let receiver = nodes.pop().unwrap();
+                let sender = ChaosNet::new(nodes.pop().unwrap(), seed)
+                    .with_fault(1, MultiplexedStreamID::Zero, Fault::Drop, 0.5);
+                for i in 0..64u8 {
+                    let bytes = Bytes::copy_from_slice(&[i]);
+                    sender
+                        .send_to(1, bytes, MultiplexedStreamID::Zero)
+                        .await
+                        .unwrap();
+                }
+                drop(sender);
+                let mut delivered = Vec::new();
+                while let Ok(bytes) =
+                    receiver.recv_from(0, MultiplexedStreamID::Zero).await
+                {
+                    delivered.push(bytes[0]);
+                }
+                delivered
+            };
+
+        let first = delivered(7).await;
+        assert!(!first.is_empty() && first.len() < 64);
+        assert_eq!(delivered(7).await, first);
+        assert_ne!(delivered(8).await, first);
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use async_trait::async_trait;
+use futures::stream::FuturesOrdered;
+use futures::StreamExt;
+use tokio::sync::mpsc::{
+    unbounded_channel, UnboundedReceiver, UnboundedSender,
+};
+use tokio::sync::Mutex;
+use tokio_util::bytes::Bytes;
+
+use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+
+/// A party of a network living in a single process.
+/// Messages go through in-memory channels, one per peer and per stream id,
+/// so sending only moves the Bytes handle around, nothing is copied or framed.
+/// Meant for fuzzing and property tests, where setting up the sockets of
+/// LocalTestNet for every round would dominate.
+pub struct InProcNet {
+    id: u32,
+    n_parties: usize,
+    king_id: u32,
+    senders: HashMap<u32, Vec<UnboundedSender<Bytes>>>,
+    receivers: HashMap<u32, Vec<Mutex<UnboundedReceiver<Bytes>>>>,
+}
+
+impl InProcNet {
+    fn peer_not_found(id: u32) -> MpcNetError {
+        MpcNetError::Generic(format!("Peer {} not found", id))
+    }
+}
+
+#[async_trait]
+impl MpcNet for InProcNet {
+    fn n_parties(&self) -> usize {
+        self.n_parties
+    }
+
+    fn party_id(&self) -> u32 {
+        self.id
+    }
+
+    fn king_id(&self) -> u32 {
+        self.king_id
+    }
+
+    fn is_init(&self) -> bool {
+        true
+    }
+
+    async fn recv_from(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MpcNetError> {
+        let receivers = self
+            .receivers
+            .get(&id)
+            .ok_or_else(|| Self::peer_not_found(id))?;
+        receivers[sid as usize]
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or(MpcNetError::NotConnected)
+    }
+
+    async fn send_to(
+        &self,
+        id: u32,
+        bytes: Bytes,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MpcNetError> {
+        let senders = self
+            .senders
+            .get(&id)
+            .ok_or_else(|| Self::peer_not_found(id))?;
+        senders[sid as usize]
+            .send(bytes)
+            .map_err(|_| MpcNetError::NotConnected)
+    }
+}
+
+/// In-process counterpart of LocalTestNet, see InProcNet
+pub struct InProcTestNet {
+    /// Ordered by party id, so the rounds return the outputs in that order
+    nodes: Vec<InProcNet>,
+}
+
+impl InProcTestNet {
+    pub fn new(n_parties: usize) -> Self {
+        let mut nodes = (0..n_parties as u32)
+            .map(|id| InProcNet {
+                id,
+                n_parties,
+                king_id: 0,
+                senders: HashMap::new(),
+                receivers: HashMap::new(),
+            })
+            .collect::<Vec<_>>();
+
+        for from in 0..n_parties {
+            for to in (0..n_parties).filter(|to| *to != from) {
+                let (senders, receivers): (Vec<_>, Vec<_>) = (0
+                    ..MultiplexedStreamID::channel_count())
+                    .map(|_| {
+                        let (tx, rx) = unbounded_channel();
+                        (tx, Mutex::new(rx))
+                    })
+                    .unzip();
+                nodes[from].senders.insert(to as u32, senders);
+                nodes[to].receivers.insert(from as u32, receivers);
+            }
+        }
+
+        Self { nodes }
+    }
+
+    /// Sets the king of every node
+    pub fn set_king_id(&mut self, king_id: u32) {
+        for node in self.nodes.iter_mut() {
+            node.king_id = king_id;
+        }
+    }
+
+    pub fn into_nodes(self) -> Vec<InProcNet> {
+        self.nodes
+    }
+
+    /// Same as LocalTestNet::simulate_network_round
+    pub async fn simulate_network_round<
+        F: Future<Output = K> + Send,
+        K: Send + Sync + 'static,
+        U: Clone + Send + Sync + 'static,
+    >(
+        self,
+        user_data: U,
+        f: impl Fn(InProcNet, U) -> F + Send + Sync + Clone + 'static,
+    ) -> Vec<K> {
+        let mut futures = FuturesOrdered::new();
+        for node in self.nodes {
+            let next_f = f.clone();
+            let next_user_data = user_data.clone();
+            futures.push_back(Box::pin(async move {
+                let task = async move { next_f(node, next_user_data).await };
+                tokio::task::spawn(task).await.unwrap()
+            }));
+        }
+        futures.collect().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inproc::InProcTestNet;
+    use crate::ser_net::MpcSerNet;
+    use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+    use tokio_util::bytes::Bytes;
+
+    const N_PARTIES: usize = 8;
+
+    #[tokio::test]
+    async fn many_king_rounds() {
+        let mut testnet = InProcTestNet::new(N_PARTIES);
+        testnet.set_king_id(3);
+
+        let results = testnet
+            .simulate_network_round((), |net, _| async move {
+                let mut outputs = Vec::new();
+                for round in 0..1000u32 {
+                    let output: u32 = net
+                        .king_round(
+                            &(net.party_id() + round),
+                            |rs| vec![rs.shares.iter().sum(); N_PARTIES],
+                            MultiplexedStreamID::Two,
+                            0,
+                        )
+                        .await
+                        .unwrap();
+                    outputs.push(output);
+                }
+                outputs
+            })
+            .await;
+
+        let ids_sum = (0..N_PARTIES as u32).sum::<u32>();
+        let expected = (0..1000u32)
+            .map(|round| ids_sum + round * N_PARTIES as u32)
+            .collect::<Vec<_>>();
+        assert!(results.iter().all(|outputs| *outputs == expected));
+    }
+
+    #[tokio::test]
+    async fn dropped_peer_is_not_connected() {
+        let mut nodes = InProcTestNet::new(2).into_nodes();
+        let peer = nodes.pop().unwrap();
+        let king = nodes.pop().unwrap();
+        drop(peer);
+
+        let sid = MultiplexedStreamID::Zero;
+        assert!(matches!(
+            king.send_to(1, Bytes::from_static(b"hi"), sid).await,
+            Err(MpcNetError::NotConnected)
+        ));
+        assert!(matches!(
+            king.recv_from(1, sid).await,
+            Err(MpcNetError::NotConnected)
+        ));
+        assert!(matches!(
+            king.recv_from(5, sid).await,
+            Err(MpcNetError::Generic(_))
+        ));
+    }
+}
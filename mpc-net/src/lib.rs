@@ -1,31 +1,98 @@
+pub mod cancel;
+pub mod chaos;
+pub mod inproc;
 pub mod multi;
 pub mod prod;
+#[cfg(feature = "recording")]
+pub mod recording;
 pub mod ser_net;
+pub mod shared;
+pub mod stats;
+pub mod subnet;
+pub mod timeout;
 
 use async_trait::async_trait;
 use auto_impl::auto_impl;
-use futures::stream::FuturesOrdered;
+use futures::stream::{FuturesOrdered, FuturesUnordered};
 use futures::StreamExt;
 pub use multi::LocalTestNet;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use timeout::DEFAULT_ROUND_TIMEOUT;
 use tokio::sync::Mutex;
 use tokio_util::bytes::Bytes;
 
+/// Identifies the job a connection is used for. Every frame is prefixed
+/// with it, so that frames of another job sharing the socket are rejected.
+pub type JobId = [u8; 32];
+
 #[derive(Clone, Debug)]
 pub enum MpcNetError {
     Generic(String),
-    Protocol { err: String, party: u32 },
+    Protocol {
+        err: String,
+        party: u32,
+    },
     NotConnected,
-    BadInput { err: &'static str },
+    BadInput {
+        err: String,
+    },
+    /// A value could not be (de)serialized
+    Serialization {
+        err: String,
+    },
+    /// An error of the connection, e.g io or TLS, kept as the source
+    Transport(Arc<dyn std::error::Error + Send + Sync>),
+}
+
+impl std::fmt::Display for MpcNetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MpcNetError::Generic(err) => write!(f, "{err}"),
+            MpcNetError::Protocol { err, party } => {
+                write!(f, "protocol error with party {party}: {err}")
+            }
+            MpcNetError::NotConnected => write!(f, "not connected"),
+            MpcNetError::BadInput { err } => write!(f, "bad input: {err}"),
+            MpcNetError::Serialization { err } => {
+                write!(f, "serialization error: {err}")
+            }
+            MpcNetError::Transport(err) => write!(f, "transport error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for MpcNetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MpcNetError::Transport(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
 }
 
-impl<T: ToString> From<T> for MpcNetError {
-    fn from(e: T) -> Self {
-        MpcNetError::Generic(e.to_string())
+macro_rules! impl_from_as_transport {
+    ($($err:ty),*) => {
+        $(impl From<$err> for MpcNetError {
+            fn from(e: $err) -> Self {
+                MpcNetError::Transport(Arc::new(e))
+            }
+        })*
+    };
+}
+
+impl_from_as_transport!(
+    std::io::Error,
+    std::net::AddrParseError,
+    rustls::Error
+);
+
+impl From<ark_serialize::SerializationError> for MpcNetError {
+    fn from(e: ark_serialize::SerializationError) -> Self {
+        MpcNetError::Serialization { err: e.to_string() }
     }
 }
 
@@ -44,6 +111,11 @@ pub enum MultiplexedStreamID {
     Zero = 0,
     One = 1,
     Two = 2,
+    Three = 3,
+    Four = 4,
+    Five = 5,
+    /// Reserved for check_peers, rounds must not use it
+    Control = 6,
 }
 
 impl MultiplexedStreamID {
@@ -60,17 +132,39 @@ pub enum ClientSendOrKingReceiveResult {
 #[async_trait]
 #[auto_impl(&, &mut, Arc)]
 pub trait MpcNet: Send + Sync {
-    /// Am I the first party?
-
+    /// Am I the king?
     fn is_king(&self) -> bool {
-        self.party_id() == 0
+        self.party_id() == self.king_id()
+    }
+    /// Which party acts as the king? Defaults to the first party.
+    fn king_id(&self) -> u32 {
+        0
     }
     /// How many parties are there?
     fn n_parties(&self) -> usize;
     /// What is my party number (0 to n-1)?
     fn party_id(&self) -> u32;
+    /// Ids of all the parties, mine included, in the order used by the king helpers.
+    /// Dense networks use 0 to n-1, a network with sparse ids should override this.
+    /// The ids must be unique, the king keys the shares it receives by id.
+    fn party_ids(&self) -> Box<dyn Iterator<Item = u32> + Send + '_> {
+        Box::new(0..self.n_parties() as u32)
+    }
+    /// Index of my share, i.e. my position in party_ids, which is my id on a
+    /// dense network. None for a king that holds no share.
+    fn party_index(&self) -> Option<usize> {
+        let own_id = self.party_id();
+        self.party_ids().position(|id| id == own_id)
+    }
     /// Is the network layer initalized?
     fn is_init(&self) -> bool;
+    /// How long the king waits for the shares of a round, see AdaptiveTimeout
+    fn round_timeout(&self) -> Duration {
+        DEFAULT_ROUND_TIMEOUT
+    }
+    /// Called by the king with the time it took to receive every share of a
+    /// round, or with the timeout if the round timed out
+    fn record_round_time(&self, _elapsed: Duration) {}
     async fn recv_from(
         &self,
         id: u32,
@@ -86,56 +180,123 @@ pub trait MpcNet: Send + Sync {
     /// All parties send bytes to the king. The king receives all the bytes
     /// Note: this function is intended to be used in ser_net only since timeouts
     /// may occur in this stage.
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(party_id = self.party_id(), sid = ?sid, bytes = bytes.len())
+    )]
     async fn client_send_or_king_receive(
         &self,
         bytes: &[u8],
         sid: MultiplexedStreamID,
         timeout: Duration,
     ) -> Result<Option<ClientSendOrKingReceiveResult>, MpcNetError> {
-        let bytes_out = Bytes::copy_from_slice(bytes);
-        let results_store = &Arc::new(Mutex::new(HashMap::new()));
-
-        let r = if self.is_king() {
-            let retrieve_task = async move {
-                let mut r = FuturesOrdered::new();
-                for id in 1..self.n_parties() as u32 {
-                    r.push_back(Box::pin(async move {
-                        let bytes_in = self.recv_from(id, sid).await?;
-                        results_store.lock().await.insert(id, bytes_in);
-                        Ok::<_, MpcNetError>(())
-                    }));
-                }
+        let results = self
+            .client_send_or_king_receive_multi_raw(&[(sid, bytes)], timeout)
+            .await?;
+        Ok(results.and_then(|mut results| results.pop()))
+    }
 
-                r.collect::<Vec<_>>().await
-            };
+    /// client_send_or_king_receive on several stream ids at once.
+    /// The king drives the receives of all stream ids in a single future,
+    /// under one combined timeout, and gets one result per stream id,
+    /// in the order of payloads.
+    /// Note: like client_send_or_king_receive, meant for ser_net only.
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(party_id = self.party_id(), sids = payloads.len())
+    )]
+    async fn client_send_or_king_receive_multi_raw(
+        &self,
+        payloads: &[(MultiplexedStreamID, &[u8])],
+        timeout: Duration,
+    ) -> Result<Option<Vec<ClientSendOrKingReceiveResult>>, MpcNetError> {
+        for (idx, (sid, _)) in payloads.iter().enumerate() {
+            if payloads[..idx].iter().any(|(other, _)| other == sid) {
+                return Err(MpcNetError::BadInput {
+                    err: format!("stream id {:?} given twice", sid),
+                });
+            }
+        }
 
-            let _ = tokio::time::timeout(timeout, retrieve_task).await;
-            let mut ret = results_store.lock().await;
-            ret.entry(0).or_insert_with(|| bytes_out.clone()); // Add the king result
+        if !self.is_king() {
+            let king_id = self.king_id();
+            let sends = payloads.iter().map(|(sid, bytes)| {
+                self.send_to(king_id, Bytes::copy_from_slice(bytes), *sid)
+            });
+            futures::future::try_join_all(sends).await?;
+            return Ok(None);
+        }
 
-            if ret.len() == self.n_parties() {
-                // All results obtained
-                let mut sorted_ret = Vec::new();
-                for x in 0..self.n_parties() {
-                    sorted_ret
-                        .push(ret.remove(&(x as u32)).expect("Should exist"));
+        let own_id = self.party_id();
+        // keyed by the index of the stream id in payloads and the party id
+        let results_store = &Mutex::new(HashMap::new());
+        let retrieve_task = async move {
+            let r = FuturesUnordered::new();
+            for (idx, (sid, _)) in payloads.iter().enumerate() {
+                for id in self.party_ids().filter(|id| *id != own_id) {
+                    r.push(async move {
+                        let bytes_in = self.recv_from(id, *sid).await?;
+                        results_store.lock().await.insert((idx, id), bytes_in);
+                        Ok::<_, MpcNetError>(())
+                    });
                 }
-
-                Ok(Some(ClientSendOrKingReceiveResult::Full(sorted_ret)))
-            } else {
-                // Only some results obtained. Leave the recovery logic to the function caller
-                Ok(Some(ClientSendOrKingReceiveResult::Partial(
-                    ret.drain().collect(),
-                )))
             }
-        } else {
-            self.send_to(0, bytes_out, sid).await?;
-            Ok(None)
+
+            r.collect::<Vec<_>>().await
         };
-        r
+
+        let start = Instant::now();
+        let received = tokio::time::timeout(timeout, retrieve_task).await;
+        match &received {
+            Ok(rs) if rs.iter().all(Result::is_ok) => {
+                self.record_round_time(start.elapsed())
+            }
+            // censored: the round took at least the whole timeout
+            Err(_) => self.record_round_time(timeout),
+            Ok(_) => {}
+        }
+        let mut store = results_store.lock().await;
+        let king_is_party = self.party_ids().any(|id| id == own_id);
+        let results = payloads
+            .iter()
+            .enumerate()
+            .map(|(idx, (_, bytes))| {
+                let mut ret = self
+                    .party_ids()
+                    .filter_map(|id| Some((id, store.remove(&(idx, id))?)))
+                    .collect::<HashMap<_, _>>();
+                if king_is_party {
+                    // Add the king result
+                    ret.insert(own_id, Bytes::copy_from_slice(bytes));
+                }
+
+                if ret.len() == self.n_parties() {
+                    // All results obtained
+                    let mut sorted_ret = Vec::new();
+                    for id in self.party_ids() {
+                        sorted_ret.push(ret.remove(&id).expect("Should exist"));
+                    }
+
+                    ClientSendOrKingReceiveResult::Full(sorted_ret)
+                } else {
+                    // Only some results obtained. Leave the recovery logic to the function caller
+                    ClientSendOrKingReceiveResult::Partial(ret)
+                }
+            })
+            .collect();
+        Ok(Some(results))
     }
+
     /// All parties recv bytes from the king.
-    /// Provide bytes iff you're the king!
+    /// Provide bytes iff you're the king, ordered like party_ids!
+    /// A king that is not one of party_ids gets empty bytes back.
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(party_id = self.party_id(), sid = ?sid, bytes = tracing::field::Empty)
+    )]
     async fn client_receive_or_king_send(
         &self,
         bytes_out: Option<Vec<Bytes>>,
@@ -146,32 +307,214 @@ pub trait MpcNet: Send + Sync {
         if let Some(bytes_out) = bytes_out {
             if !self.is_king() {
                 return Err(MpcNetError::BadInput {
-                    err: "recv_from_king called with bytes_out when not king",
+                    err: "recv_from_king called with bytes_out when not king"
+                        .to_string(),
                 });
             }
 
-            let m = bytes_out[0].len();
+            let expected = self.party_ids().count();
+            if bytes_out.len() != expected {
+                return Err(MpcNetError::BadInput {
+                    err: format!(
+                        "king output length mismatch: expected {}, got {}",
+                        expected,
+                        bytes_out.len()
+                    ),
+                });
+            }
+
+            let m = bytes_out.first().map_or(0, Bytes::len);
+            tracing::Span::current().record("bytes", m);
+            let mut own_bytes = None;
 
-            for id in (0..self.n_parties()).filter(|p| *p != own_id as usize) {
-                if bytes_out[id].len() != m {
+            for (idx, id) in self.party_ids().enumerate() {
+                if id == own_id {
+                    own_bytes = Some(bytes_out[idx].clone());
+                    continue;
+                }
+                if bytes_out[idx].len() != m {
                     return Err(MpcNetError::Protocol {
                         err: format!("Peer {} sent wrong number of bytes", id),
-                        party: id as u32,
+                        party: id,
                     });
                 }
 
-                self.send_to(id as u32, bytes_out[id].clone(), sid).await?;
+                self.send_to(id, bytes_out[idx].clone(), sid).await?;
             }
 
-            Ok(bytes_out[own_id as usize].clone())
+            Ok(own_bytes.unwrap_or_default())
         } else {
             if self.is_king() {
                 return Err(MpcNetError::BadInput {
-                    err: "recv_from_king called with no bytes_out when king",
+                    err: "recv_from_king called with no bytes_out when king"
+                        .to_string(),
                 });
             }
 
-            self.recv_from(0, sid).await
+            let bytes_in = self.recv_from(self.king_id(), sid).await?;
+            tracing::Span::current().record("bytes", bytes_in.len());
+            Ok(bytes_in)
+        }
+    }
+
+    /// Every party sends bytes to every other party, without the king.
+    /// Returns the bytes of all parties ordered like party_ids, own included.
+    /// Needs a connection between every pair of parties (e.g LocalTestNet),
+    /// and costs n(n - 1) messages where a round through the king takes 2(n - 1).
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(party_id = self.party_id(), sid = ?sid, bytes = bytes.len())
+    )]
+    async fn broadcast(
+        &self,
+        bytes: &[u8],
+        sid: MultiplexedStreamID,
+    ) -> Result<Vec<Bytes>, MpcNetError> {
+        let own_id = self.party_id();
+        if !self.party_ids().any(|id| id == own_id) {
+            return Err(MpcNetError::BadInput {
+                err: "broadcast called by a king that is not a party"
+                    .to_string(),
+            });
         }
+
+        let bytes_out = Bytes::copy_from_slice(bytes);
+        let sends = self
+            .party_ids()
+            .filter(|id| *id != own_id)
+            .map(|id| self.send_to(id, bytes_out.clone(), sid));
+        let recvs = self.party_ids().map(|id| {
+            let own_bytes = bytes_out.clone();
+            async move {
+                if id == own_id {
+                    Ok(own_bytes)
+                } else {
+                    self.recv_from(id, sid).await
+                }
+            }
+        });
+        let (_, received) = futures::future::try_join(
+            futures::future::try_join_all(sends),
+            futures::future::try_join_all(recvs),
+        )
+        .await?;
+        Ok(received)
+    }
+
+    /// Pings the peers of the king helpers and reports which of them answered
+    /// within PING_TIMEOUT: the king pings every other party, the other
+    /// parties only hear from the king and wait twice as long for its ping.
+    /// Like a round, all parties must call it together. It only uses
+    /// MultiplexedStreamID::Control, so it never leaves bytes on a data
+    /// stream, and every ping carries a nonce so a pong arriving after the
+    /// timeout is discarded by the next check instead of being taken for its
+    /// answer.
+    async fn check_peers(&self) -> HashMap<u32, bool> {
+        let sid = MultiplexedStreamID::Control;
+        let own_id = self.party_id();
+
+        if self.is_king() {
+            let nonce = ping_nonce();
+            let ping = control_message(PING, nonce);
+            let mut pings = FuturesOrdered::new();
+            for id in self.party_ids().filter(|id| *id != own_id) {
+                let ping = ping.clone();
+                pings.push_back(Box::pin(async move {
+                    let pong = async {
+                        self.send_to(id, ping, sid).await?;
+                        // pongs to earlier checks that timed out
+                        while self.recv_from(id, sid).await?
+                            != control_message(PONG, nonce)
+                        {
+                        }
+                        Ok::<_, MpcNetError>(())
+                    };
+                    let alive = matches!(
+                        tokio::time::timeout(PING_TIMEOUT, pong).await,
+                        Ok(Ok(()))
+                    );
+                    (id, alive)
+                }));
+            }
+            pings.collect().await
+        } else {
+            let king_id = self.king_id();
+            let pong = async {
+                let ping = self.recv_from(king_id, sid).await?;
+                let nonce = match ping.strip_prefix(PING) {
+                    Some(nonce) => nonce,
+                    None => {
+                        return Err(MpcNetError::Protocol {
+                            err: "Expected a ping".to_string(),
+                            party: king_id,
+                        })
+                    }
+                };
+                let pong = [PONG, nonce].concat();
+                self.send_to(king_id, Bytes::from(pong), sid).await
+            };
+            // the king may still be waiting on a slow peer of the last check
+            let alive = matches!(
+                tokio::time::timeout(2 * PING_TIMEOUT, pong).await,
+                Ok(Ok(()))
+            );
+            HashMap::from([(king_id, alive)])
+        }
+    }
+}
+
+/// How long check_peers waits for a peer to answer
+pub const PING_TIMEOUT: Duration = Duration::from_secs(5);
+const PING: &[u8] = b"ping";
+const PONG: &[u8] = b"pong";
+
+/// Tells the pongs of one check_peers call from those of earlier calls
+fn ping_nonce() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+fn control_message(kind: &[u8], nonce: u64) -> Bytes {
+    Bytes::from([kind, &nonce.to_le_bytes()].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LocalTestNet, MpcNet, MpcNetError};
+    use std::error::Error;
+
+    #[test]
+    fn errors_display_and_box() {
+        fn boxed() -> Result<(), Box<dyn Error>> {
+            Err(MpcNetError::Protocol {
+                err: "bad share".to_string(),
+                party: 2,
+            })?
+        }
+        let err = boxed().unwrap_err();
+        assert_eq!(err.to_string(), "protocol error with party 2: bad share");
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn transport_errors_keep_their_source() {
+        let io = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "closed");
+        let err = MpcNetError::from(io);
+        assert_eq!(err.to_string(), "transport error: closed");
+        let source = err.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<std::io::Error>().unwrap().kind(),
+            std::io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[tokio::test]
+    async fn mpc_net_is_dyn_compatible() {
+        let testnet = LocalTestNet::new_local_testnet(4).await.unwrap();
+        let king: &dyn MpcNet = testnet.get_king();
+        assert_eq!(king.party_ids().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(king.party_index(), Some(0));
     }
 }
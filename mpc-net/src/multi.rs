@@ -1,22 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::{TryFrom, TryInto};
 use std::fmt::{Debug, Formatter};
 use std::future::Future;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 
 use crate::ser_net::{MpcSerNet, ReceivedShares};
-use crate::{MpcNetError, MultiplexedStreamID};
+use crate::timeout::AdaptiveTimeout;
+use crate::{JobId, MpcNetError, MultiplexedStreamID};
 use async_smux::{MuxBuilder, MuxStream};
 use async_trait::async_trait;
 use futures::stream::{FuturesOrdered, FuturesUnordered};
 use futures::{SinkExt, StreamExt, TryStreamExt};
 use log::trace;
 use parking_lot::Mutex;
-use tokio::sync::Mutex as TokioMutex;
-use tokio_util::bytes::Bytes;
+use tokio::sync::{Mutex as TokioMutex, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_util::bytes::{BufMut, Bytes, BytesMut};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
 use super::MpcNet;
@@ -36,6 +40,8 @@ pub struct Peer<IO: AsyncRead + AsyncWrite + Unpin> {
     pub id: u32,
     pub listen_addr: SocketAddr,
     pub streams: Option<Vec<TokioMutex<WrappedMuxStream<IO>>>>,
+    /// The smux worker serving streams, stopped when the peer is dropped
+    pub worker: Option<MuxWorkerHandle>,
 }
 
 impl<IO: AsyncRead + AsyncWrite + Unpin> Debug for Peer<IO> {
@@ -54,25 +60,149 @@ impl<IO: AsyncRead + AsyncWrite + Unpin> Clone for Peer<IO> {
             id: self.id,
             listen_addr: self.listen_addr,
             streams: None,
+            worker: None,
         }
     }
 }
 
+impl<IO: AsyncRead + AsyncWrite + Unpin> Peer<IO> {
+    /// Flushes and closes the streams to the peer, then stops its smux
+    /// worker. Once the streams are flushed the worker has written all
+    /// their frames to the connection, so nothing sent is lost.
+    pub async fn shutdown(&mut self) -> Result<(), MpcNetError> {
+        if let Some(streams) = self.streams.take() {
+            for stream in streams {
+                SinkExt::<Bytes>::close(&mut stream.into_inner()).await?;
+            }
+        }
+        if let Some(worker) = self.worker.take() {
+            worker.shutdown().await;
+        }
+        Ok(())
+    }
+}
+
+/// Handle of the smux worker task of a connection, which would otherwise
+/// outlive the connection. Dropping the handle aborts the task right away:
+/// every send flushes its stream, so only frames fed without a flush can be
+/// lost, use Peer::shutdown to close the connection cleanly.
+#[derive(Debug)]
+pub struct MuxWorkerHandle {
+    task: JoinHandle<()>,
+}
+
+impl MuxWorkerHandle {
+    fn spawn<W: Future + Send + 'static>(
+        worker: W,
+        runtime: &tokio::runtime::Handle,
+    ) -> Self {
+        Self {
+            task: runtime.spawn(async move {
+                let _ = worker.await;
+            }),
+        }
+    }
+
+    /// Stops the worker and waits until it is gone
+    pub async fn shutdown(mut self) {
+        self.task.abort();
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for MuxWorkerHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
 pub type WrappedMuxStream<T> = Framed<MuxStream<T>, LengthDelimitedCodec>;
 pub const MULTIPLEXED_STREAMS: usize = MultiplexedStreamID::channel_count();
 
+/// Tuning of the smux multiplexer running over each connection.
+/// The queues are counted in smux frames, which carry at most 64KiB each,
+/// and are shared by the MULTIPLEXED_STREAMS channels of the connection.
+/// To keep a whole d_fft payload (m / l field elements to or from the king)
+/// in flight on every channel, size them to at least
+/// MULTIPLEXED_STREAMS * payload_bytes / 64KiB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MuxConfig {
+    /// Frames waiting to be written to the connection
+    pub max_tx_queue: NonZeroUsize,
+    /// Frames received but not yet read by a channel
+    pub max_rx_queue: NonZeroUsize,
+}
+
+impl Default for MuxConfig {
+    /// The async_smux defaults
+    fn default() -> Self {
+        Self {
+            max_tx_queue: NonZeroUsize::new(1024).unwrap(),
+            max_rx_queue: NonZeroUsize::new(1024).unwrap(),
+        }
+    }
+}
+
 /// Should be called immediately after making a connection to a peer.
+/// The connection is served until the returned worker handle is shut down or
+/// dropped, see MuxWorkerHandle.
 pub async fn multiplex_stream<
     T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
 >(
     channels: usize,
     is_server: bool,
     stream: T,
-) -> Result<Vec<TokioMutex<WrappedMuxStream<T>>>, MpcNetError> {
+) -> Result<(Vec<TokioMutex<WrappedMuxStream<T>>>, MuxWorkerHandle), MpcNetError>
+{
+    multiplex_stream_with_config(
+        channels,
+        is_server,
+        stream,
+        MuxConfig::default(),
+    )
+    .await
+}
+
+/// multiplex_stream with a tuned multiplexer
+pub async fn multiplex_stream_with_config<
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+>(
+    channels: usize,
+    is_server: bool,
+    stream: T,
+    config: MuxConfig,
+) -> Result<(Vec<TokioMutex<WrappedMuxStream<T>>>, MuxWorkerHandle), MpcNetError>
+{
+    multiplex_stream_on(
+        channels,
+        is_server,
+        stream,
+        config,
+        &tokio::runtime::Handle::current(),
+    )
+    .await
+}
+
+/// multiplex_stream_with_config, spawning the smux worker on `runtime`
+/// instead of the runtime the caller happens to run on
+pub async fn multiplex_stream_on<
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+>(
+    channels: usize,
+    is_server: bool,
+    stream: T,
+    config: MuxConfig,
+    runtime: &tokio::runtime::Handle,
+) -> Result<(Vec<TokioMutex<WrappedMuxStream<T>>>, MuxWorkerHandle), MpcNetError>
+{
     if is_server {
+        let mut builder = MuxBuilder::server();
+        builder
+            .with_max_tx_queue(config.max_tx_queue)
+            .with_max_rx_queue(config.max_rx_queue);
         let (_connector, mut acceptor, worker) =
-            MuxBuilder::server().with_connection(stream).build();
-        tokio::spawn(worker);
+            builder.with_connection(stream).build();
+        let worker = MuxWorkerHandle::spawn(worker, runtime);
         let mut ret = Vec::new();
         for _ in 0..channels {
             ret.push(TokioMutex::new(wrap_stream(
@@ -84,17 +214,21 @@ pub async fn multiplex_stream<
             )));
         }
 
-        Ok(ret)
+        Ok((ret, worker))
     } else {
+        let mut builder = MuxBuilder::client();
+        builder
+            .with_max_tx_queue(config.max_tx_queue)
+            .with_max_rx_queue(config.max_rx_queue);
         let (connector, _acceptor, worker) =
-            MuxBuilder::client().with_connection(stream).build();
-        tokio::spawn(worker);
+            builder.with_connection(stream).build();
+        let worker = MuxWorkerHandle::spawn(worker, runtime);
         let mut ret = Vec::new();
         for _ in 0..channels {
             ret.push(TokioMutex::new(wrap_stream(connector.connect()?)));
         }
 
-        Ok(ret)
+        Ok((ret, worker))
     }
 }
 
@@ -104,6 +238,48 @@ pub struct MpcNetConnection<IO: AsyncRead + AsyncWrite + Unpin> {
     pub listener: Option<TcpListener>,
     pub peers: HashMap<u32, Peer<IO>>,
     pub n_parties: usize,
+    pub job_id: JobId,
+    pub king_id: u32,
+    /// The king only coordinates and holds no share: it is left out of
+    /// n_parties and party_ids
+    pub dedicated_king: bool,
+    /// The king's round timeout, tuned to the rounds it measures
+    pub timeout: AdaptiveTimeout,
+}
+
+/// Prefixes the payload with the job id
+pub(crate) fn frame_with_job_id(job_id: &JobId, payload: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(job_id.len() + payload.len());
+    frame.put_slice(job_id);
+    frame.put_slice(payload);
+    frame.freeze()
+}
+
+/// Strips the job id prefix from a frame received from `party`,
+/// rejecting frames that belong to another job
+pub(crate) fn strip_job_id(
+    job_id: &JobId,
+    mut frame: Bytes,
+    party: u32,
+) -> Result<Bytes, MpcNetError> {
+    if frame.len() < job_id.len() || frame[..job_id.len()] != job_id[..] {
+        return Err(MpcNetError::Protocol {
+            err: "Received frame for another job".to_string(),
+            party,
+        });
+    }
+
+    Ok(frame.split_off(job_id.len()))
+}
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> MpcNetConnection<IO> {
+    /// Shuts down the connection to every peer, see Peer::shutdown
+    pub async fn shutdown(mut self) -> Result<(), MpcNetError> {
+        for peer in self.peers.values_mut() {
+            peer.shutdown().await?;
+        }
+        Ok(())
+    }
 }
 
 impl MpcNetConnection<TcpStream> {
@@ -142,10 +318,12 @@ impl MpcNetConnection<TcpStream> {
 
                 let peer_id = stream.read_u32().await?;
                 // Now, multiplex the stream
-                let muxed =
+                let (muxed, worker) =
                     multiplex_stream(MULTIPLEXED_STREAMS, true, stream).await?;
-                new_peers_server.lock().get_mut(&peer_id).unwrap().streams =
-                    Some(muxed);
+                let mut peers = new_peers_server.lock();
+                let peer = peers.get_mut(&peer_id).unwrap();
+                peer.streams = Some(muxed);
+                peer.worker = Some(worker);
                 trace!("{my_id} connected to peer {peer_id}")
             }
 
@@ -171,14 +349,13 @@ impl MpcNetConnection<TcpStream> {
                     })?;
                 stream.write_u32(my_id).await.unwrap();
 
-                let muxed =
+                let (muxed, worker) =
                     multiplex_stream(MULTIPLEXED_STREAMS, false, stream)
                         .await?;
-                new_peers_client
-                    .lock()
-                    .get_mut(&next_peer_to_connect_to)
-                    .unwrap()
-                    .streams = Some(muxed);
+                let mut peers = new_peers_client.lock();
+                let peer = peers.get_mut(&next_peer_to_connect_to).unwrap();
+                peer.streams = Some(muxed);
+                peer.worker = Some(worker);
                 trace!("{my_id} connected to peer {next_peer_to_connect_to}")
             }
 
@@ -204,19 +381,11 @@ impl MpcNetConnection<TcpStream> {
             )
             .await?;
 
-        if from_all.is_some() {
-            self.client_receive_or_king_send_serialized(
-                Some(from_all.unwrap().shares),
-                genesis_round_channel,
-            )
-            .await?;
-        } else {
-            self.client_receive_or_king_send_serialized(
-                None,
-                genesis_round_channel,
-            )
-            .await?;
-        }
+        self.client_receive_or_king_send_serialized(
+            from_all.map(|rs| rs.shares),
+            genesis_round_channel,
+        )
+        .await?;
 
         for peer in &self.peers {
             if peer.0 == &self.id {
@@ -236,8 +405,40 @@ impl MpcNetConnection<TcpStream> {
     }
 }
 
+/// Runs CPU heavy jobs on the blocking pool, at most a fixed number at a
+/// time, see LocalTestNet::simulate_network_round_bounded
+#[derive(Clone)]
+pub struct CpuBound {
+    permits: Arc<Semaphore>,
+}
+
+impl CpuBound {
+    pub fn new(max_concurrency: usize) -> Self {
+        assert!(max_concurrency > 0, "max_concurrency must be positive");
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrency)),
+        }
+    }
+
+    /// Runs job once a slot is free and returns its output
+    pub async fn run<R: Send + 'static>(
+        &self,
+        job: impl FnOnce() -> R + Send + 'static,
+    ) -> R {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("the semaphore is never closed");
+        tokio::task::spawn_blocking(job)
+            .await
+            .expect("CPU bound job panicked")
+    }
+}
+
 pub struct LocalTestNet {
-    nodes: HashMap<usize, MpcNetConnection<TcpStream>>,
+    /// Ordered by party id, so the rounds return the outputs in that order
+    nodes: BTreeMap<usize, MpcNetConnection<TcpStream>>,
 }
 
 impl LocalTestNet {
@@ -261,6 +462,10 @@ impl LocalTestNet {
                 listener: Some(my_listener),
                 peers: Default::default(),
                 n_parties,
+                job_id: JobId::default(),
+                king_id: 0,
+                dedicated_king: false,
+                timeout: AdaptiveTimeout::default(),
             };
             for peer_id in 0..n_parties {
                 // NOTE: this is the listen addr
@@ -271,6 +476,7 @@ impl LocalTestNet {
                         id: peer_id as u32,
                         listen_addr: peer_addr,
                         streams: None,
+                        worker: None,
                     },
                 );
             }
@@ -293,8 +499,24 @@ impl LocalTestNet {
         Ok(Self { nodes })
     }
 
+    /// Creates n_share_holders + 1 nodes, where node 0 is a king holding no share.
+    /// The share holders are nodes 1 to n_share_holders and n_parties() is
+    /// n_share_holders, so the network matches PackedSharingParams with
+    /// n = n_share_holders. Rounds still return one output per node, the king's first.
+    /// The king gets empty bytes back from client_receive_or_king_send.
+    pub async fn new_local_testnet_with_king(
+        n_share_holders: usize,
+    ) -> Result<Self, MpcNetError> {
+        let mut testnet = Self::new_local_testnet(n_share_holders + 1).await?;
+        for node in testnet.nodes.values_mut() {
+            node.n_parties = n_share_holders;
+            node.dedicated_king = true;
+        }
+        Ok(testnet)
+    }
+
     /// For each node, run a function (a Future) provided by the parameter that accepts the node's Connection.
-    /// Then, run all these futures in a FuturesOrdered.
+    /// Then, run all these futures in a FuturesOrdered, so the i-th output is party i's.
     ///
     /// The provided `user_data` of type U is then given to each of these futures, by cloning it.
     /// So if you have a struct that you want to pass to each of these futures, you can do that.
@@ -311,20 +533,48 @@ impl LocalTestNet {
             + Clone
             + 'static,
     ) -> Vec<K> {
-        let mut futures = FuturesOrdered::new();
-        let mut sorted_nodes = self.nodes.into_iter().collect::<Vec<_>>();
-        sorted_nodes.sort_by(|a, b| a.0.cmp(&b.0));
-        for (_, connections) in sorted_nodes {
-            let next_f = f.clone();
-            let next_user_data = user_data.clone();
-            futures.push_back(Box::pin(async move {
-                let task =
-                    async move { next_f(connections, next_user_data).await };
-                let handle = tokio::task::spawn(task);
-                handle.await.unwrap()
-            }));
-        }
-        futures.collect().await
+        let n_parties = self.nodes.len();
+        futures::stream::iter(self.nodes.into_values())
+            .map(|connections| {
+                let next_f = f.clone();
+                let next_user_data = user_data.clone();
+                async move {
+                    let task = async move {
+                        next_f(connections, next_user_data).await
+                    };
+                    let handle = tokio::task::spawn(task);
+                    handle.await.unwrap()
+                }
+            })
+            .buffered(n_parties)
+            .collect()
+            .await
+    }
+
+    /// Same as simulate_network_round, but f also gets a CpuBound that runs
+    /// at most max_concurrency CPU heavy jobs of the parties at a time.
+    /// All the parties still run at once, so the rounds where they wait on
+    /// each other make progress, only the jobs given to the CpuBound queue.
+    pub async fn simulate_network_round_bounded<
+        F: Future<Output = K> + Send,
+        K: Send + Sync + 'static,
+        U: Clone + Send + Sync + 'static,
+    >(
+        self,
+        max_concurrency: usize,
+        user_data: U,
+        f: impl Fn(MpcNetConnection<TcpStream>, U, CpuBound) -> F
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+    ) -> Vec<K> {
+        let cpu = CpuBound::new(max_concurrency);
+        self.simulate_network_round(
+            (user_data, cpu),
+            move |conn, (user_data, cpu)| f(conn, user_data, cpu),
+        )
+        .await
     }
 
     pub async fn simulate_lossy_network_round<
@@ -341,10 +591,8 @@ impl LocalTestNet {
             + 'static,
     ) -> ReceivedShares<K> {
         let mut futures = FuturesOrdered::new();
-        let mut sorted_nodes = self.nodes.into_iter().collect::<Vec<_>>();
-        sorted_nodes.sort_by(|a, b| a.0.cmp(&b.0));
-        let n_parties = sorted_nodes.len();
-        for (_, connections) in sorted_nodes {
+        let n_parties = self.nodes.len();
+        for connections in self.nodes.into_values() {
             let next_f = f.clone();
             let next_user_data = user_data.clone();
             futures.push_back(Box::pin(async move {
@@ -359,6 +607,14 @@ impl LocalTestNet {
         ReceivedShares {
             shares: result[0..n_parties - 1].to_vec(),
             parties: (0..(n_parties - 1) as u32).collect(),
+            faulty: Vec::new(),
+        }
+    }
+
+    /// Sets the job id of all the nodes, frames are tagged with it
+    pub fn set_job_id(&mut self, job_id: JobId) {
+        for node in self.nodes.values_mut() {
+            node.job_id = job_id;
         }
     }
 
@@ -370,8 +626,16 @@ impl LocalTestNet {
         self.nodes.get(&party_id).unwrap()
     }
 
+    /// Sets the king of every node
+    pub fn set_king_id(&mut self, king_id: u32) {
+        for node in self.nodes.values_mut() {
+            node.king_id = king_id;
+        }
+    }
+
     pub fn get_king(&self) -> &MpcNetConnection<TcpStream> {
-        self.get_connection(0)
+        let king_id = self.nodes.values().next().map_or(0, |n| n.king_id);
+        self.get_connection(king_id as usize)
     }
 }
 
@@ -387,10 +651,30 @@ impl<IO: AsyncRead + AsyncWrite + Unpin + Send> MpcNet
         self.id
     }
 
+    fn king_id(&self) -> u32 {
+        self.king_id
+    }
+
+    fn party_ids(&self) -> Box<dyn Iterator<Item = u32> + Send + '_> {
+        let (king_id, dedicated_king) = (self.king_id, self.dedicated_king);
+        let n_nodes = self.n_parties as u32 + dedicated_king as u32;
+        Box::new(
+            (0..n_nodes).filter(move |id| !dedicated_king || *id != king_id),
+        )
+    }
+
     fn is_init(&self) -> bool {
         self.peers.iter().all(|r| r.1.streams.is_some())
     }
 
+    fn round_timeout(&self) -> Duration {
+        self.timeout.timeout()
+    }
+
+    fn record_round_time(&self, elapsed: Duration) {
+        self.timeout.record(elapsed)
+    }
+
     async fn recv_from(
         &self,
         id: u32,
@@ -399,7 +683,8 @@ impl<IO: AsyncRead + AsyncWrite + Unpin + Send> MpcNet
         let peer = self.peers.get(&id).ok_or_else(|| {
             MpcNetError::Generic(format!("Peer {} not found", id))
         })?;
-        recv_stream(peer.streams.as_ref(), sid).await
+        let frame = recv_stream(peer.streams.as_ref(), sid).await?;
+        strip_job_id(&self.job_id, frame, id)
     }
 
     async fn send_to(
@@ -411,17 +696,90 @@ impl<IO: AsyncRead + AsyncWrite + Unpin + Send> MpcNet
         let peer = self.peers.get(&id).ok_or_else(|| {
             MpcNetError::Generic(format!("Peer {} not found", id))
         })?;
-        send_stream(peer.streams.as_ref(), bytes, sid).await
+        let frame = frame_with_job_id(&self.job_id, &bytes);
+        send_stream(peer.streams.as_ref(), frame, sid).await
     }
 }
 
+/// Largest payload sent in a single frame, bigger payloads are split into chunks.
+/// Stays well below the frame length limit of the codec.
+pub const MAX_CHUNK_SIZE: usize = 1 << 22;
+/// chunk_index and total_chunks, both u32
+const CHUNK_HEADER_LEN: usize = 8;
+
+/// Sends `bytes` as one or more frames, each prefixed with (chunk_index, total_chunks)
+pub(crate) async fn send_chunked<T: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut WrappedStream<T>,
+    bytes: Bytes,
+) -> Result<(), MpcNetError> {
+    let total_chunks = bytes.len().div_ceil(MAX_CHUNK_SIZE).max(1);
+    let total_chunks =
+        u32::try_from(total_chunks).map_err(|_| MpcNetError::BadInput {
+            err: format!("Payload of {} bytes is too large", bytes.len()),
+        })?;
+
+    for chunk_index in 0..total_chunks {
+        let start = chunk_index as usize * MAX_CHUNK_SIZE;
+        let end = (start + MAX_CHUNK_SIZE).min(bytes.len());
+        let mut frame = BytesMut::with_capacity(CHUNK_HEADER_LEN + end - start);
+        frame.put_u32(chunk_index);
+        frame.put_u32(total_chunks);
+        frame.put_slice(&bytes[start..end]);
+        if chunk_index + 1 < total_chunks {
+            stream.feed(frame.freeze()).await?;
+        } else {
+            // flushes the chunks fed so far as well
+            stream.send(frame.freeze()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Receives the frames sent by send_chunked and reassembles the payload
+pub(crate) async fn recv_chunked<T: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut WrappedStream<T>,
+) -> Result<Bytes, MpcNetError> {
+    let mut payload = BytesMut::new();
+    let mut total_chunks = 1;
+    let mut chunk_index = 0;
+
+    while chunk_index < total_chunks {
+        let mut frame = stream
+            .next()
+            .await
+            .ok_or_else(|| MpcNetError::Generic("Stream died".to_string()))??;
+        if frame.len() < CHUNK_HEADER_LEN {
+            return Err(MpcNetError::Generic(
+                "Received frame without a chunk header".to_string(),
+            ));
+        }
+
+        let header = frame.split_to(CHUNK_HEADER_LEN);
+        let index = u32::from_be_bytes(header[..4].try_into().unwrap());
+        let total = u32::from_be_bytes(header[4..].try_into().unwrap());
+        if chunk_index == 0 {
+            total_chunks = total;
+        }
+        if index != chunk_index || total != total_chunks {
+            return Err(MpcNetError::Generic(format!(
+                "Expected chunk {chunk_index} of {total_chunks}, got {index} of {total}"
+            )));
+        }
+
+        payload.unsplit(frame);
+        chunk_index += 1;
+    }
+
+    Ok(payload.freeze())
+}
+
 async fn send_stream<T: AsyncRead + AsyncWrite + Unpin>(
     stream: Option<&Vec<TokioMutex<WrappedStream<T>>>>,
     bytes: Bytes,
     sid: MultiplexedStreamID,
 ) -> Result<(), MpcNetError> {
     if let Some(stream) = stream.and_then(|r| r.get(sid as usize)) {
-        Ok(stream.lock().await.send(bytes).await?)
+        send_chunked(&mut *stream.lock().await, bytes).await
     } else {
         Err(MpcNetError::Generic("Stream is None".to_string()))
     }
@@ -432,13 +790,7 @@ async fn recv_stream<T: AsyncRead + AsyncWrite + Unpin>(
     sid: MultiplexedStreamID,
 ) -> Result<Bytes, MpcNetError> {
     if let Some(stream) = stream.and_then(|r| r.get(sid as usize)) {
-        Ok(stream
-            .lock()
-            .await
-            .next()
-            .await
-            .ok_or_else(|| MpcNetError::Generic("Stream died".to_string()))??
-            .freeze())
+        recv_chunked(&mut *stream.lock().await).await
     } else {
         Err(MpcNetError::Generic("Stream is None".to_string()))
     }
@@ -446,9 +798,18 @@ async fn recv_stream<T: AsyncRead + AsyncWrite + Unpin>(
 
 #[cfg(test)]
 mod tests {
-    use crate::multi::{recv_stream, send_stream};
-    use crate::{LocalTestNet, MultiplexedStreamID};
+    use crate::multi::{recv_stream, send_stream, MAX_CHUNK_SIZE};
+    use crate::ser_net::MpcSerNet;
+    use crate::{
+        ClientSendOrKingReceiveResult, LocalTestNet, MpcNet,
+        MultiplexedStreamID, PING_TIMEOUT,
+    };
     use std::collections::HashMap;
+    use std::convert::TryInto;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio_util::bytes::Bytes;
 
     #[tokio::test]
     async fn test_multiplexing() {
@@ -502,4 +863,293 @@ mod tests {
             })
             .await;
     }
+
+    #[tokio::test]
+    async fn test_chunked_payload() {
+        let testnet = LocalTestNet::new_local_testnet(2).await.unwrap();
+        let payload = (0..2 * MAX_CHUNK_SIZE + 123)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+
+        testnet
+            .simulate_network_round(payload, |conn, payload| async move {
+                let sid = MultiplexedStreamID::One;
+                if conn.is_king() {
+                    conn.send_to(1, payload.into(), sid).await.unwrap();
+                } else {
+                    let received = conn.recv_from(0, sid).await.unwrap();
+                    assert_eq!(payload, received);
+                }
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_check_peers() {
+        const N_PARTIES: usize = 4;
+        const SILENT: u32 = 2;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let healthy = testnet
+            .simulate_network_round((), |conn, _| async move {
+                conn.check_peers().await
+            })
+            .await;
+        assert_eq!(healthy[0].len(), N_PARTIES - 1);
+        assert!(healthy.iter().all(|peers| peers.values().all(|ok| *ok)));
+
+        // a party that doesn't answer is reported by the king
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        let health = testnet
+            .simulate_network_round((), |conn, _| async move {
+                if conn.party_id() == SILENT {
+                    return HashMap::new();
+                }
+                conn.check_peers().await
+            })
+            .await;
+        assert_eq!(health[0].len(), N_PARTIES - 1);
+        for (id, ok) in &health[0] {
+            assert_eq!(*ok, *id != SILENT);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_peers_discards_late_pongs() {
+        const N_PARTIES: usize = 4;
+        const LATE: u32 = 2;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let health = testnet
+            .simulate_network_round((), |conn, _| async move {
+                // answers the first ping after the king gave up on it
+                if conn.party_id() == LATE {
+                    tokio::time::sleep(
+                        PING_TIMEOUT + Duration::from_millis(500),
+                    )
+                    .await;
+                }
+                let late = conn.check_peers().await;
+                let next = conn.check_peers().await;
+
+                // the data streams are left clean
+                let sid = MultiplexedStreamID::Zero;
+                let ids = conn
+                    .client_send_or_king_receive_serialized(
+                        &conn.party_id(),
+                        sid,
+                        N_PARTIES,
+                    )
+                    .await
+                    .unwrap();
+                (late, next, ids.map(|rs| rs.shares))
+            })
+            .await;
+
+        let (late, next, ids) = &health[0];
+        for (id, ok) in late {
+            assert_eq!(*ok, *id != LATE);
+        }
+        assert!(next.values().all(|ok| *ok));
+        assert_eq!(ids.as_deref(), Some(&[0, 1, 2, 3][..]));
+    }
+
+    #[tokio::test]
+    async fn test_round_outputs_ordered_by_party() {
+        const N_PARTIES: usize = 8;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let ids = testnet
+            .simulate_network_round(
+                (),
+                |conn, _| async move { conn.party_id() },
+            )
+            .await;
+
+        assert_eq!(ids, (0..N_PARTIES as u32).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_king_other_than_first_party() {
+        const N_PARTIES: usize = 4;
+        const KING: u32 = 2;
+        let mut testnet =
+            LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        testnet.set_king_id(KING);
+        assert_eq!(testnet.get_king().party_id(), KING);
+
+        let sums = testnet
+            .simulate_network_round((), |conn, _| async move {
+                assert_eq!(conn.is_king(), conn.party_id() == KING);
+                let sid = MultiplexedStreamID::Zero;
+                let king_answer = conn
+                    .client_send_or_king_receive_serialized(
+                        &conn.party_id(),
+                        sid,
+                        0,
+                    )
+                    .await
+                    .unwrap()
+                    .map(|rs| vec![rs.shares.iter().sum::<u32>(); N_PARTIES]);
+                conn.client_receive_or_king_send_serialized(king_answer, sid)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            })
+            .await;
+
+        assert!(sums.iter().all(|s| *s == (0..N_PARTIES as u32).sum()));
+    }
+
+    #[tokio::test]
+    async fn test_king_without_share() {
+        const N_SHARE_HOLDERS: usize = 4;
+        let testnet =
+            LocalTestNet::new_local_testnet_with_king(N_SHARE_HOLDERS)
+                .await
+                .unwrap();
+
+        let results = testnet
+            .simulate_network_round((), |conn, _| async move {
+                assert_eq!(conn.n_parties(), N_SHARE_HOLDERS);
+                assert_eq!(
+                    conn.party_ids().collect::<Vec<_>>(),
+                    (1..=N_SHARE_HOLDERS as u32).collect::<Vec<_>>()
+                );
+
+                let sid = MultiplexedStreamID::Zero;
+                let king_answer = conn
+                    .client_send_or_king_receive(
+                        &conn.party_id().to_le_bytes(),
+                        sid,
+                        Duration::from_secs(5),
+                    )
+                    .await
+                    .unwrap()
+                    .map(|result| {
+                        let ClientSendOrKingReceiveResult::Full(shares) =
+                            result
+                        else {
+                            panic!("a share holder timed out");
+                        };
+                        let sum = shares
+                            .iter()
+                            .map(|b| {
+                                u32::from_le_bytes(b[..].try_into().unwrap())
+                            })
+                            .sum::<u32>();
+                        vec![
+                            Bytes::copy_from_slice(&sum.to_le_bytes());
+                            N_SHARE_HOLDERS
+                        ]
+                    });
+                conn.client_receive_or_king_send(king_answer, sid)
+                    .await
+                    .unwrap()
+            })
+            .await;
+
+        // the king holds no share, the share holders got the sum of their ids
+        assert!(results[0].is_empty());
+        let sum = (1..=N_SHARE_HOLDERS as u32).sum::<u32>();
+        assert!(results[1..].iter().all(|r| r[..] == sum.to_le_bytes()));
+    }
+
+    #[tokio::test]
+    async fn test_bounded_round() {
+        const N_PARTIES: usize = 8;
+        const MAX_CONCURRENCY: usize = 3;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        // jobs running now, and the most seen at once
+        let running = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+
+        // every party waits on the king, which waits on every party
+        let sums = testnet
+            .simulate_network_round_bounded(
+                MAX_CONCURRENCY,
+                running.clone(),
+                |conn, running, cpu| async move {
+                    let party_id = conn.party_id();
+                    let id = cpu
+                        .run(move || {
+                            let now = running.0.fetch_add(1, Ordering::SeqCst);
+                            running.1.fetch_max(now + 1, Ordering::SeqCst);
+                            std::thread::sleep(Duration::from_millis(20));
+                            running.0.fetch_sub(1, Ordering::SeqCst);
+                            party_id
+                        })
+                        .await;
+
+                    let sid = MultiplexedStreamID::Zero;
+                    let king_answer = conn
+                        .client_send_or_king_receive_serialized(&id, sid, 0)
+                        .await
+                        .unwrap()
+                        .map(|rs| {
+                            vec![rs.shares.iter().sum::<u32>(); N_PARTIES]
+                        });
+                    conn.client_receive_or_king_send_serialized(
+                        king_answer,
+                        sid,
+                    )
+                    .await
+                    .unwrap()
+                    .unwrap()
+                },
+            )
+            .await;
+
+        assert!(sums.iter().all(|s| *s == (0..N_PARTIES as u32).sum()));
+        // scheduling decides how many jobs overlap, never more than the bound
+        let peak = running.1.load(Ordering::SeqCst);
+        assert!(peak >= 1);
+        assert!(peak <= MAX_CONCURRENCY);
+    }
+
+    #[tokio::test]
+    async fn dropped_connections_stop_their_workers() {
+        let metrics = tokio::runtime::Handle::current().metrics();
+        let baseline = metrics.num_alive_tasks();
+
+        for _ in 0..16 {
+            let testnet = LocalTestNet::new_local_testnet(4).await.unwrap();
+            assert!(metrics.num_alive_tasks() > baseline);
+            drop(testnet);
+        }
+
+        // aborted tasks are only reaped once the runtime gets to them
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while metrics.num_alive_tasks() > baseline {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("smux workers outlived their connections");
+    }
+
+    #[tokio::test]
+    async fn shutdown_flushes_the_last_messages() {
+        let metrics = tokio::runtime::Handle::current().metrics();
+        let baseline = metrics.num_alive_tasks();
+        let testnet = LocalTestNet::new_local_testnet(4).await.unwrap();
+
+        // the parties shut down right after sending, before the king reads
+        let results = testnet
+            .simulate_network_round((), |net, _| async move {
+                let received = net
+                    .client_send_or_king_receive_serialized(
+                        &net.party_id(),
+                        MultiplexedStreamID::Zero,
+                        0,
+                    )
+                    .await
+                    .unwrap();
+                net.shutdown().await.unwrap();
+                received.map(|rs| rs.shares)
+            })
+            .await;
+
+        assert_eq!(results[0], Some(vec![0, 1, 2, 3]));
+        assert_eq!(metrics.num_alive_tasks(), baseline);
+    }
 }
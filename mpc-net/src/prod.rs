@@ -1,18 +1,23 @@
 use crate::multi::{
-    multiplex_stream, MpcNetConnection, Peer, WrappedMuxStream,
+    frame_with_job_id, multiplex_stream_on, recv_chunked, send_chunked,
+    strip_job_id, MpcNetConnection, MuxConfig, Peer, WrappedMuxStream,
     MULTIPLEXED_STREAMS,
 };
-use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+use crate::timeout::{AdaptiveTimeout, TimeoutConfig};
+use crate::{JobId, MpcNet, MpcNetError, MultiplexedStreamID};
 use async_trait::async_trait;
-use futures::SinkExt;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
 use rustls::server::AllowAnyAuthenticatedClient;
 use rustls::{RootCertStore, ServerConfig};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
-use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::runtime::Handle;
 use tokio::sync::Mutex;
 use tokio_rustls::{TlsAcceptor, TlsStream};
 use tokio_util::bytes::Bytes;
@@ -38,6 +43,19 @@ impl CertToDer for RustlsCertificate {
     }
 }
 
+/// Builds the store of trusted roots from exactly the given certificates,
+/// e.g the client certs the king should accept for a job
+pub fn root_cert_store_from_certs<T: CertToDer>(
+    certs: &[T],
+) -> Result<RootCertStore, MpcNetError> {
+    let mut store = RootCertStore::empty();
+    for cert in certs {
+        store
+            .add(&rustls::Certificate(cert.serialize_certificate_to_der()?))?;
+    }
+    Ok(store)
+}
+
 pub fn create_server_mutual_tls_acceptor<T: CertToDer>(
     client_certs: RootCertStore,
     server_certificate: T,
@@ -119,10 +137,17 @@ impl<
 pub struct ProdNet<T: IOStream> {
     /// The king will have a connection to each party, and each party will have a connection to the king.
     /// Thus, if this node is a king, there will be n_parties connections below. If this node is not a king,
-    /// then, where will be only a single connection to the thing with ID 0
+    /// then, where will be only a single connection to the king, unless the
+    /// peers were connected to each other with with_mesh
     connections: MpcNetConnection<T>,
+    /// Multiplexer of the connections, with_mesh reuses it
+    mux: MuxConfig,
 }
 
+/// Version of the wire format of [`ProtocolPacket`].
+/// Must be bumped on any incompatible change.
+pub const PROTOCOL_VERSION: u8 = 1;
+
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
 pub enum ProtocolPacket {
     Syn,
@@ -130,12 +155,156 @@ pub enum ProtocolPacket {
     Packet(Vec<u8>),
 }
 
+impl ProtocolPacket {
+    const SYN: u8 = 0;
+    const SYN_ACK: u8 = 1;
+    const PACKET: u8 = 2;
+
+    /// Encodes as `version || opcode || payload`
+    pub fn encode(&self) -> Vec<u8> {
+        let (opcode, payload): (u8, &[u8]) = match self {
+            ProtocolPacket::Syn => (Self::SYN, &[]),
+            ProtocolPacket::SynAck => (Self::SYN_ACK, &[]),
+            ProtocolPacket::Packet(payload) => (Self::PACKET, payload),
+        };
+        let mut bytes = Vec::with_capacity(2 + payload.len());
+        bytes.push(PROTOCOL_VERSION);
+        bytes.push(opcode);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// Decodes a packet received from `party`, rejecting other versions
+    pub fn decode(bytes: &[u8], party: u32) -> Result<Self, MpcNetError> {
+        let protocol_err = |err: String| MpcNetError::Protocol { err, party };
+        let (version, opcode, payload) = match bytes {
+            [version, opcode, payload @ ..] => (*version, *opcode, payload),
+            _ => return Err(protocol_err("Packet too short".to_string())),
+        };
+
+        if version != PROTOCOL_VERSION {
+            return Err(protocol_err(format!(
+                "Unsupported protocol version {version}, expected {PROTOCOL_VERSION}"
+            )));
+        }
+
+        match opcode {
+            Self::SYN if payload.is_empty() => Ok(ProtocolPacket::Syn),
+            Self::SYN_ACK if payload.is_empty() => Ok(ProtocolPacket::SynAck),
+            Self::PACKET => Ok(ProtocolPacket::Packet(payload.to_vec())),
+            _ => Err(protocol_err(format!("Unknown opcode {opcode}"))),
+        }
+    }
+}
+
+/// Programmatic description of a party of the network, so a host
+/// application can launch parties without parsing argv or reading a peer file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkConfig {
+    /// This party's id
+    pub id: u32,
+    /// Id of the king
+    pub king_id: u32,
+    /// Packing factor of the protocol run over the network
+    pub l: usize,
+    /// Threshold of the protocol run over the network, which needs
+    /// 2(t + l) parties
+    pub t: usize,
+    /// Addresses of all the parties, indexed by id.
+    /// The king binds to peers[king_id] and the other parties connect to it.
+    pub peers: Vec<SocketAddr>,
+    /// Multiplexer of every connection of this party
+    pub mux: MuxConfig,
+}
+
 impl ProdNet<TlsStream<TcpStream>> {
+    /// Creates the king or a peer from config, depending on its id
+    /// certs: the client certs if king, otherwise the king's cert
+    pub async fn from_config<R: CertToDer>(
+        config: &NetworkConfig,
+        identity: R,
+        certs: RootCertStore,
+    ) -> Result<ProdNet<TlsStream<TcpStream>>, MpcNetError> {
+        let n_parties = config.peers.len();
+        if n_parties != 2 * (config.t + config.l) {
+            return Err(MpcNetError::BadInput {
+                err: format!(
+                    "{} peers, but t = {} and l = {} need {}",
+                    n_parties,
+                    config.t,
+                    config.l,
+                    2 * (config.t + config.l)
+                ),
+            });
+        }
+        for id in [config.id, config.king_id] {
+            if id as usize >= n_parties {
+                return Err(MpcNetError::BadInput {
+                    err: format!(
+                        "Party id {} out of range for {} parties",
+                        id, n_parties
+                    ),
+                });
+            }
+        }
+
+        let king_addr = config.peers[config.king_id as usize];
+        if config.id == config.king_id {
+            if certs.len() != n_parties - 1 {
+                return Err(MpcNetError::BadInput {
+                    err: format!(
+                        "Expected {} client certs, got {}",
+                        n_parties - 1,
+                        certs.len()
+                    ),
+                });
+            }
+            Self::king_tls(
+                config.king_id,
+                king_addr,
+                identity,
+                certs,
+                config.mux,
+            )
+            .await
+        } else {
+            Self::peer_tls(
+                config.id,
+                config.king_id,
+                king_addr,
+                identity,
+                certs,
+                n_parties,
+                config.mux,
+            )
+            .await
+        }
+    }
+
     /// Returns when all the parties have connected.
     pub async fn new_king_tls<V: ToSocketAddrs, R: CertToDer>(
         bind_addr: V,
         identity: R,
         root_cert_store: RootCertStore,
+    ) -> Result<ProdNet<TlsStream<TcpStream>>, MpcNetError> {
+        Self::king_tls(
+            0,
+            bind_addr,
+            identity,
+            root_cert_store,
+            MuxConfig::default(),
+        )
+        .await
+    }
+
+    /// new_king_tls for the king with id king_id, multiplexing the
+    /// connections with mux
+    async fn king_tls<V: ToSocketAddrs, R: CertToDer>(
+        king_id: u32,
+        bind_addr: V,
+        identity: R,
+        root_cert_store: RootCertStore,
+        mux: MuxConfig,
     ) -> Result<ProdNet<TlsStream<TcpStream>>, MpcNetError> {
         let tcp_listener = tokio::net::TcpListener::bind(bind_addr).await?;
         let n_peers = root_cert_store.roots.len();
@@ -153,7 +322,77 @@ impl ProdNet<TlsStream<TcpStream>> {
 
         let n_parties = n_peers + 1;
 
-        ProdNet::new_from_pre_existing_connection(0, n_parties, tls_conns).await
+        ProdNet::new_from_pre_existing_connection_with_king(
+            king_id,
+            king_id,
+            n_parties,
+            tls_conns,
+            mux,
+            &Handle::current(),
+        )
+        .await
+    }
+
+    /// Like new_king_tls, but binds every peer id to the address and certificate
+    /// it must connect with. A peer claiming an id whose certificate or address
+    /// it doesn't match is rejected, so it cannot impersonate another party.
+    pub async fn new_king_tls_with_peers<V: ToSocketAddrs, R: CertToDer>(
+        bind_addr: V,
+        identity: R,
+        peers: HashMap<u32, (SocketAddr, rustls::Certificate)>,
+    ) -> Result<ProdNet<TlsStream<TcpStream>>, MpcNetError> {
+        let tcp_listener = tokio::net::TcpListener::bind(bind_addr).await?;
+        let mut root_cert_store = RootCertStore::empty();
+        for (_, cert) in peers.values() {
+            root_cert_store.add(cert)?;
+        }
+
+        let tls_acceptor =
+            create_server_mutual_tls_acceptor(root_cert_store, identity)?;
+
+        let mut tls_conns = HashMap::new();
+
+        for _ in 0..peers.len() {
+            let (stream, addr) = tcp_listener.accept().await?;
+            let mut stream = tls_acceptor.accept(stream).await?;
+            let peer_id = stream.read_u32().await?;
+
+            let (expected_addr, expected_cert) =
+                peers.get(&peer_id).ok_or(MpcNetError::Protocol {
+                    err: "Unknown peer id".to_string(),
+                    party: peer_id,
+                })?;
+            let presented_cert = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first());
+            if presented_cert != Some(expected_cert)
+                || addr.ip() != expected_addr.ip()
+            {
+                return Err(MpcNetError::Protocol {
+                    err: "Peer does not match the certificate and address of the id it claims".to_string(),
+                    party: peer_id,
+                });
+            }
+            if tls_conns.contains_key(&peer_id) {
+                return Err(MpcNetError::Protocol {
+                    err: "Peer id connected twice".to_string(),
+                    party: peer_id,
+                });
+            }
+
+            tls_conns.insert(peer_id, TlsStream::Server(stream));
+        }
+
+        ProdNet::new_king_from_identified_connections(
+            0,
+            peers.len() + 1,
+            tls_conns,
+            MuxConfig::default(),
+            &Handle::current(),
+        )
+        .await
     }
 
     pub async fn new_peer_tls<R: CertToDer, V: std::net::ToSocketAddrs>(
@@ -162,12 +401,35 @@ impl ProdNet<TlsStream<TcpStream>> {
         identity: R,
         server_cert: RootCertStore,
         n_parties: usize,
+    ) -> Result<ProdNet<TlsStream<TcpStream>>, MpcNetError> {
+        Self::peer_tls(
+            id,
+            0,
+            king,
+            identity,
+            server_cert,
+            n_parties,
+            MuxConfig::default(),
+        )
+        .await
+    }
+
+    /// new_peer_tls connecting to the king with id king_id, multiplexing the
+    /// connection with mux
+    async fn peer_tls<R: CertToDer, V: std::net::ToSocketAddrs>(
+        id: u32,
+        king_id: u32,
+        king: V,
+        identity: R,
+        server_cert: RootCertStore,
+        n_parties: usize,
+        mux: MuxConfig,
     ) -> Result<ProdNet<TlsStream<TcpStream>>, MpcNetError> {
         let king_addr: SocketAddr =
             king.to_socket_addrs()?
                 .next()
                 .ok_or(MpcNetError::BadInput {
-                    err: "King socket addr invalid",
+                    err: "King socket addr invalid".to_string(),
                 })?;
 
         let stream = TcpStream::connect(king_addr).await?;
@@ -179,69 +441,431 @@ impl ProdNet<TlsStream<TcpStream>> {
                 .await?,
         );
 
-        ProdNet::new_from_pre_existing_connection(id, n_parties, vec![stream])
-            .await
+        ProdNet::new_from_pre_existing_connection_with_king(
+            id,
+            king_id,
+            n_parties,
+            vec![stream],
+            mux,
+            &Handle::current(),
+        )
+        .await
+    }
+
+    /// with_mesh over TLS: binds to the address of this party in `peers`,
+    /// accepts the peers with a lower id and connects to the ones with a
+    /// higher id. Every peer must call it, a peer that isn't listening within
+    /// the round timeout fails the call.
+    /// peers: the address and certificate of every party but the king, this
+    /// one included. As for new_king_tls_with_peers, a peer whose certificate
+    /// or address doesn't match the id it announces is rejected.
+    pub async fn with_mesh_tls<R: CertToDer + Clone>(
+        self,
+        identity: R,
+        peers: HashMap<u32, (SocketAddr, rustls::Certificate)>,
+    ) -> Result<Self, MpcNetError> {
+        if self.is_king() {
+            return self.with_mesh(vec![]).await;
+        }
+        let my_id = self.party_id();
+        let own_addr = match peers.get(&my_id) {
+            Some((addr, _)) if peers.len() == self.n_parties() - 1 => *addr,
+            _ => {
+                return Err(MpcNetError::BadInput {
+                    err: "peers do not describe this network".to_string(),
+                })
+            }
+        };
+        let mut certs = RootCertStore::empty();
+        for (id, (_, cert)) in &peers {
+            if *id != my_id {
+                certs.add(cert)?;
+            }
+        }
+
+        let listener = TcpListener::bind(own_addr).await?;
+        let tls_acceptor =
+            create_server_mutual_tls_acceptor(certs.clone(), identity.clone())?;
+        let tls_connector =
+            create_client_mutual_tls_connector(certs, identity)?;
+        let timeout = self.round_timeout();
+        let mut higher = peers
+            .iter()
+            .filter(|(id, _)| **id > my_id)
+            .map(|(id, (addr, _))| (*id, *addr))
+            .collect::<Vec<_>>();
+        higher.sort();
+        let n_lower = peers.len() - 1 - higher.len();
+
+        let accept = async {
+            let mut tls_conns = vec![];
+            for _ in 0..n_lower {
+                let (stream, _) = listener.accept().await?;
+                tls_conns.push(TlsStream::Server(
+                    tls_acceptor.accept(stream).await?,
+                ));
+            }
+            Ok::<_, MpcNetError>(tls_conns)
+        };
+        let connect = async {
+            let mut tls_conns = vec![];
+            for (_, addr) in &higher {
+                let stream = connect_with_retry(*addr, timeout).await?;
+                tls_conns.push(TlsStream::Client(
+                    tls_connector
+                        .connect(
+                            rustls::ServerName::IpAddress(addr.ip()),
+                            stream,
+                        )
+                        .await?,
+                ));
+            }
+            Ok::<_, MpcNetError>(tls_conns)
+        };
+
+        let (mut tls_conns, connected) = tokio::try_join!(accept, connect)?;
+        tls_conns.extend(connected);
+        self.with_verified_mesh(tls_conns, |peer_id, stream| {
+            let (expected_addr, expected_cert) = &peers[&peer_id];
+            let (tcp, session) = stream.get_ref();
+            let presented_cert =
+                session.peer_certificates().and_then(|certs| certs.first());
+            if presented_cert != Some(expected_cert)
+                || tcp.peer_addr()?.ip() != expected_addr.ip()
+            {
+                return Err(MpcNetError::Protocol {
+                    err: "Peer does not match the certificate and address of the id it claims".to_string(),
+                    party: peer_id,
+                });
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Connects to addr, retrying until it listens or timeout has passed
+async fn connect_with_retry(
+    addr: SocketAddr,
+    timeout: Duration,
+) -> Result<TcpStream, MpcNetError> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) if tokio::time::Instant::now() >= deadline => {
+                return Err(err.into())
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
     }
 }
 
 impl<T: IOStream> ProdNet<T> {
     /// Must pass a list of connections to all the peers if king, otherwise a single connection
     /// if a peer
+    /// Every peer announces its id on its connection, and the ids must be unique:
+    /// the king rejects a connection claiming an id already taken, its own included,
+    /// since the shares of two parties with the same id would overwrite each other.
     pub async fn new_from_pre_existing_connection(
         id: u32,
         n_parties: usize,
+        ios: Vec<T>,
+    ) -> Result<Self, MpcNetError> {
+        Self::new_from_pre_existing_connection_with_mux(
+            id,
+            n_parties,
+            ios,
+            MuxConfig::default(),
+        )
+        .await
+    }
+
+    /// new_from_pre_existing_connection with a tuned multiplexer, see MuxConfig
+    pub async fn new_from_pre_existing_connection_with_mux(
+        id: u32,
+        n_parties: usize,
+        ios: Vec<T>,
+        mux: MuxConfig,
+    ) -> Result<Self, MpcNetError> {
+        Self::new_from_pre_existing_connection_on(
+            id,
+            n_parties,
+            ios,
+            mux,
+            &Handle::current(),
+        )
+        .await
+    }
+
+    /// new_from_pre_existing_connection_with_mux, spawning the smux workers
+    /// on `runtime`. Lets an application with its own executor construct the
+    /// net outside of a tokio context, the connections are driven by runtime.
+    pub async fn new_from_pre_existing_connection_on(
+        id: u32,
+        n_parties: usize,
+        ios: Vec<T>,
+        mux: MuxConfig,
+        runtime: &Handle,
+    ) -> Result<Self, MpcNetError> {
+        Self::new_from_pre_existing_connection_with_king(
+            id, 0, n_parties, ios, mux, runtime,
+        )
+        .await
+    }
+
+    /// new_from_pre_existing_connection_on where the king is the party
+    /// king_id rather than 0. The king passes the connections to all the
+    /// other parties, a peer the one to king_id.
+    pub async fn new_from_pre_existing_connection_with_king(
+        id: u32,
+        king_id: u32,
+        n_parties: usize,
         mut ios: Vec<T>,
+        mux: MuxConfig,
+        runtime: &Handle,
     ) -> Result<Self, MpcNetError> {
-        if id != 0 && ios.len() != 1 {
+        if king_id as usize >= n_parties {
+            return Err(MpcNetError::BadInput {
+                err: format!(
+                    "King id {} out of range for {} parties",
+                    king_id, n_parties
+                ),
+            });
+        }
+
+        if id != king_id && ios.len() != 1 {
             return Err(MpcNetError::BadInput {
-                err: "Must pass a single connection to the king if you are a peer",
+                err: "Must pass a single connection to the king if you are a peer"
+                    .to_string(),
             });
         }
 
+        if id == king_id {
+            let mut identified = HashMap::new();
+            for mut stream in ios.into_iter() {
+                let peer_id = stream.read_u32().await?;
+                if peer_id == king_id || identified.contains_key(&peer_id) {
+                    return Err(MpcNetError::Protocol {
+                        err: "Peer id connected twice".to_string(),
+                        party: peer_id,
+                    });
+                }
+                identified.insert(peer_id, stream);
+            }
+            return Self::new_king_from_identified_connections(
+                king_id, n_parties, identified, mux, runtime,
+            )
+            .await;
+        }
+
+        let mut stream = ios.pop().expect("Should exist");
+        let oeer_addr = stream.peer_addr()?;
+        stream.write_u32(id).await?;
+        let (muxed, worker) = multiplex_stream_on(
+            MULTIPLEXED_STREAMS,
+            false,
+            stream,
+            mux,
+            runtime,
+        )
+        .await?;
+
         let mut connections = MpcNetConnection {
             id,
             listener: None,
             peers: Default::default(),
             n_parties,
+            job_id: JobId::default(),
+            king_id,
+            dedicated_king: false,
+            timeout: AdaptiveTimeout::default(),
         };
+        connections.peers.insert(
+            king_id,
+            Peer {
+                id: king_id,
+                listen_addr: oeer_addr,
+                streams: Some(muxed),
+                worker: Some(worker),
+            },
+        );
 
-        if id == 0 {
-            for mut stream in ios.into_iter() {
-                let peer_id = stream.read_u32().await?;
-                let peer_addr = stream.peer_addr()?;
-                let muxed =
-                    multiplex_stream(MULTIPLEXED_STREAMS, true, stream).await?;
-                connections.peers.insert(
-                    peer_id,
-                    Peer {
-                        id: peer_id,
-                        listen_addr: peer_addr,
-                        streams: Some(muxed),
-                    },
-                );
-            }
-        } else {
-            let mut stream = ios.pop().expect("Should exist");
-            let oeer_addr = stream.peer_addr()?;
-            stream.write_u32(id).await?;
-            let muxed =
-                multiplex_stream(MULTIPLEXED_STREAMS, false, stream).await?;
+        let this = Self { connections, mux };
+        this.synchronize().await?;
+
+        Ok(this)
+    }
+
+    /// King side of new_from_pre_existing_connection, once every
+    /// connection is known to belong to the given peer id
+    async fn new_king_from_identified_connections(
+        king_id: u32,
+        n_parties: usize,
+        ios: HashMap<u32, T>,
+        mux: MuxConfig,
+        runtime: &Handle,
+    ) -> Result<Self, MpcNetError> {
+        let mut connections = MpcNetConnection {
+            id: king_id,
+            listener: None,
+            peers: Default::default(),
+            n_parties,
+            job_id: JobId::default(),
+            king_id,
+            dedicated_king: false,
+            timeout: AdaptiveTimeout::default(),
+        };
+
+        for (peer_id, stream) in ios {
+            let peer_addr = stream.peer_addr()?;
+            let (muxed, worker) = multiplex_stream_on(
+                MULTIPLEXED_STREAMS,
+                true,
+                stream,
+                mux,
+                runtime,
+            )
+            .await?;
             connections.peers.insert(
-                0,
+                peer_id,
                 Peer {
-                    id: 0,
-                    listen_addr: oeer_addr,
+                    id: peer_id,
+                    listen_addr: peer_addr,
                     streams: Some(muxed),
+                    worker: Some(worker),
                 },
             );
         }
 
-        let this = Self { connections };
+        let this = Self { connections, mux };
         this.synchronize().await?;
 
         Ok(this)
     }
 
+    /// Tunes the king's round timeout, see AdaptiveTimeout.
+    /// Forgets the rounds measured so far.
+    pub fn with_timeout_config(mut self, config: TimeoutConfig) -> Self {
+        self.connections.timeout = AdaptiveTimeout::new(config);
+        self
+    }
+
+    /// Connects this peer directly to the other peers, one connection in ios
+    /// per peer, so that send_to and recv_from work between any two parties
+    /// rather than only with the king. The king is connected to everyone
+    /// already and passes no connection.
+    /// Opt-in, since a full mesh costs every peer n - 1 connections with their
+    /// smux workers and buffers instead of one, (n - 1)(n - 2) / 2 more in the
+    /// whole network. Protocols that only talk to the king don't need it.
+    pub async fn with_mesh(self, ios: Vec<T>) -> Result<Self, MpcNetError> {
+        self.with_verified_mesh(ios, |_, _| Ok(())).await
+    }
+
+    /// with_mesh where `verify` checks that a connection belongs to the
+    /// peer id announced on it
+    async fn with_verified_mesh(
+        mut self,
+        mut ios: Vec<T>,
+        verify: impl Fn(u32, &T) -> Result<(), MpcNetError>,
+    ) -> Result<Self, MpcNetError> {
+        let my_id = self.party_id();
+        let n_others = if self.is_king() {
+            0
+        } else {
+            self.n_parties() - 2
+        };
+        if ios.len() != n_others {
+            return Err(MpcNetError::BadInput {
+                err: format!(
+                    "Expected a connection to each of the {} other peers, got {}",
+                    n_others,
+                    ios.len()
+                ),
+            });
+        }
+
+        // announce our id on every connection before reading any, so that
+        // two peers never wait for each other's id
+        for stream in ios.iter_mut() {
+            stream.write_u32(my_id).await?;
+        }
+        for mut stream in ios {
+            let peer_id = stream.read_u32().await?;
+            if peer_id == my_id
+                || peer_id == self.king_id()
+                || peer_id as usize >= self.n_parties()
+                || self.connections.peers.contains_key(&peer_id)
+            {
+                return Err(MpcNetError::Protocol {
+                    err: "Unexpected peer id on a mesh connection".to_string(),
+                    party: peer_id,
+                });
+            }
+            verify(peer_id, &stream)?;
+            let peer_addr = stream.peer_addr()?;
+            let (muxed, worker) = multiplex_stream_on(
+                MULTIPLEXED_STREAMS,
+                peer_id < my_id,
+                stream,
+                self.mux,
+                &Handle::current(),
+            )
+            .await?;
+            self.connections.peers.insert(
+                peer_id,
+                Peer {
+                    id: peer_id,
+                    listen_addr: peer_addr,
+                    streams: Some(muxed),
+                    worker: Some(worker),
+                },
+            );
+        }
+
+        Ok(self)
+    }
+
+    /// Flushes and closes the connections and stops their smux workers, see
+    /// MpcNetConnection::shutdown
+    pub async fn shutdown(self) -> Result<(), MpcNetError> {
+        self.connections.shutdown().await
+    }
+
+    /// Switches the connections to `job_id`, frames of other jobs are
+    /// rejected from now on. All parties must switch to the same id since
+    /// they synchronize under it before returning.
+    pub async fn with_job_id(
+        mut self,
+        job_id: JobId,
+    ) -> Result<Self, MpcNetError> {
+        self.connections.job_id = job_id;
+        self.synchronize().await?;
+        Ok(self)
+    }
+
+    /// Runs `handler` on every job of `jobs` over the already established
+    /// connections, so that a long lived prover doesn't reconnect per proof.
+    /// All parties must be fed the same jobs in the same order. Jobs run one
+    /// at a time and the parties synchronize with the king before each one.
+    pub async fn run_jobs<'a, J, K, Fut>(
+        &'a self,
+        jobs: impl Stream<Item = J>,
+        mut handler: impl FnMut(&'a Self, J) -> Fut,
+    ) -> Result<Vec<K>, MpcNetError>
+    where
+        Fut: Future<Output = Result<K, MpcNetError>> + 'a,
+    {
+        let mut results = Vec::new();
+        futures::pin_mut!(jobs);
+        while let Some(job) = jobs.next().await {
+            self.synchronize().await?;
+            results.push(handler(self, job).await?);
+        }
+
+        Ok(results)
+    }
+
     /// Ensure all peers are connected to the king
     async fn synchronize(&self) -> Result<(), MpcNetError> {
         if self.is_king() {
@@ -249,6 +873,7 @@ impl<T: IOStream> ProdNet<T> {
             for conn in self.connections.peers.values() {
                 send_packet(
                     conn.streams.as_ref(),
+                    &self.connections.job_id,
                     MultiplexedStreamID::Zero,
                     ProtocolPacket::Syn,
                 )
@@ -259,6 +884,8 @@ impl<T: IOStream> ProdNet<T> {
             for conn in self.connections.peers.values() {
                 let packet = recv_packet(
                     conn.streams.as_ref(),
+                    &self.connections.job_id,
+                    conn.id,
                     MultiplexedStreamID::Zero,
                 )
                 .await?;
@@ -270,22 +897,27 @@ impl<T: IOStream> ProdNet<T> {
                 }
             }
         } else {
+            let king_id = self.king_id();
+            let king = self.connections.peers.get(&king_id).unwrap();
             // Wait for a Syn packet
             let packet = recv_packet(
-                self.connections.peers.get(&0).unwrap().streams.as_ref(),
+                king.streams.as_ref(),
+                &self.connections.job_id,
+                king_id,
                 MultiplexedStreamID::Zero,
             )
             .await?;
             if packet != ProtocolPacket::Syn {
                 return Err(MpcNetError::Protocol {
                     err: "Did not receive Syn".to_string(),
-                    party: 0,
+                    party: king_id,
                 });
             }
 
-            // Send a SynAck packet to party_id=0
+            // Send a SynAck packet to the king
             send_packet(
-                self.connections.peers.get(&0).unwrap().streams.as_ref(),
+                king.streams.as_ref(),
+                &self.connections.job_id,
                 MultiplexedStreamID::Zero,
                 ProtocolPacket::SynAck,
             )
@@ -306,10 +938,26 @@ impl<T: IOStream> MpcNet for ProdNet<T> {
         self.connections.party_id()
     }
 
+    fn king_id(&self) -> u32 {
+        self.connections.king_id()
+    }
+
+    fn party_ids(&self) -> Box<dyn Iterator<Item = u32> + Send + '_> {
+        self.connections.party_ids()
+    }
+
     fn is_init(&self) -> bool {
         self.connections.is_init()
     }
 
+    fn round_timeout(&self) -> Duration {
+        self.connections.round_timeout()
+    }
+
+    fn record_round_time(&self, elapsed: Duration) {
+        self.connections.record_round_time(elapsed)
+    }
+
     async fn recv_from(
         &self,
         id: u32,
@@ -319,7 +967,7 @@ impl<T: IOStream> MpcNet for ProdNet<T> {
             MpcNetError::Generic(format!("Peer {} not found", id))
         })?;
 
-        recv_packet(peer.streams.as_ref(), sid)
+        recv_packet(peer.streams.as_ref(), &self.connections.job_id, id, sid)
             .await
             .map(|r| match r {
                 ProtocolPacket::Packet(packet) => Ok(Bytes::from(packet)),
@@ -342,6 +990,7 @@ impl<T: IOStream> MpcNet for ProdNet<T> {
 
         send_packet(
             peer.streams.as_ref(),
+            &self.connections.job_id,
             sid,
             ProtocolPacket::Packet(bytes.to_vec()),
         )
@@ -351,30 +1000,28 @@ impl<T: IOStream> MpcNet for ProdNet<T> {
 
 async fn send_packet<T: IOStream>(
     streams: Option<&Vec<Mutex<WrappedMuxStream<T>>>>,
+    job_id: &JobId,
     sid: MultiplexedStreamID,
     packet: ProtocolPacket,
 ) -> Result<(), MpcNetError> {
     let stream = streams.ok_or(MpcNetError::NotConnected)?;
     let stream = stream.get(sid as usize).ok_or(MpcNetError::NotConnected)?;
-    let packet = bincode2::serialize(&packet)?;
-    stream.lock().await.send(Bytes::from(packet)).await?;
-    Ok(())
+    let frame = frame_with_job_id(job_id, &packet.encode());
+    send_chunked(&mut *stream.lock().await, frame).await
 }
 
+/// Receives a packet from `party`, rejecting packets of other jobs
 async fn recv_packet<T: IOStream>(
     streams: Option<&Vec<Mutex<WrappedMuxStream<T>>>>,
+    job_id: &JobId,
+    party: u32,
     sid: MultiplexedStreamID,
 ) -> Result<ProtocolPacket, MpcNetError> {
     let stream = streams.ok_or(MpcNetError::NotConnected)?;
     let stream = stream.get(sid as usize).ok_or(MpcNetError::NotConnected)?;
-    let packet = stream
-        .lock()
-        .await
-        .next()
-        .await
-        .ok_or(MpcNetError::NotConnected)??;
-    let packet = bincode2::deserialize(&packet)?;
-    Ok(packet)
+    let packet = recv_chunked(&mut *stream.lock().await).await?;
+    let packet = strip_job_id(job_id, packet, party)?;
+    ProtocolPacket::decode(&packet, party)
 }
 
 #[cfg(test)]
@@ -407,6 +1054,16 @@ mod test {
         rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()])
     }
 
+    fn generate_rustls_identity() -> RustlsCertificate {
+        let identity = generate_self_signed_cert().unwrap();
+        RustlsCertificate {
+            cert: rustls::Certificate(identity.serialize_der().unwrap()),
+            private_key: rustls::PrivateKey(
+                identity.serialize_private_key_der(),
+            ),
+        }
+    }
+
     struct LocalTestNetProd<T: IOStream> {
         nodes: Vec<ProdNet<T>>,
     }
@@ -491,6 +1148,178 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_protocol_packet_versioning() {
+        for packet in [
+            ProtocolPacket::Syn,
+            ProtocolPacket::SynAck,
+            ProtocolPacket::Packet(vec![1, 2, 3]),
+        ] {
+            let bytes = packet.encode();
+            assert_eq!(ProtocolPacket::decode(&bytes, 1).unwrap(), packet);
+        }
+
+        let mut bytes = ProtocolPacket::Syn.encode();
+        bytes[0] = PROTOCOL_VERSION + 1;
+        assert!(matches!(
+            ProtocolPacket::decode(&bytes, 1),
+            Err(MpcNetError::Protocol { party: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_root_cert_store_from_certs() {
+        let clients = (0..2)
+            .map(|_| generate_self_signed_cert().unwrap())
+            .collect::<Vec<_>>();
+        let store = root_cert_store_from_certs(&clients).unwrap();
+        assert_eq!(store.len(), 2);
+    }
+
+    /// Connects peers presenting client_identities[i] and claiming claimed_ids[i]
+    /// to a king expecting peer i + 1 to use client_identities[i]
+    async fn init_king_with_peers(
+        claimed_ids: Vec<u32>,
+    ) -> Result<ProdNet<TlsStream<TcpStream>>, MpcNetError> {
+        let n_peers = claimed_ids.len();
+        let king_addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server_identity = generate_rustls_identity();
+        let mut server_cert = RootCertStore::empty();
+        server_cert.add(&server_identity.cert).unwrap();
+
+        let client_identities = (0..n_peers)
+            .map(|_| generate_rustls_identity())
+            .collect::<Vec<_>>();
+        let expected = client_identities
+            .iter()
+            .enumerate()
+            .map(|(i, identity)| {
+                ((i + 1) as u32, (king_addr, identity.cert.clone()))
+            })
+            .collect();
+
+        let king = tokio::spawn(ProdNet::new_king_tls_with_peers(
+            king_addr,
+            server_identity,
+            expected,
+        ));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let peers = client_identities
+            .into_iter()
+            .zip(claimed_ids)
+            .map(|(identity, id)| {
+                tokio::spawn(ProdNet::new_peer_tls(
+                    id,
+                    king_addr,
+                    identity,
+                    server_cert.clone(),
+                    n_peers + 1,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let king = king.await.unwrap();
+        peers.iter().for_each(|peer| peer.abort());
+        king
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_king_binds_peer_ids_to_certs() {
+        assert!(init_king_with_peers(vec![1, 2, 3]).await.is_ok());
+
+        // the first peer claims the id of the second one
+        assert!(matches!(
+            init_king_with_peers(vec![2, 2, 3]).await,
+            Err(MpcNetError::Protocol { party: 2, .. })
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_from_config() {
+        const N_PARTIES: usize = 4;
+        let king_addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let identities = (0..N_PARTIES)
+            .map(|_| generate_rustls_identity())
+            .collect::<Vec<_>>();
+        let config = NetworkConfig {
+            id: 0,
+            king_id: 0,
+            l: 1,
+            t: 1,
+            peers: vec![king_addr; N_PARTIES],
+            mux: MuxConfig {
+                max_tx_queue: std::num::NonZeroUsize::new(16).unwrap(),
+                max_rx_queue: std::num::NonZeroUsize::new(16).unwrap(),
+            },
+        };
+
+        let mut king_cert = RootCertStore::empty();
+        king_cert.add(&identities[0].cert).unwrap();
+        let client_certs =
+            root_cert_store_from_certs(&identities[1..]).unwrap();
+
+        // a network of the wrong size for t and l is rejected
+        assert!(matches!(
+            ProdNet::from_config(
+                &NetworkConfig {
+                    l: 3,
+                    ..config.clone()
+                },
+                identities[0].clone(),
+                client_certs.clone(),
+            )
+            .await,
+            Err(MpcNetError::BadInput { .. })
+        ));
+
+        // a party id outside of the network is rejected
+        assert!(matches!(
+            ProdNet::from_config(
+                &NetworkConfig {
+                    id: N_PARTIES as u32,
+                    ..config.clone()
+                },
+                identities[1].clone(),
+                king_cert.clone(),
+            )
+            .await,
+            Err(MpcNetError::BadInput { .. })
+        ));
+
+        let nets = identities.into_iter().enumerate().map(|(id, identity)| {
+            let config = NetworkConfig {
+                id: id as u32,
+                ..config.clone()
+            };
+            let certs = if id == 0 {
+                client_certs.clone()
+            } else {
+                king_cert.clone()
+            };
+            tokio::spawn(async move {
+                if id != 0 {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+                ProdNet::from_config(&config, identity, certs).await
+            })
+        });
+
+        for (id, net) in nets.collect::<Vec<_>>().into_iter().enumerate() {
+            let net = net.await.unwrap().unwrap();
+            assert_eq!(net.party_id(), id as u32);
+            assert_eq!(net.n_parties(), N_PARTIES);
+        }
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_network_init() {
         let _ = init_network(3).await;
@@ -514,6 +1343,67 @@ mod test {
         add_protocol_inner(testnet, expected_result, N_PEERS).await;
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_run_jobs_reuses_connections() {
+        const N_PEERS: usize = 4;
+        const N_JOBS: u32 = 3;
+        let nodes = init_network_channels(N_PEERS).await;
+        let testnet = LocalTestNetProd { nodes };
+
+        let results = testnet
+            .simulate_network_round(move |net| async move {
+                let jobs = futures::stream::iter(0..N_JOBS);
+                net.run_jobs(jobs, |net, job| async move {
+                    let sid = MultiplexedStreamID::One;
+                    let king_answer = net
+                        .client_send_or_king_receive_serialized(
+                            &(net.party_id() + job),
+                            sid,
+                            0,
+                        )
+                        .await?
+                        .map(|rs| {
+                            vec![rs.shares.iter().sum::<u32>(); N_PEERS + 1]
+                        });
+                    net.client_receive_or_king_send_serialized(king_answer, sid)
+                        .await
+                        .map(Option::unwrap)
+                })
+                .await
+                .unwrap()
+            })
+            .await;
+
+        let id_sum: u32 = (0..=N_PEERS).map(|r| r as u32).sum();
+        let expected = (0..N_JOBS)
+            .map(|job| id_sum + job * (N_PEERS as u32 + 1))
+            .collect::<Vec<_>>();
+        assert!(results.iter().all(|r| *r == expected));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_mismatched_job_id_is_rejected() {
+        const N_PEERS: usize = 2;
+        let nodes = init_network_channels(N_PEERS).await;
+        let testnet = LocalTestNetProd { nodes };
+
+        let results = testnet
+            .simulate_network_round(move |net| async move {
+                let mut job_id = [7u8; 32];
+                if net.is_king() {
+                    job_id[0] = 0;
+                }
+                net.with_job_id(job_id).await.err()
+            })
+            .await;
+
+        // the peers reject the Syn of the king, which then can't synchronize
+        assert!(results.iter().all(|r| r.is_some()));
+        for r in &results[..N_PEERS] {
+            assert!(matches!(r, Some(MpcNetError::Protocol { party: 0, .. })));
+        }
+    }
+
     async fn add_protocol_inner<T: IOStream>(
         testnet: LocalTestNetProd<T>,
         expected_result: u32,
@@ -578,32 +1468,16 @@ mod test {
             .unwrap()
             .local_addr()
             .unwrap();
-        let server_identity = generate_self_signed_cert().unwrap();
-        let server_identity = RustlsCertificate {
-            cert: rustls::Certificate(server_identity.serialize_der().unwrap()),
-            private_key: rustls::PrivateKey(
-                server_identity.serialize_private_key_der(),
-            ),
-        };
+        let server_identity = generate_rustls_identity();
 
         let mut server_cert = RootCertStore::empty();
         server_cert.add(&server_identity.cert).unwrap();
 
-        let mut client_certs = RootCertStore::empty();
-        let mut client_identities = Vec::new();
-        for _ in 0..n_peers {
-            let peer_identity = generate_self_signed_cert().unwrap();
-            let peer_identity = RustlsCertificate {
-                cert: rustls::Certificate(
-                    peer_identity.serialize_der().unwrap(),
-                ),
-                private_key: rustls::PrivateKey(
-                    peer_identity.serialize_private_key_der(),
-                ),
-            };
-            client_certs.add(&peer_identity.cert).unwrap();
-            client_identities.push(peer_identity);
-        }
+        let client_identities = (0..n_peers)
+            .map(|_| generate_rustls_identity())
+            .collect::<Vec<_>>();
+        let client_certs =
+            root_cert_store_from_certs(&client_identities).unwrap();
 
         let king = tokio::spawn(ProdNet::<TlsStream<TcpStream>>::new_king_tls(
             king_addr,
@@ -633,6 +1507,267 @@ mod test {
         r_clients
     }
 
+    #[tokio::test]
+    async fn test_king_rejects_duplicate_peer_id() {
+        let ios = (0..2)
+            .map(|_| {
+                let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+                // both peers claim to be party 1
+                tx.send(1u32.to_be_bytes().to_vec()).unwrap();
+                let (tx, _) = tokio::sync::mpsc::unbounded_channel();
+                ChannelIO { tx, rx }
+            })
+            .collect();
+
+        assert!(matches!(
+            ProdNet::new_from_pre_existing_connection(0, 3, ios).await,
+            Err(MpcNetError::Protocol { party: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_construct_on_runtime_handle() {
+        // no tokio context here, the smux workers must go to runtime
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (to_peer, from_king) = tokio::sync::mpsc::unbounded_channel();
+        let (to_king, from_peer) = tokio::sync::mpsc::unbounded_channel();
+        let king_io = ChannelIO {
+            tx: to_peer,
+            rx: from_peer,
+        };
+        let peer_io = ChannelIO {
+            tx: to_king,
+            rx: from_king,
+        };
+
+        let (king, peer) = futures::executor::block_on(futures::future::join(
+            ProdNet::new_from_pre_existing_connection_on(
+                0,
+                2,
+                vec![king_io],
+                MuxConfig::default(),
+                runtime.handle(),
+            ),
+            ProdNet::new_from_pre_existing_connection_on(
+                1,
+                2,
+                vec![peer_io],
+                MuxConfig::default(),
+                runtime.handle(),
+            ),
+        ));
+        assert!(king.unwrap().is_king());
+        assert_eq!(peer.unwrap().party_id(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_king_other_than_zero() {
+        let (to_peer, from_king) = tokio::sync::mpsc::unbounded_channel();
+        let (to_king, from_peer) = tokio::sync::mpsc::unbounded_channel();
+        let king_io = ChannelIO {
+            tx: to_peer,
+            rx: from_peer,
+        };
+        let peer_io = ChannelIO {
+            tx: to_king,
+            rx: from_king,
+        };
+
+        // party 1 is the king, the constructors synchronize with it
+        let runtime = Handle::current();
+        let (king, peer) = tokio::try_join!(
+            ProdNet::new_from_pre_existing_connection_with_king(
+                1,
+                1,
+                2,
+                vec![king_io],
+                MuxConfig::default(),
+                &runtime,
+            ),
+            ProdNet::new_from_pre_existing_connection_with_king(
+                0,
+                1,
+                2,
+                vec![peer_io],
+                MuxConfig::default(),
+                &runtime,
+            ),
+        )
+        .unwrap();
+        assert!(king.is_king() && !peer.is_king());
+        assert_eq!(peer.king_id(), 1);
+
+        peer.send_to(1, Bytes::from_static(b"hi"), MultiplexedStreamID::One)
+            .await
+            .unwrap();
+        let bytes = king.recv_from(0, MultiplexedStreamID::One).await.unwrap();
+        assert_eq!(bytes.as_ref(), b"hi");
+    }
+
+    /// Every peer sends its id to every other peer, bypassing the king
+    async fn exchange_ids_between_peers<T: IOStream>(nets: Vec<ProdNet<T>>) {
+        LocalTestNetProd { nodes: nets }
+            .simulate_network_round(|net| async move {
+                let my_id = net.party_id();
+                let others = net
+                    .party_ids()
+                    .filter(|id| *id != my_id && *id != net.king_id())
+                    .collect::<Vec<_>>();
+                if net.is_king() {
+                    return;
+                }
+                for id in &others {
+                    let bytes = bincode2::serialize(&my_id).unwrap().into();
+                    net.send_to(*id, bytes, MultiplexedStreamID::One)
+                        .await
+                        .unwrap();
+                }
+                let mut received = vec![];
+                for id in &others {
+                    let bytes = net
+                        .recv_from(*id, MultiplexedStreamID::One)
+                        .await
+                        .unwrap();
+                    received
+                        .push(bincode2::deserialize::<u32>(&bytes).unwrap());
+                }
+                assert_eq!(received, others);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_mesh_over_pre_existing_connections() {
+        const N_PEERS: usize = 3;
+        let mut nodes = init_network_channels(N_PEERS).await;
+        nodes.sort_by_key(|net| net.party_id());
+
+        // the king is connected to everyone already
+        let (to_peer, _) = tokio::sync::mpsc::unbounded_channel();
+        let (_, from_peer) = tokio::sync::mpsc::unbounded_channel();
+        let king = nodes.remove(0);
+        assert!(matches!(
+            king.with_mesh(vec![ChannelIO {
+                tx: to_peer,
+                rx: from_peer
+            }])
+            .await,
+            Err(MpcNetError::BadInput { .. })
+        ));
+
+        let mut ios = (0..=N_PEERS).map(|_| vec![]).collect::<Vec<_>>();
+        for i in 1..=N_PEERS {
+            for j in i + 1..=N_PEERS {
+                let (to_j, from_i) = tokio::sync::mpsc::unbounded_channel();
+                let (to_i, from_j) = tokio::sync::mpsc::unbounded_channel();
+                ios[i].push(ChannelIO {
+                    tx: to_j,
+                    rx: from_j,
+                });
+                ios[j].push(ChannelIO {
+                    tx: to_i,
+                    rx: from_i,
+                });
+            }
+        }
+
+        let mut nets = init_network_channels(N_PEERS).await;
+        nets.sort_by_key(|net| net.party_id());
+        let nets = futures::future::try_join_all(
+            nets.into_iter()
+                .zip(ios)
+                .map(|(net, ios)| net.with_mesh(ios)),
+        )
+        .await
+        .unwrap();
+        exchange_ids_between_peers(nets).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_mesh_tls() {
+        let nets = mesh_tls(None).await;
+        exchange_ids_between_peers(
+            nets.into_iter().map(Result::unwrap).collect(),
+        )
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_mesh_tls_rejects_peer_with_wrong_cert() {
+        const MISLED: u32 = 1;
+        let nets = mesh_tls(Some(MISLED)).await;
+        assert!(matches!(
+            nets[MISLED as usize],
+            Err(MpcNetError::Protocol { .. })
+        ));
+    }
+
+    /// Sets up a TLS mesh of 4 parties. If given, party `misled` expects
+    /// parties 2 and 3 to present each other's certificate.
+    async fn mesh_tls(
+        misled: Option<u32>,
+    ) -> Vec<Result<ProdNet<TlsStream<TcpStream>>, MpcNetError>> {
+        const N_PARTIES: usize = 4;
+        let mut peers = vec![];
+        for _ in 0..N_PARTIES {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            peers.push(listener.local_addr().unwrap());
+        }
+        let config = NetworkConfig {
+            id: 0,
+            king_id: 0,
+            l: 1,
+            t: 1,
+            peers,
+            mux: MuxConfig::default(),
+        };
+        let identities = (0..N_PARTIES)
+            .map(|_| generate_rustls_identity())
+            .collect::<Vec<_>>();
+        let mut king_cert = RootCertStore::empty();
+        king_cert.add(&identities[0].cert).unwrap();
+        let peer_certs = root_cert_store_from_certs(&identities[1..]).unwrap();
+        let mesh_peers = (1..N_PARTIES)
+            .map(|id| {
+                (id as u32, (config.peers[id], identities[id].cert.clone()))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let nets = identities.into_iter().enumerate().map(|(id, identity)| {
+            let config = NetworkConfig {
+                id: id as u32,
+                ..config.clone()
+            };
+            let (king_cert, peer_certs) =
+                (king_cert.clone(), peer_certs.clone());
+            let mut mesh_peers = mesh_peers.clone();
+            if misled == Some(id as u32) {
+                let cert_2 = mesh_peers[&2].1.clone();
+                let cert_3 = std::mem::replace(
+                    &mut mesh_peers.get_mut(&3).unwrap().1,
+                    cert_2,
+                );
+                mesh_peers.get_mut(&2).unwrap().1 = cert_3;
+            }
+            tokio::spawn(async move {
+                let net = if id == 0 {
+                    ProdNet::new_king_tls(
+                        config.peers[0],
+                        identity.clone(),
+                        peer_certs.clone(),
+                    )
+                    .await
+                } else {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    ProdNet::from_config(&config, identity.clone(), king_cert)
+                        .await
+                };
+                net.unwrap().with_mesh_tls(identity, mesh_peers).await
+            })
+        });
+        futures::future::try_join_all(nets).await.unwrap()
+    }
+
     async fn init_network_channels(n_peers: usize) -> Vec<ProdNet<ChannelIO>> {
         let n_parties = n_peers + 1;
         let mut king_conns = vec![];
@@ -0,0 +1,173 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio_util::bytes::Bytes;
+
+use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+
+/// A single message observed by a [`RecordingNet`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MessageRecord {
+    pub from: u32,
+    pub to: u32,
+    pub sid: MultiplexedStreamID,
+    pub len: usize,
+    /// Hash of the payload, stable across runs of the same binary.
+    pub bytes_hash: u64,
+}
+
+impl MessageRecord {
+    fn new(from: u32, to: u32, sid: MultiplexedStreamID, bytes: &[u8]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        Self {
+            from,
+            to,
+            sid,
+            len: bytes.len(),
+            bytes_hash: hasher.finish(),
+        }
+    }
+}
+
+/// Log of messages, in the order they were observed.
+/// Can be shared between several parties to get a global trace.
+pub type MessageLog = Arc<Mutex<Vec<MessageRecord>>>;
+
+/// Wraps a network and records every message sent or received through it.
+/// Intended for debugging only, e.g diffing the payloads the king sends
+/// back across two runs of a protocol.
+pub struct RecordingNet<N: MpcNet> {
+    inner: N,
+    log: MessageLog,
+}
+
+impl<N: MpcNet> RecordingNet<N> {
+    pub fn new(inner: N) -> Self {
+        Self::with_log(inner, MessageLog::default())
+    }
+
+    /// Records into an existing log, useful to interleave all parties
+    /// of a local simulation into a single trace.
+    pub fn with_log(inner: N, log: MessageLog) -> Self {
+        Self { inner, log }
+    }
+
+    pub fn log(&self) -> MessageLog {
+        self.log.clone()
+    }
+
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<N: MpcNet> MpcNet for RecordingNet<N> {
+    fn n_parties(&self) -> usize {
+        self.inner.n_parties()
+    }
+
+    fn party_id(&self) -> u32 {
+        self.inner.party_id()
+    }
+
+    fn king_id(&self) -> u32 {
+        self.inner.king_id()
+    }
+
+    fn party_ids(&self) -> Box<dyn Iterator<Item = u32> + Send + '_> {
+        self.inner.party_ids()
+    }
+
+    fn is_init(&self) -> bool {
+        self.inner.is_init()
+    }
+
+    fn round_timeout(&self) -> Duration {
+        self.inner.round_timeout()
+    }
+
+    fn record_round_time(&self, elapsed: Duration) {
+        self.inner.record_round_time(elapsed)
+    }
+
+    async fn recv_from(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MpcNetError> {
+        let bytes = self.inner.recv_from(id, sid).await?;
+        self.log.lock().push(MessageRecord::new(
+            id,
+            self.party_id(),
+            sid,
+            &bytes,
+        ));
+        Ok(bytes)
+    }
+
+    async fn send_to(
+        &self,
+        id: u32,
+        bytes: Bytes,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MpcNetError> {
+        let record = MessageRecord::new(self.party_id(), id, sid, &bytes);
+        self.inner.send_to(id, bytes, sid).await?;
+        self.log.lock().push(record);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::recording::{MessageLog, RecordingNet};
+    use crate::ser_net::MpcSerNet;
+    use crate::{LocalTestNet, MpcNet, MultiplexedStreamID};
+
+    #[tokio::test]
+    async fn records_king_round() {
+        const N_PARTIES: usize = 4;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        let log = MessageLog::default();
+
+        testnet
+            .simulate_network_round(log.clone(), |conn, log| async move {
+                let net = RecordingNet::with_log(conn, log);
+                let sid = MultiplexedStreamID::One;
+                let king_answer = net
+                    .client_send_or_king_receive_serialized(
+                        &net.party_id(),
+                        sid,
+                        0,
+                    )
+                    .await
+                    .unwrap()
+                    .map(|rs| vec![rs.shares.iter().sum::<u32>(); N_PARTIES]);
+                net.client_receive_or_king_send_serialized(king_answer, sid)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            })
+            .await;
+
+        let log = log.lock();
+        // every client sends to and receives from the king, both ends record
+        assert_eq!(log.len(), 4 * (N_PARTIES - 1));
+        for from in 1..N_PARTIES as u32 {
+            let sent = log.iter().find(|r| r.from == from && r.to == 0);
+            let received = log.iter().find(|r| r.from == 0 && r.to == from);
+            assert!(sent.is_some() && received.is_some());
+        }
+        // the king sends the same answer to everyone
+        let answers = log.iter().filter(|r| r.from == 0).collect::<Vec<_>>();
+        assert!(answers
+            .iter()
+            .all(|r| r.bytes_hash == answers[0].bytes_hash));
+    }
+}
@@ -3,12 +3,18 @@ use crate::{
 };
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use async_trait::async_trait;
+use log::warn;
 use std::time::Duration;
 
+/// The shares the king received in a round. Parties are given by the index
+/// of their share (see MpcNet::party_index), as unpack_missing_shares expects.
 #[derive(Clone)]
 pub struct ReceivedShares<T: Clone> {
     pub shares: Vec<T>,
     pub parties: Vec<u32>,
+    /// Parties whose share arrived but could not be deserialized.
+    /// Parties in neither `parties` nor `faulty` timed out
+    pub faulty: Vec<u32>,
 }
 
 #[async_trait]
@@ -21,8 +27,7 @@ pub trait MpcSerNet: MpcNet {
         sid: MultiplexedStreamID,
         threshold: usize,
     ) -> Result<Option<ReceivedShares<T>>, MpcNetError> {
-        let mut bytes_out = Vec::new();
-        out.serialize_compressed(&mut bytes_out).unwrap();
+        let bytes_out = serialize(out)?;
         let bytes_in = self
             .client_send_or_king_receive(
                 &bytes_out,
@@ -31,98 +36,514 @@ pub trait MpcSerNet: MpcNet {
             )
             .await?;
 
-        if let Some(result) = bytes_in {
-            match result {
-                ClientSendOrKingReceiveResult::Full(bytes_in) => {
-                    let results: Vec<Result<T, MpcNetError>> = bytes_in
-                        .into_iter()
-                        .map(|b| {
-                            T::deserialize_compressed(&b[..]).map_err(|err| {
-                                MpcNetError::Generic(err.to_string())
-                            })
-                        })
-                        .collect();
-
-                    let mut ret = Vec::new();
-                    for result in results {
-                        ret.push(result?);
-                    }
-
-                    Ok(Some(ReceivedShares {
-                        shares: ret,
-                        parties: (0..self.n_parties() as u32).collect(),
-                    }))
-                }
+        bytes_in
+            .map(|result| received_shares(self, result, threshold))
+            .transpose()
+    }
 
-                ClientSendOrKingReceiveResult::Partial(received_results) => {
-                    // create a hash map with deserialized results, dropping the ones that return MpcNetError
-                    let serialized_results = received_results
-                        .into_iter()
-                        .filter_map(|(id, bytes)| {
-                            let result = T::deserialize_compressed(&bytes[..])
-                                .map_err(|err| {
-                                    MpcNetError::Generic(err.to_string())
-                                });
-                            if result.is_err() {
-                                return None;
-                            }
-                            Some((id, result.unwrap()))
-                        })
-                        .collect::<Vec<_>>();
-
-                    if serialized_results.len() < threshold {
-                        return Err(MpcNetError::Protocol {
-                            err: format!(
-                                "Timeout: only {} responses received",
-                                serialized_results.len()
-                            ),
-                            party: 0,
-                        });
-                    }
+    /// client_send_or_king_receive_serialized on several stream ids at once,
+    /// with a single timeout for all of them (see
+    /// MpcNet::client_send_or_king_receive_multi_raw). The king gets the
+    /// shares received on each stream id, in the order of payloads.
+    async fn client_send_or_king_receive_multi<
+        T: Clone + CanonicalDeserialize + CanonicalSerialize + Sync,
+    >(
+        &self,
+        payloads: &[(MultiplexedStreamID, &T)],
+        threshold: usize,
+    ) -> Result<Option<Vec<ReceivedShares<T>>>, MpcNetError> {
+        let bytes_out = payloads
+            .iter()
+            .map(|(sid, out)| Ok((*sid, serialize(*out)?)))
+            .collect::<Result<Vec<_>, MpcNetError>>()?;
+        let bytes_out = bytes_out
+            .iter()
+            .map(|(sid, bytes)| (*sid, &bytes[..]))
+            .collect::<Vec<_>>();
+        let bytes_in = self
+            .client_send_or_king_receive_multi_raw(
+                &bytes_out,
+                self.calculate_timeout(),
+            )
+            .await?;
 
-                    Ok(Some(ReceivedShares {
-                        shares: serialized_results
-                            .iter()
-                            .map(|(_, share)| share.clone())
-                            .collect::<Vec<_>>(),
-
-                        parties: serialized_results
-                            .iter()
-                            .map(|(party, _)| *party)
-                            .collect(),
-                    }))
-                }
-            }
-        } else {
-            Ok(None)
-        }
+        bytes_in
+            .map(|results| {
+                results
+                    .into_iter()
+                    .map(|result| received_shares(self, result, threshold))
+                    .collect()
+            })
+            .transpose()
     }
 
+    /// Returns None to a king that is not one of party_ids, as it is sent no
+    /// answer
     async fn client_receive_or_king_send_serialized<
         T: CanonicalDeserialize + CanonicalSerialize + Send,
     >(
         &self,
         out: Option<Vec<T>>,
         sid: MultiplexedStreamID,
-    ) -> Result<T, MpcNetError> {
-        let bytes = out.map(|outs| {
-            outs.iter()
-                .map(|out| {
-                    let mut bytes_out = Vec::new();
-                    out.serialize_compressed(&mut bytes_out).unwrap();
-                    bytes_out.into()
-                })
-                .collect()
-        });
+    ) -> Result<Option<T>, MpcNetError> {
+        let bytes = out
+            .map(|outs| {
+                outs.iter()
+                    .map(|out| serialize(out).map(Into::into))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
 
         let bytes_in = self.client_receive_or_king_send(bytes, sid).await?;
-        Ok(T::deserialize_compressed(&bytes_in[..])?)
+        if self.party_index().is_none() {
+            return Ok(None);
+        }
+        T::deserialize_compressed(&bytes_in[..])
+            .map(Some)
+            .map_err(|err| MpcNetError::Serialization {
+                err: format!("Could not deserialize the king's answer: {err}"),
+            })
+    }
+
+    /// MpcNet::broadcast of a serializable value: every party gets the
+    /// values of all parties, ordered like party_ids
+    async fn broadcast_serialized<
+        T: Clone + CanonicalDeserialize + CanonicalSerialize + Sync,
+    >(
+        &self,
+        out: &T,
+        sid: MultiplexedStreamID,
+    ) -> Result<ReceivedShares<T>, MpcNetError> {
+        let bytes_out = serialize(out)?;
+        let bytes_in = self.broadcast(&bytes_out, sid).await?;
+        received_shares(
+            self,
+            ClientSendOrKingReceiveResult::Full(bytes_in),
+            self.n_parties(),
+        )
+    }
+
+    /// A round through the king: every party sends `out`, the king turns the
+    /// shares it received into one answer per party (ordered like party_ids)
+    /// with `king_fn`, and every party returns its answer.
+    /// A king that is not one of party_ids holds no share and gets
+    /// U::default(), see king_broadcast_round for public outputs.
+    async fn king_round<T, U>(
+        &self,
+        out: &T,
+        king_fn: impl FnOnce(ReceivedShares<T>) -> Vec<U> + Send,
+        sid: MultiplexedStreamID,
+        threshold: usize,
+    ) -> Result<U, MpcNetError>
+    where
+        T: Clone + CanonicalDeserialize + CanonicalSerialize + Send + Sync,
+        U: CanonicalDeserialize + CanonicalSerialize + Send + Default,
+    {
+        self.try_king_round(out, |rs| Ok(king_fn(rs)), sid, threshold)
+            .await
+    }
+
+    /// Same as king_round, but king_fn can fail, e.g on shares that do not
+    /// reconstruct. The king then returns the error and sends every party an
+    /// abort, which fails the round there with MpcNetError::Protocol instead
+    /// of leaving them to time out.
+    async fn try_king_round<T, U>(
+        &self,
+        out: &T,
+        king_fn: impl FnOnce(ReceivedShares<T>) -> Result<Vec<U>, MpcNetError>
+            + Send,
+        sid: MultiplexedStreamID,
+        threshold: usize,
+    ) -> Result<U, MpcNetError>
+    where
+        T: Clone + CanonicalDeserialize + CanonicalSerialize + Send + Sync,
+        U: CanonicalDeserialize + CanonicalSerialize + Send + Default,
+    {
+        let mut king_err = None;
+        let king_answer = self
+            .client_send_or_king_receive_serialized(out, sid, threshold)
+            .await?
+            .map(|rs| match king_fn(rs) {
+                Ok(answers) => answers.into_iter().map(Some).collect(),
+                Err(err) => {
+                    king_err = Some(err);
+                    self.party_ids().map(|_| None).collect::<Vec<Option<U>>>()
+                }
+            });
+        let answer = self
+            .client_receive_or_king_send_serialized(king_answer, sid)
+            .await?;
+        if let Some(err) = king_err {
+            return Err(err);
+        }
+        match answer {
+            Some(Some(answer)) => Ok(answer),
+            Some(None) => Err(MpcNetError::Protocol {
+                err: "The king aborted the round".to_string(),
+                party: self.king_id(),
+            }),
+            None => Ok(U::default()),
+        }
+    }
+
+    /// Same as try_king_round, but king_fn computes a single answer for all
+    /// parties, e.g a public output. Every party returns it, including a king
+    /// that is not one of party_ids.
+    async fn king_broadcast_round<T, U>(
+        &self,
+        out: &T,
+        king_fn: impl FnOnce(ReceivedShares<T>) -> Result<U, MpcNetError> + Send,
+        sid: MultiplexedStreamID,
+        threshold: usize,
+    ) -> Result<U, MpcNetError>
+    where
+        T: Clone + CanonicalDeserialize + CanonicalSerialize + Send + Sync,
+        U: Clone + CanonicalDeserialize + CanonicalSerialize + Send + Default,
+    {
+        let mut king_answer = None;
+        let answer = self
+            .try_king_round(
+                out,
+                |rs| {
+                    let answer = king_fn(rs)?;
+                    let answers =
+                        self.party_ids().map(|_| answer.clone()).collect();
+                    king_answer = Some(answer);
+                    Ok(answers)
+                },
+                sid,
+                threshold,
+            )
+            .await?;
+        Ok(king_answer.unwrap_or(answer))
     }
 
     fn calculate_timeout(&self) -> Duration {
-        // For now, assume a fixed timeout of 30 seconds
-        Duration::from_secs(30)
+        self.round_timeout()
     }
 }
 
 impl<N: MpcNet> MpcSerNet for N {}
+
+/// Deserializes the shares the king received in a round
+fn received_shares<T, N>(
+    net: &N,
+    result: ClientSendOrKingReceiveResult,
+    threshold: usize,
+) -> Result<ReceivedShares<T>, MpcNetError>
+where
+    T: Clone + CanonicalDeserialize,
+    N: MpcNet + ?Sized,
+{
+    match result {
+        ClientSendOrKingReceiveResult::Full(bytes_in) => {
+            let n_shares = bytes_in.len() as u32;
+            let results: Vec<Result<T, MpcNetError>> = bytes_in
+                .into_iter()
+                .zip(net.party_ids())
+                .map(|(b, id)| {
+                    T::deserialize_compressed(&b[..]).map_err(|err| {
+                        MpcNetError::Protocol {
+                            err: format!("Could not deserialize share: {err}"),
+                            party: id,
+                        }
+                    })
+                })
+                .collect();
+
+            let mut ret = Vec::new();
+            for result in results {
+                ret.push(result?);
+            }
+
+            Ok(ReceivedShares {
+                shares: ret,
+                parties: (0..n_shares).collect(),
+                faulty: Vec::new(),
+            })
+        }
+
+        ClientSendOrKingReceiveResult::Partial(received_results) => {
+            // deserialize the results, setting aside the parties that sent malformed shares
+            let mut serialized_results = Vec::new();
+            let mut faulty = Vec::new();
+            for (index, id) in net.party_ids().enumerate() {
+                let Some(bytes) = received_results.get(&id) else {
+                    continue;
+                };
+                match T::deserialize_compressed(&bytes[..]) {
+                    Ok(share) => serialized_results.push((index as u32, share)),
+                    Err(err) => {
+                        warn!("Party {id} sent a share that could not be deserialized: {err}");
+                        faulty.push(index as u32);
+                    }
+                }
+            }
+
+            if serialized_results.len() < threshold {
+                return Err(MpcNetError::Protocol {
+                    err: format!(
+                        "Timeout: only {} responses received",
+                        serialized_results.len()
+                    ),
+                    party: net.king_id(),
+                });
+            }
+
+            Ok(ReceivedShares {
+                shares: serialized_results
+                    .iter()
+                    .map(|(_, share)| share.clone())
+                    .collect::<Vec<_>>(),
+
+                parties: serialized_results
+                    .iter()
+                    .map(|(party, _)| *party)
+                    .collect(),
+                faulty,
+            })
+        }
+    }
+}
+
+fn serialize<T: CanonicalSerialize>(out: &T) -> Result<Vec<u8>, MpcNetError> {
+    let mut bytes_out = Vec::new();
+    out.serialize_compressed(&mut bytes_out).map_err(|err| {
+        MpcNetError::Serialization {
+            err: format!("Could not serialize: {err}"),
+        }
+    })?;
+    Ok(bytes_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ser_net::MpcSerNet;
+    use crate::{LocalTestNet, MpcNet, MpcNetError, MultiplexedStreamID};
+    use ark_serialize::{
+        CanonicalDeserialize, CanonicalSerialize, Compress, Read,
+        SerializationError, Valid, Validate, Write,
+    };
+    use std::time::Duration;
+
+    /// Fails to serialize, like a vector too large for its length prefix
+    #[derive(Clone)]
+    struct Unserializable;
+
+    impl CanonicalSerialize for Unserializable {
+        fn serialize_with_mode<W: Write>(
+            &self,
+            _writer: W,
+            _compress: Compress,
+        ) -> Result<(), SerializationError> {
+            Err(SerializationError::NotEnoughSpace)
+        }
+
+        fn serialized_size(&self, _compress: Compress) -> usize {
+            0
+        }
+    }
+
+    impl Valid for Unserializable {
+        fn check(&self) -> Result<(), SerializationError> {
+            Ok(())
+        }
+    }
+
+    impl CanonicalDeserialize for Unserializable {
+        fn deserialize_with_mode<R: Read>(
+            _reader: R,
+            _compress: Compress,
+            _validate: Validate,
+        ) -> Result<Self, SerializationError> {
+            Err(SerializationError::InvalidData)
+        }
+    }
+
+    #[tokio::test]
+    async fn serialization_failure_is_an_error() {
+        let testnet = LocalTestNet::new_local_testnet(4).await.unwrap();
+        let king = testnet.get_king();
+        let sid = MultiplexedStreamID::Zero;
+
+        let sent = king
+            .client_send_or_king_receive_serialized(&Unserializable, sid, 0)
+            .await;
+        assert!(matches!(sent, Err(MpcNetError::Serialization { .. })));
+
+        let answered = king
+            .client_receive_or_king_send_serialized(
+                Some(vec![Unserializable; 4]),
+                sid,
+            )
+            .await;
+        assert!(matches!(answered, Err(MpcNetError::Serialization { .. })));
+
+        let short = king
+            .client_receive_or_king_send_serialized(Some(vec![0u32; 3]), sid)
+            .await;
+        assert!(matches!(short, Err(MpcNetError::BadInput { .. })));
+    }
+
+    #[tokio::test]
+    async fn undeserializable_share_names_the_party() {
+        const N_PARTIES: usize = 4;
+        const FAULTY: u32 = 2;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let results = testnet
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::Zero;
+                if net.party_id() == FAULTY {
+                    // a u32 needs 4 bytes
+                    net.client_send_or_king_receive(
+                        &[1u8],
+                        sid,
+                        Duration::from_secs(1),
+                    )
+                    .await
+                    .unwrap();
+                    None
+                } else {
+                    net.client_send_or_king_receive_serialized(
+                        &net.party_id(),
+                        sid,
+                        0,
+                    )
+                    .await
+                    .err()
+                }
+            })
+            .await;
+
+        assert!(matches!(
+            results[0],
+            Some(MpcNetError::Protocol { party: FAULTY, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn king_round_answers_each_party() {
+        let testnet = LocalTestNet::new_local_testnet(4).await.unwrap();
+
+        let results = testnet
+            .simulate_network_round((), |net, _| async move {
+                net.king_round(
+                    &net.party_id(),
+                    |rs| {
+                        let sum: u32 = rs.shares.iter().sum();
+                        rs.parties.iter().map(|id| sum + id).collect()
+                    },
+                    MultiplexedStreamID::Zero,
+                    0,
+                )
+                .await
+                .unwrap()
+            })
+            .await;
+
+        assert_eq!(results, vec![6, 7, 8, 9]);
+    }
+
+    #[tokio::test]
+    async fn king_broadcast_round_answers_a_king_holding_no_share() {
+        let testnet =
+            LocalTestNet::new_local_testnet_with_king(3).await.unwrap();
+
+        let results = testnet
+            .simulate_network_round((), |net, _| async move {
+                net.king_broadcast_round(
+                    &net.party_id(),
+                    |rs| Ok(rs.shares.iter().sum::<u32>()),
+                    MultiplexedStreamID::Zero,
+                    0,
+                )
+                .await
+                .unwrap()
+            })
+            .await;
+
+        // node 0 is the king, the share holders are 1, 2 and 3
+        assert_eq!(results, vec![6; 4]);
+    }
+
+    #[tokio::test]
+    async fn failed_king_round_aborts_every_party() {
+        let testnet = LocalTestNet::new_local_testnet(4).await.unwrap();
+
+        let results = testnet
+            .simulate_network_round((), |net, _| async move {
+                net.try_king_round::<u32, u32>(
+                    &net.party_id(),
+                    |_| Err(MpcNetError::Generic("rejected".to_string())),
+                    MultiplexedStreamID::Zero,
+                    0,
+                )
+                .await
+            })
+            .await;
+
+        assert!(matches!(results[0], Err(MpcNetError::Generic(_))));
+        for result in &results[1..] {
+            assert!(matches!(
+                result,
+                Err(MpcNetError::Protocol { party: 0, .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn multi_receives_every_stream_id() {
+        const N_PARTIES: usize = 4;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let results = testnet
+            .simulate_network_round((), |net, _| async move {
+                let id = net.party_id();
+                let (a, b) = (id, 10 * id);
+                net.client_send_or_king_receive_multi(
+                    &[
+                        (MultiplexedStreamID::Two, &a),
+                        (MultiplexedStreamID::Zero, &b),
+                    ],
+                    N_PARTIES,
+                )
+                .await
+                .unwrap()
+            })
+            .await;
+
+        let received = results[0].as_ref().unwrap();
+        assert_eq!(received[0].shares, vec![0, 1, 2, 3]);
+        assert_eq!(received[1].shares, vec![0, 10, 20, 30]);
+        assert!(results[1..].iter().all(Option::is_none));
+
+        let testnet = LocalTestNet::new_local_testnet(2).await.unwrap();
+        let king = testnet.get_king();
+        let sid = MultiplexedStreamID::One;
+        assert!(matches!(
+            king.client_send_or_king_receive_multi(
+                &[(sid, &1u32), (sid, &2)],
+                0
+            )
+            .await,
+            Err(MpcNetError::BadInput { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn broadcast_reaches_every_party() {
+        const N_PARTIES: usize = 4;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let results = testnet
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::One;
+                let rs = net.broadcast_serialized(&net.party_id(), sid).await;
+                rs.unwrap().shares
+            })
+            .await;
+
+        assert!(results.iter().all(|shares| *shares == [0, 1, 2, 3]));
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tokio_util::bytes::Bytes;
+
+use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+
+/// Cheaply clonable handle to a network, to run several protocols from
+/// different tasks over the same connections.
+///
+/// Concurrent use of *different* stream ids is safe, each stream of each
+/// peer is locked independently. The same stream id must not be used by two
+/// protocols at the same time, since their messages would interleave. Tasks
+/// that can't partition the stream ids statically should hold the guard of
+/// [`SharedNet::acquire`] for the duration of the protocol.
+pub struct SharedNet<N: MpcNet> {
+    inner: Arc<N>,
+    sids: Arc<Vec<Arc<Mutex<()>>>>,
+}
+
+impl<N: MpcNet> Clone for SharedNet<N> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            sids: self.sids.clone(),
+        }
+    }
+}
+
+impl<N: MpcNet> SharedNet<N> {
+    pub fn new(inner: N) -> Self {
+        let sids = (0..MultiplexedStreamID::channel_count())
+            .map(|_| Arc::new(Mutex::new(())))
+            .collect();
+        Self {
+            inner: Arc::new(inner),
+            sids: Arc::new(sids),
+        }
+    }
+
+    /// Waits until no other task holds `sid` and reserves it until the
+    /// returned guard is dropped
+    pub async fn acquire(
+        &self,
+        sid: MultiplexedStreamID,
+    ) -> OwnedMutexGuard<()> {
+        self.sids[sid as usize].clone().lock_owned().await
+    }
+}
+
+#[async_trait]
+impl<N: MpcNet> MpcNet for SharedNet<N> {
+    fn n_parties(&self) -> usize {
+        self.inner.n_parties()
+    }
+
+    fn party_id(&self) -> u32 {
+        self.inner.party_id()
+    }
+
+    fn king_id(&self) -> u32 {
+        self.inner.king_id()
+    }
+
+    fn party_ids(&self) -> Box<dyn Iterator<Item = u32> + Send + '_> {
+        self.inner.party_ids()
+    }
+
+    fn is_init(&self) -> bool {
+        self.inner.is_init()
+    }
+
+    fn round_timeout(&self) -> Duration {
+        self.inner.round_timeout()
+    }
+
+    fn record_round_time(&self, elapsed: Duration) {
+        self.inner.record_round_time(elapsed)
+    }
+
+    async fn recv_from(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MpcNetError> {
+        self.inner.recv_from(id, sid).await
+    }
+
+    async fn send_to(
+        &self,
+        id: u32,
+        bytes: Bytes,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MpcNetError> {
+        self.inner.send_to(id, bytes, sid).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ser_net::MpcSerNet;
+    use crate::shared::SharedNet;
+    use crate::{LocalTestNet, MpcNet, MultiplexedStreamID};
+
+    #[tokio::test]
+    async fn concurrent_rounds_on_distinct_sids() {
+        const N_PARTIES: usize = 4;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        let sids = [
+            MultiplexedStreamID::Zero,
+            MultiplexedStreamID::One,
+            MultiplexedStreamID::Two,
+        ];
+
+        let results = testnet
+            .simulate_network_round((), move |conn, _| async move {
+                let net = SharedNet::new(conn);
+                let mut handles = Vec::new();
+                for (job, sid) in sids.iter().copied().enumerate() {
+                    let net = net.clone();
+                    handles.push(tokio::spawn(async move {
+                        let _guard = net.acquire(sid).await;
+                        let king_answer = net
+                            .client_send_or_king_receive_serialized(
+                                &(net.party_id() * job as u32),
+                                sid,
+                                0,
+                            )
+                            .await
+                            .unwrap()
+                            .map(|rs| {
+                                vec![rs.shares.iter().sum::<u32>(); N_PARTIES]
+                            });
+                        net.client_receive_or_king_send_serialized(
+                            king_answer,
+                            sid,
+                        )
+                        .await
+                        .unwrap()
+                        .unwrap()
+                    }));
+                }
+
+                let mut sums = Vec::new();
+                for handle in handles {
+                    sums.push(handle.await.unwrap());
+                }
+                sums
+            })
+            .await;
+
+        let id_sum = (0..N_PARTIES as u32).sum::<u32>();
+        for sums in results {
+            assert_eq!(sums, vec![0, id_sum, 2 * id_sum]);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::ops::{Add, Sub};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio_util::bytes::Bytes;
+
+use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+
+/// Traffic seen on a single channel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+}
+
+impl Add for ChannelStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            bytes_sent: self.bytes_sent + other.bytes_sent,
+            bytes_received: self.bytes_received + other.bytes_received,
+            messages_sent: self.messages_sent + other.messages_sent,
+            messages_received: self.messages_received + other.messages_received,
+        }
+    }
+}
+
+impl Sub for ChannelStats {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            bytes_sent: self.bytes_sent - other.bytes_sent,
+            bytes_received: self.bytes_received - other.bytes_received,
+            messages_sent: self.messages_sent - other.messages_sent,
+            messages_received: self.messages_received - other.messages_received,
+        }
+    }
+}
+
+/// Traffic seen on every channel, indexed by MultiplexedStreamID
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetStats {
+    pub channels: [ChannelStats; MultiplexedStreamID::channel_count()],
+}
+
+impl NetStats {
+    pub fn channel(&self, sid: MultiplexedStreamID) -> ChannelStats {
+        self.channels[sid as usize]
+    }
+
+    /// Sum over all channels
+    pub fn total(&self) -> ChannelStats {
+        self.channels
+            .iter()
+            .fold(ChannelStats::default(), |acc, c| acc + *c)
+    }
+
+    /// Traffic since an earlier snapshot, e.g over one phase of a protocol
+    pub fn since(&self, earlier: &Self) -> Self {
+        let mut channels = self.channels;
+        for (c, e) in channels.iter_mut().zip(earlier.channels) {
+            *c = *c - e;
+        }
+        Self { channels }
+    }
+}
+
+/// Wraps a network and counts the bytes and messages sent and received
+/// through it, per channel. Works for any MpcNet, e.g ProdNet or
+/// MpcNetConnection, so benchmarks can tell network from compute time.
+pub struct StatsNet<N: MpcNet> {
+    inner: N,
+    stats: Arc<Mutex<NetStats>>,
+}
+
+impl<N: MpcNet> StatsNet<N> {
+    pub fn new(inner: N) -> Self {
+        Self {
+            inner,
+            stats: Default::default(),
+        }
+    }
+
+    /// Snapshot of the traffic so far
+    pub fn stats(&self) -> NetStats {
+        *self.stats.lock()
+    }
+
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<N: MpcNet> MpcNet for StatsNet<N> {
+    fn n_parties(&self) -> usize {
+        self.inner.n_parties()
+    }
+
+    fn party_id(&self) -> u32 {
+        self.inner.party_id()
+    }
+
+    fn king_id(&self) -> u32 {
+        self.inner.king_id()
+    }
+
+    fn party_ids(&self) -> Box<dyn Iterator<Item = u32> + Send + '_> {
+        self.inner.party_ids()
+    }
+
+    fn is_init(&self) -> bool {
+        self.inner.is_init()
+    }
+
+    fn round_timeout(&self) -> Duration {
+        self.inner.round_timeout()
+    }
+
+    fn record_round_time(&self, elapsed: Duration) {
+        self.inner.record_round_time(elapsed)
+    }
+
+    async fn recv_from(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MpcNetError> {
+        let bytes = self.inner.recv_from(id, sid).await?;
+        let mut stats = self.stats.lock();
+        let channel = &mut stats.channels[sid as usize];
+        channel.bytes_received += bytes.len() as u64;
+        channel.messages_received += 1;
+        Ok(bytes)
+    }
+
+    async fn send_to(
+        &self,
+        id: u32,
+        bytes: Bytes,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MpcNetError> {
+        let len = bytes.len() as u64;
+        self.inner.send_to(id, bytes, sid).await?;
+        let mut stats = self.stats.lock();
+        let channel = &mut stats.channels[sid as usize];
+        channel.bytes_sent += len;
+        channel.messages_sent += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ser_net::MpcSerNet;
+    use crate::stats::{ChannelStats, StatsNet};
+    use crate::{LocalTestNet, MpcNet, MultiplexedStreamID};
+
+    #[tokio::test]
+    async fn counts_king_round() {
+        const N_PARTIES: usize = 4;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let stats = testnet
+            .simulate_network_round((), |conn, _| async move {
+                let net = StatsNet::new(conn);
+                let sid = MultiplexedStreamID::One;
+                net.king_round(
+                    &net.party_id(),
+                    |rs| vec![rs.shares.iter().sum::<u32>(); N_PARTIES],
+                    sid,
+                    0,
+                )
+                .await
+                .unwrap();
+                net.stats()
+            })
+            .await;
+
+        // a serialized u32 is 4 bytes, the answers of the king are Options
+        // of one, see try_king_round, and take one more
+        let n = N_PARTIES as u64 - 1;
+        let king = stats[0].channel(MultiplexedStreamID::One);
+        assert_eq!(
+            king,
+            ChannelStats {
+                bytes_sent: 5 * n,
+                bytes_received: 4 * n,
+                messages_sent: n,
+                messages_received: n,
+            }
+        );
+        assert_eq!(stats[0].total(), king);
+        for client in &stats[1..] {
+            assert_eq!(client.total().bytes_sent, 4);
+            assert_eq!(client.total().bytes_received, 5);
+        }
+        assert_eq!(stats[1].since(&stats[1]).total(), ChannelStats::default());
+    }
+}
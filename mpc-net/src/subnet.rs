@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio_util::bytes::Bytes;
+
+use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+
+/// View of a committee of a network as a network on its own, e.g to run
+/// different parts of a protocol among different sets of machines.
+/// Party offset + i of the inner network is party i of the committee,
+/// and party 0 is its king.
+pub struct SubNet<N: MpcNet> {
+    inner: N,
+    offset: u32,
+    n_parties: usize,
+}
+
+impl<N: MpcNet> SubNet<N> {
+    /// The committee of parties offset..offset + n_parties of inner,
+    /// which must contain this party
+    pub fn new(
+        inner: N,
+        offset: u32,
+        n_parties: usize,
+    ) -> Result<Self, MpcNetError> {
+        let id = inner.party_id();
+        if id < offset || id - offset >= n_parties as u32 {
+            return Err(MpcNetError::BadInput {
+                err: format!(
+                    "party {} is not in the committee {}..{}",
+                    id,
+                    offset,
+                    offset + n_parties as u32
+                ),
+            });
+        }
+        Ok(Self {
+            inner,
+            offset,
+            n_parties,
+        })
+    }
+
+    /// Id in the inner network of the committee's party id
+    pub fn inner_id(&self, id: u32) -> u32 {
+        self.offset + id
+    }
+
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<N: MpcNet> MpcNet for SubNet<N> {
+    fn n_parties(&self) -> usize {
+        self.n_parties
+    }
+
+    fn party_id(&self) -> u32 {
+        self.inner.party_id() - self.offset
+    }
+
+    fn is_init(&self) -> bool {
+        self.inner.is_init()
+    }
+
+    fn round_timeout(&self) -> Duration {
+        self.inner.round_timeout()
+    }
+
+    fn record_round_time(&self, elapsed: Duration) {
+        self.inner.record_round_time(elapsed)
+    }
+
+    async fn recv_from(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MpcNetError> {
+        self.inner.recv_from(self.inner_id(id), sid).await
+    }
+
+    async fn send_to(
+        &self,
+        id: u32,
+        bytes: Bytes,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MpcNetError> {
+        self.inner.send_to(self.inner_id(id), bytes, sid).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inproc::InProcTestNet;
+    use crate::ser_net::MpcSerNet;
+    use crate::subnet::SubNet;
+    use crate::{MpcNet, MpcNetError, MultiplexedStreamID};
+
+    #[tokio::test]
+    async fn committees_run_separate_rounds() {
+        const N_PARTIES: usize = 4;
+        let testnet = InProcTestNet::new(2 * N_PARTIES);
+
+        let results = testnet
+            .simulate_network_round((), |net, _| async move {
+                let n = N_PARTIES as u32;
+                let offset = net.party_id() / n * n;
+                let committee = SubNet::new(net, offset, N_PARTIES).unwrap();
+                // both committees use the same stream id at the same time
+                let sum: u32 = committee
+                    .king_round(
+                        &committee.inner_id(committee.party_id()),
+                        |rs| vec![rs.shares.iter().sum(); N_PARTIES],
+                        MultiplexedStreamID::Zero,
+                        0,
+                    )
+                    .await
+                    .unwrap();
+                sum
+            })
+            .await;
+
+        assert_eq!(results, [vec![6; 4], vec![22; 4]].concat());
+    }
+
+    #[tokio::test]
+    async fn rejects_outsiders() {
+        let net = InProcTestNet::new(4).into_nodes().remove(1);
+        assert!(matches!(
+            SubNet::new(net, 2, 2),
+            Err(MpcNetError::BadInput { .. })
+        ));
+    }
+}
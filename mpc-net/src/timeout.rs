@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+/// Timeout of the king receiving the shares of a round, for networks that
+/// don't measure their rounds
+pub const DEFAULT_ROUND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tuning of AdaptiveTimeout
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeoutConfig {
+    /// Timeout until `warmup` rounds were measured
+    pub initial: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// The timeout is factor * average + slack
+    pub factor: f64,
+    pub slack: Duration,
+    /// Weight of the latest round in the moving average, in (0, 1]
+    pub alpha: f64,
+    /// Rounds to measure before adapting
+    pub warmup: usize,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            initial: DEFAULT_ROUND_TIMEOUT,
+            min: Duration::from_secs(5),
+            max: Duration::from_secs(300),
+            factor: 4.0,
+            slack: Duration::from_secs(1),
+            alpha: 0.2,
+            warmup: 8,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RoundTimes {
+    /// Exponentially weighted moving average, in seconds
+    average: f64,
+    samples: usize,
+}
+
+/// Round timeout following an exponentially weighted moving average of the
+/// measured round times, so that fast local runs detect a missing party
+/// quickly while slow WAN runs don't give up on it too early.
+/// A round that times out is recorded as taking the whole timeout, a lower
+/// bound of its time, so the timeout keeps growing while rounds slow down.
+#[derive(Debug, Default)]
+pub struct AdaptiveTimeout {
+    config: TimeoutConfig,
+    times: Mutex<RoundTimes>,
+}
+
+impl AdaptiveTimeout {
+    pub fn new(config: TimeoutConfig) -> Self {
+        assert!(config.min <= config.max, "min timeout above max timeout");
+        assert!(
+            config.alpha > 0.0 && config.alpha <= 1.0,
+            "alpha must be in (0, 1]"
+        );
+        Self {
+            config,
+            times: Default::default(),
+        }
+    }
+
+    pub fn config(&self) -> &TimeoutConfig {
+        &self.config
+    }
+
+    /// Adds the time a round took to complete to the average
+    pub fn record(&self, elapsed: Duration) {
+        let mut times = self.times.lock();
+        let elapsed = elapsed.as_secs_f64();
+        times.average = if times.samples == 0 {
+            elapsed
+        } else {
+            self.config.alpha * elapsed
+                + (1.0 - self.config.alpha) * times.average
+        };
+        times.samples += 1;
+    }
+
+    pub fn timeout(&self) -> Duration {
+        let times = self.times.lock();
+        if times.samples < self.config.warmup {
+            return self.config.initial;
+        }
+        let adapted =
+            Duration::from_secs_f64(self.config.factor * times.average)
+                + self.config.slack;
+        adapted.clamp(self.config.min, self.config.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::ser_net::MpcSerNet;
+    use crate::timeout::{
+        AdaptiveTimeout, TimeoutConfig, DEFAULT_ROUND_TIMEOUT,
+    };
+    use crate::{LocalTestNet, MpcNet, MultiplexedStreamID};
+
+    #[test]
+    fn adapts_after_warmup_within_bounds() {
+        let config = TimeoutConfig {
+            warmup: 2,
+            ..Default::default()
+        };
+        let timeout = AdaptiveTimeout::new(config);
+
+        timeout.record(Duration::from_secs(2));
+        assert_eq!(timeout.timeout(), config.initial);
+        timeout.record(Duration::from_secs(2));
+        assert_eq!(timeout.timeout(), Duration::from_secs(9));
+
+        // a slow round moves the average by alpha
+        timeout.record(Duration::from_secs(12));
+        assert_eq!(timeout.timeout(), Duration::from_secs(17));
+
+        for _ in 0..100 {
+            timeout.record(Duration::from_millis(1));
+        }
+        assert_eq!(timeout.timeout(), config.min);
+        for _ in 0..100 {
+            timeout.record(Duration::from_secs(3600));
+        }
+        assert_eq!(timeout.timeout(), config.max);
+    }
+
+    #[test]
+    fn grows_while_rounds_slow_down() {
+        let config = TimeoutConfig {
+            warmup: 2,
+            ..Default::default()
+        };
+        let timeout = AdaptiveTimeout::new(config);
+        let mut timed_out = 0;
+        // rounds of 1s, then of 30s
+        for round in 0..20 {
+            let elapsed = Duration::from_secs(if round < 10 { 1 } else { 30 });
+            let waited = timeout.timeout();
+            if elapsed > waited {
+                timeout.record(waited);
+                timed_out += 1;
+            } else {
+                timeout.record(elapsed);
+            }
+        }
+
+        // 5s, 8.2s, 13.3s and 21.5s time out
+        assert_eq!(timed_out, 4);
+        assert!(timeout.timeout() > Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn king_tunes_its_timeout_to_the_rounds() {
+        const N_PARTIES: usize = 4;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let timeouts = testnet
+            .simulate_network_round((), |net, _| async move {
+                let before = net.round_timeout();
+                for _ in 0..TimeoutConfig::default().warmup {
+                    let _: u32 = net
+                        .king_round(
+                            &net.party_id(),
+                            |rs| vec![rs.shares.iter().sum(); N_PARTIES],
+                            MultiplexedStreamID::Zero,
+                            0,
+                        )
+                        .await
+                        .unwrap();
+                }
+                (before, net.round_timeout())
+            })
+            .await;
+
+        // only the king measures the rounds, they are fast on localhost
+        assert_eq!(timeouts[0].0, DEFAULT_ROUND_TIMEOUT);
+        assert_eq!(timeouts[0].1, TimeoutConfig::default().min);
+        assert!(timeouts[1..]
+            .iter()
+            .all(|t| *t == (DEFAULT_ROUND_TIMEOUT, DEFAULT_ROUND_TIMEOUT)));
+    }
+}
@@ -1,8 +1,13 @@
 #!/bin/bash
 set -ex
 echo "Generating certificates..."
-n=5 # number of key/cert pairs to generate
-king_addr="127.0.0.1:12344"
+n=8 # number of key/cert pairs to generate, 2(t + l) for t = l = 2
+
+# the address of every party, the king binds to the first one
+rm -f ./peers
+for i in $(seq 0 $((n-1))); do
+  echo "127.0.0.1:$((12344 + i))" >> ./peers
+done
 
 mkdir -p ./certs
 for i in $(seq 0 $((n-1))); do
@@ -26,13 +31,13 @@ do
     if [ $i == 0 ]
     then
       # Setup king
-      RUST_BACKTRACE=0 RUST_LOG=fft $BIN ./public_$i.cert.der ./private_$i.key.der --id $i --n-parties $n  --bind-addr $king_addr --client-cert-dir ./certs &
+      RUST_BACKTRACE=0 RUST_LOG=fft $BIN ./public_$i.cert.der ./private_$i.key.der ./peers --id $i --client-cert-dir ./certs &
       pid=$!
       PROCS[$i]=$pid
       sleep 1
     else
       # Setup basic node
-      RUST_LOG=fft $BIN ./certs/public_$i.cert.der ./certs/private_$i.key.der --id $i --n-parties $n --king-addr $king_addr --king-cert ./public_0.cert.der > /dev/null &
+      RUST_LOG=fft $BIN ./certs/public_$i.cert.der ./certs/private_$i.key.der ./peers --id $i --king-cert ./public_0.cert.der > /dev/null &
       pid=$!
       PROCS[$i]=$pid
     fi
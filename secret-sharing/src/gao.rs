@@ -50,6 +50,18 @@ impl<F: FftField> PackedSharingParams<F> {
         codelength: usize,
         dimension: usize,
     ) -> DensePolynomial<F> {
+        self.try_decode_to_message(received_code, codelength, dimension)
+            .expect("decoding failed")
+    }
+
+    /// Same as decode_to_message, but returns None when there are too many
+    /// errors to decode instead of panicking
+    pub fn try_decode_to_message(
+        &self,
+        received_code: Vec<F>,
+        codelength: usize,
+        dimension: usize,
+    ) -> Option<DensePolynomial<F>> {
         // Based on SageMath's implementation
         // https://github.com/sagemath/sage/blob/b002b63fb42e44f5404a1f8856378aa1ba5b2b1c/src/sage/coding/grs_code.py#L1584
         // Decodes a received code word ``received_code`` into a code word and the corresponding message.
@@ -75,12 +87,13 @@ impl<F: FftField> PackedSharingParams<F> {
         let q0 = DenseOrSparsePolynomial::from(q0);
 
         // h should be the message
-        let (h, rem) = q1.divide_with_q_and_r(&q0).unwrap();
+        let (h, rem) = q1.divide_with_q_and_r(&q0)?;
 
-        // todo: add various checks for failed decoding
-        assert!(rem.is_zero());
+        if !rem.is_zero() || h.degree() >= dimension {
+            return None;
+        }
 
-        h
+        Some(h)
     }
 }
 
@@ -1,10 +1,129 @@
 use ark_poly::{domain::DomainCoeff, EvaluationDomain, Radix2EvaluationDomain};
 
 use ark_ff::FftField;
-use ark_std::{rand::Rng, UniformRand};
+use ark_std::{cfg_into_iter, rand::Rng, UniformRand};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 use crate::utils::lagrange_interpolate;
 
+/// Reasons for which a set of shares can not be unpacked
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnpackError<T> {
+    /// Expected one share per party
+    WrongLength { expected: usize, got: usize },
+    /// The shares do not lie on a polynomial of the expected degree.
+    /// Reports the first coefficient past the degree bound that is non zero
+    NonZeroCoefficient { index: usize, value: T },
+    /// No polynomial of the expected degree agrees with needed of the
+    /// received shares
+    Inconsistent { needed: usize, received: usize },
+}
+
+impl<T: core::fmt::Debug> core::fmt::Display for UnpackError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            UnpackError::WrongLength { expected, got } => {
+                write!(f, "expected {} shares, got {}", expected, got)
+            }
+            UnpackError::NonZeroCoefficient { index, value } => {
+                write!(f, "non zero coefficient {:?} at index {}", value, index)
+            }
+            UnpackError::Inconsistent { needed, received } => write!(
+                f,
+                "fewer than {} of the {} shares lie on one polynomial",
+                needed, received
+            ),
+        }
+    }
+}
+
+/// Reasons for which PackedSharingParams can not be built
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SharingError {
+    /// The secrets domain needs t + l to be a power of two
+    NotPowerOfTwo { t: usize, l: usize },
+    /// The field has no subgroup of size n = 2(t + l),
+    /// it only supports domains up to 2^two_adicity
+    DomainTooLarge { n: usize, two_adicity: u32 },
+    /// FAST splits base_l in halves, see from_preset
+    OddBase { base_l: usize },
+    /// The preset has no packing yet, see from_preset
+    UnsupportedPreset { preset: SharingPreset },
+}
+
+impl core::fmt::Display for SharingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SharingError::NotPowerOfTwo { t, l } => {
+                write!(f, "t + l = {} + {} is not a power of two", t, l)
+            }
+            SharingError::DomainTooLarge { n, two_adicity } => write!(
+                f,
+                "{} parties need a domain of size 2^{}, the field only supports up to 2^{}",
+                n,
+                n.trailing_zeros(),
+                two_adicity
+            ),
+            SharingError::OddBase { base_l } => {
+                write!(f, "the preset needs an even base l, got {}", base_l)
+            }
+            SharingError::UnsupportedPreset { preset } => write!(
+                f,
+                "the {:?} preset needs n > 2(t + l) parties, which is not supported yet",
+                preset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SharingError {}
+
+/// Named configurations of the PackedSharingParams doc, scaled to
+/// n = 4 base_l parties, see PackedSharingParams::from_preset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SharingPreset {
+    /// (base_l / 2, base_l), tolerates the most dropouts.
+    /// Not supported yet, see from_preset
+    Robust,
+    /// (base_l / 2, 3 base_l / 2), packs the most secrets per share
+    Fast,
+    /// (base_l, base_l), tolerates the most corruptions, same as new
+    Private,
+}
+
+/// A preset name that is not one of "robust", "fast" or "private"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownPreset(pub String);
+
+impl core::fmt::Display for UnknownPreset {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "unknown preset {:?}, expected robust, fast or private",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownPreset {}
+
+/// Parses a preset name, e.g from a config file or the command line,
+/// ignoring case
+impl TryFrom<&str> for SharingPreset {
+    type Error = UnknownPreset;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "robust" => Ok(SharingPreset::Robust),
+            "fast" => Ok(SharingPreset::Fast),
+            "private" => Ok(SharingPreset::Private),
+            _ => Err(UnknownPreset(name.to_string())),
+        }
+    }
+}
+
 /// Packed Secret Sharing Parameters
 ///
 /// Configures the parameters for packed secret sharing. It assumes that the number of parties is `4l`,
@@ -13,8 +132,11 @@ use crate::utils::lagrange_interpolate;
 /// Some possible configurations `(t, l, n) - #dropouts tolerated = n - (2(t+l-1) + 1)`:
 /// 1. (1, 2, 8) - 3 (ROBUST)
 /// 2. (1, 3, 8) - 1 (FAST)
-/// 3. (2, 2, 8) - 1 (PRIVATE) [currently implemented]
-/// The other configurations will need the packing and unpacking functions to be modified and reimplemented
+/// 3. (2, 2, 8) - 1 (PRIVATE) [default, see new]
+///
+/// Configurations where t + l is a power of two can be built with with_params, e.g FAST,
+/// or by name with from_preset.
+/// ROBUST will need the packing and unpacking functions to be modified and reimplemented
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct PackedSharingParams<F>
 where
@@ -36,10 +158,58 @@ where
 
 impl<F: FftField> PackedSharingParams<F> {
     /// Creates a new instance of PackedSharingParams with the given packing factor
+    /// Panics if l is not supported, see try_new
     pub fn new(l: usize) -> Self {
-        let n = l * 4;
-        let t = l;
-        debug_assert_eq!(n, 2 * (t + l));
+        Self::with_params(l, l)
+    }
+
+    /// Same as new, but returns an error if l is not a power of two
+    /// or if the field can not host a domain of n = 4l parties
+    pub fn try_new(l: usize) -> Result<Self, SharingError> {
+        Self::try_with_params(l, l)
+    }
+
+    /// Creates a new instance of PackedSharingParams with threshold t and packing factor l
+    /// for n = 2(t + l) parties, e.g with_params(1, 3) for FAST.
+    /// Panics if t and l are not supported, see try_with_params
+    pub fn with_params(t: usize, l: usize) -> Self {
+        Self::try_with_params(t, l).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Params of a named configuration for n = 4 base_l parties,
+    /// e.g from_preset(SharingPreset::Fast, 2) is (1, 3, 8).
+    /// ROBUST is not supported yet and always fails with UnsupportedPreset.
+    pub fn from_preset(
+        preset: SharingPreset,
+        base_l: usize,
+    ) -> Result<Self, SharingError> {
+        let (t, l) = match preset {
+            SharingPreset::Robust => {
+                return Err(SharingError::UnsupportedPreset { preset })
+            }
+            SharingPreset::Private => (base_l, base_l),
+            SharingPreset::Fast if base_l % 2 == 1 => {
+                return Err(SharingError::OddBase { base_l })
+            }
+            SharingPreset::Fast => (base_l / 2, 3 * base_l / 2),
+        };
+        Self::try_with_params(t, l)
+    }
+
+    /// Same as with_params, but returns an error if t + l is not a power of two
+    /// or if the field can not host a domain of n = 2(t + l) parties
+    pub fn try_with_params(t: usize, l: usize) -> Result<Self, SharingError> {
+        if !(t + l).is_power_of_two() {
+            return Err(SharingError::NotPowerOfTwo { t, l });
+        }
+        let n = 2 * (t + l);
+        // the share and secrets2 domains have size n, the largest of the three
+        if n.trailing_zeros() > F::TWO_ADICITY {
+            return Err(SharingError::DomainTooLarge {
+                n,
+                two_adicity: F::TWO_ADICITY,
+            });
+        }
 
         let share = Radix2EvaluationDomain::<F>::new(n).unwrap();
         let secret = Radix2EvaluationDomain::<F>::new(l + t)
@@ -55,35 +225,53 @@ impl<F: FftField> PackedSharingParams<F> {
         debug_assert_eq!(secret.size(), l + t);
         debug_assert_eq!(secret2.size(), 2 * (l + t));
 
-        PackedSharingParams {
+        Ok(PackedSharingParams {
             t,
             l,
             n,
             share,
             secret,
             secret2,
-        }
+        })
     }
 
     /// Deterministically packs secrets into shares
+    /// Same as pack_from_public, see there for when this is safe to use
     pub fn det_pack<T: DomainCoeff<F> + UniformRand>(
         &self,
         secrets: Vec<T>,
     ) -> Vec<T> {
-        debug_assert!(secrets.len() == self.l, "Secrets length mismatch");
+        self.pack_from_public(secrets)
+    }
 
+    /// Packs public values into shares without any randomness
+    /// The t random points of pack are replaced by zeros, so any l parties
+    /// learn the values. Only use this for data everyone may know, e.g
+    /// public inputs or constants, never for the witness or masks.
+    pub fn pack_from_public<T: DomainCoeff<F>>(
+        &self,
+        secrets: Vec<T>,
+    ) -> Vec<T> {
         let mut result = secrets;
+        self.pack_from_public_in_place(&mut result);
+        result
+    }
+
+    /// In place version of pack_from_public
+    pub fn pack_from_public_in_place<T: DomainCoeff<F>>(
+        &self,
+        secrets: &mut Vec<T>,
+    ) {
+        debug_assert!(secrets.len() == self.l, "Secrets length mismatch");
 
         // Resize the secrets with t zeros
-        result.resize(self.t, T::zero());
+        secrets.resize(self.l + self.t, T::zero());
 
         // interpolating on secrets domain
-        self.secret.ifft_in_place(&mut result);
+        self.secret.ifft_in_place(secrets);
 
         // evaluate on share domain
-        self.share.fft_in_place(&mut result);
-
-        result
+        self.share.fft_in_place(secrets);
     }
 
     /// Packs secrets into shares
@@ -103,24 +291,27 @@ impl<F: FftField> PackedSharingParams<F> {
         // interpolating on secrets domain
         self.secret.ifft_in_place(&mut result);
 
-        #[cfg(debug_assertions)]
-        {
-            // assert that all but first l+t elements are zero
-            for item in result.iter().skip(self.l + self.t) {
-                debug_assert!(
-                    item.is_zero(),
-                    "Pack found non zero coefficient: {:?}",
-                    result
-                );
-            }
-        }
-
         // evaluate on share domain
         self.share.fft_in_place(&mut result);
 
         result
     }
 
+    /// First non zero coefficient of degree above `degree` of the polynomial
+    /// interpolating the shares, if any
+    fn first_coefficient_above<T: DomainCoeff<F>>(
+        &self,
+        shares: &[T],
+        degree: usize,
+    ) -> Option<(usize, T)> {
+        self.share
+            .ifft(shares)
+            .into_iter()
+            .enumerate()
+            .skip(degree + 1)
+            .find(|(_, c)| !c.is_zero())
+    }
+
     /// Unpacks shares of degree t+l into secrets
     pub fn unpack<T: DomainCoeff<F>>(&self, shares: Vec<T>) -> Vec<T> {
         let mut result = shares;
@@ -159,10 +350,43 @@ impl<F: FftField> PackedSharingParams<F> {
         // evaluate on secrets domain
         self.secret2.fft_in_place(&mut result);
 
-        // drop alternate elements from shares array and only iterate till 2l as the rest of it is randomness
-        result = result[0..2 * self.l].iter().step_by(2).copied().collect();
+        self.secrets_from_secret2(result)
+    }
 
-        result
+    /// Checked version of unpack2, also verifies the degree in release builds
+    /// Returns the first non zero coefficient past degree 2(t+l)-1 if any
+    pub fn try_unpack2<T: DomainCoeff<F>>(
+        &self,
+        shares: Vec<T>,
+    ) -> Result<Vec<T>, UnpackError<T>> {
+        if shares.len() != self.n {
+            return Err(UnpackError::WrongLength {
+                expected: self.n,
+                got: shares.len(),
+            });
+        }
+
+        let mut result = shares;
+
+        // interpolating on share domain
+        self.share.ifft_in_place(&mut result);
+
+        if let Some((index, value)) = result
+            .iter()
+            .enumerate()
+            .skip(2 * (self.l + self.t) - 1)
+            .find(|(_, item)| !item.is_zero())
+        {
+            return Err(UnpackError::NonZeroCoefficient {
+                index,
+                value: *value,
+            });
+        }
+
+        // evaluate on secrets domain
+        self.secret2.fft_in_place(&mut result);
+
+        Ok(self.secrets_from_secret2(result))
     }
 
     /// Runs lagrange interpolation to unpack the secrets. Can be used when some shares are missing.
@@ -198,10 +422,15 @@ impl<F: FftField> PackedSharingParams<F> {
         // evaluate on secrets domain
         self.secret2.fft_in_place(&mut result);
 
-        // drop alternate elements from shares array and only iterate till 2l as the rest of it is randomness
-        result = result[0..2 * self.l].iter().step_by(2).copied().collect();
+        self.secrets_from_secret2(result)
+    }
 
-        result
+    /// Picks the l secrets out of the evaluations on the secrets2 domain
+    /// The secrets domain is every (secret2 / secret)-th point of secrets2,
+    /// only its first l points hold secrets, the remaining t are randomness
+    fn secrets_from_secret2<T: DomainCoeff<F>>(&self, evals: Vec<T>) -> Vec<T> {
+        let stride = self.secret2.size() / self.secret.size();
+        evals.into_iter().step_by(stride).take(self.l).collect()
     }
 
     /// A default implementation of unpacking when there may be missing shares
@@ -219,6 +448,179 @@ impl<F: FftField> PackedSharingParams<F> {
             self.lagrange_unpack(shares, parties)
         }
     }
+
+    /// unpack_missing_shares_checked of many sharings received from the same
+    /// parties, in parallel if enabled. shares holds one row per party, the
+    /// c-th sharing is made of the c-th share of every row. Builds the
+    /// sharings one at a time instead of transposing all the shares.
+    pub fn unpack_missing_shares_many<T: DomainCoeff<F>>(
+        &self,
+        shares: &[Vec<T>],
+        parties: &[u32],
+        degree: usize,
+    ) -> Result<Vec<Vec<T>>, UnpackError<T>> {
+        let cols = shares.first().map_or(0, Vec::len);
+        cfg_into_iter!(0..cols)
+            .map(|c| {
+                let column =
+                    shares.iter().map(|row| row[c]).collect::<Vec<_>>();
+                self.unpack_missing_shares_checked(&column, parties, degree)
+            })
+            .collect()
+    }
+
+    /// Same as unpack_missing_shares for shares of any type, but checks that
+    /// they lie on a polynomial of the given degree. Shares off that degree,
+    /// or fewer than n of them, go through robust_reconstruct, which corrects
+    /// the lying parties it can and fails otherwise.
+    pub fn unpack_missing_shares_checked<T: DomainCoeff<F>>(
+        &self,
+        shares: &[T],
+        parties: &[u32],
+        degree: usize,
+    ) -> Result<Vec<T>, UnpackError<T>> {
+        debug_assert_eq!(shares.len(), parties.len());
+        if shares.len() == self.n
+            && self.first_coefficient_above(shares, degree).is_none()
+        {
+            return Ok(self.unpack2(shares.to_vec()));
+        }
+        self.robust_reconstruct(shares, parties, degree)
+            .map(|(secrets, _)| secrets)
+    }
+
+    /// Same as unpack_missing_shares_checked for field elements, but when all n
+    /// shares are present and they do not lie on a polynomial of the given
+    /// degree, corrects them with Gao's decoder instead of a subset search.
+    /// Up to (n - degree - 1) / 2 corrupted shares can be corrected, e.g
+    /// l of them for fresh shares of degree t+l-1. Also returns the corrected parties.
+    pub fn unpack_missing_shares_robust(
+        &self,
+        shares: &[F],
+        parties: &[u32],
+        degree: usize,
+    ) -> Result<(Vec<F>, Vec<u32>), UnpackError<F>> {
+        debug_assert_eq!(shares.len(), parties.len());
+        assert!(degree < 2 * (self.l + self.t), "degree too large to unpack");
+        if shares.len() != self.n {
+            let (secrets, consistent) =
+                self.robust_reconstruct(shares, parties, degree)?;
+            let corrected = parties
+                .iter()
+                .filter(|party| !consistent.contains(party))
+                .copied()
+                .collect();
+            return Ok((secrets, corrected));
+        }
+
+        // order the shares by party, as the share domain expects
+        let mut received = vec![F::zero(); self.n];
+        for (share, party) in shares.iter().zip(parties) {
+            received[*party as usize] = *share;
+        }
+
+        // cheap consistency check: the shares interpolate to a low degree polynomial
+        let coeffs = self.share.ifft(&received);
+        let Some((index, value)) = coeffs
+            .iter()
+            .enumerate()
+            .skip(degree + 1)
+            .find(|(_, c)| !c.is_zero())
+        else {
+            return Ok((self.unpack2(received), Vec::new()));
+        };
+
+        let message = self
+            .try_decode_to_message(received.clone(), self.n, degree + 1)
+            .ok_or(UnpackError::NonZeroCoefficient {
+                index,
+                value: *value,
+            })?;
+        let codeword = self.share.fft(&message.coeffs);
+
+        let corrected = (0..self.n as u32)
+            .filter(|i| codeword[*i as usize] != received[*i as usize])
+            .collect::<Vec<_>>();
+        if corrected.len() > (self.n - degree - 1) / 2 {
+            return Err(UnpackError::NonZeroCoefficient {
+                index,
+                value: *value,
+            });
+        }
+
+        Ok((self.unpack2(codeword), corrected))
+    }
+
+    /// Error-correcting counterpart of lagrange_unpack: works with missing
+    /// shares and with any share type, e.g the group elements of d_msm.
+    /// Tries the subsets of degree + 1 of the k shares in turn, and returns the
+    /// secrets of the first polynomial of the given degree that agrees with all
+    /// but (k - degree - 1) / 2 of them, so that many lying parties are
+    /// tolerated. Also returns the parties whose share agrees with it.
+    /// The number of subsets tried grows quickly with the number of parties
+    /// and of liars, prefer unpack_missing_shares_robust for field elements.
+    pub fn robust_reconstruct<T: DomainCoeff<F>>(
+        &self,
+        shares: &[T],
+        parties: &[u32],
+        degree: usize,
+    ) -> Result<(Vec<T>, Vec<u32>), UnpackError<T>> {
+        debug_assert_eq!(shares.len(), parties.len());
+        assert!(degree < 2 * (self.l + self.t), "degree too large to unpack");
+        let received = shares.len();
+        if received <= degree {
+            return Err(UnpackError::Inconsistent {
+                needed: degree + 1,
+                received,
+            });
+        }
+        let needed = received - (received - degree - 1) / 2;
+
+        let share_elements = self.share.elements().collect::<Vec<F>>();
+        let xs = parties
+            .iter()
+            .map(|party| share_elements[*party as usize])
+            .collect::<Vec<_>>();
+
+        let mut subset = (0..=degree).collect::<Vec<_>>();
+        loop {
+            let mut coeffs = lagrange_interpolate(
+                &subset.iter().map(|i| xs[*i]).collect::<Vec<_>>(),
+                &subset.iter().map(|i| shares[*i]).collect::<Vec<_>>(),
+            );
+            coeffs.resize(self.n, T::zero());
+            let codeword = self.share.fft(&coeffs);
+            let consistent = parties
+                .iter()
+                .zip(shares)
+                .filter(|(party, share)| codeword[**party as usize] == **share)
+                .map(|(party, _)| *party)
+                .collect::<Vec<_>>();
+
+            if consistent.len() >= needed {
+                // evaluate on secrets domain
+                self.secret2.fft_in_place(&mut coeffs);
+                return Ok((self.secrets_from_secret2(coeffs), consistent));
+            }
+            if !next_subset(&mut subset, received) {
+                return Err(UnpackError::Inconsistent { needed, received });
+            }
+        }
+    }
+}
+
+/// Advances subset, sorted indices into 0..len, to the next subset of the
+/// same size in lexicographic order. Returns false after the last one.
+fn next_subset(subset: &mut [usize], len: usize) -> bool {
+    let size = subset.len();
+    let Some(i) = (0..size).rev().find(|i| subset[*i] < len - size + i) else {
+        return false;
+    };
+    subset[i] += 1;
+    for j in i + 1..size {
+        subset[j] = subset[j - 1] + 1;
+    }
+    true
 }
 
 // Tests
@@ -246,6 +648,58 @@ mod tests {
         assert_eq!(pp.secret2.size(), 2 * (L + T));
     }
 
+    #[test]
+    fn test_try_new() {
+        assert_eq!(
+            PackedSharingParams::<F>::try_new(L),
+            Ok(PackedSharingParams::new(L))
+        );
+        assert_eq!(
+            PackedSharingParams::<F>::try_new(3),
+            Err(SharingError::NotPowerOfTwo { t: 3, l: 3 })
+        );
+
+        // n = 4l = 2^(TWO_ADICITY + 1) does not fit the field
+        let l = 1 << (F::TWO_ADICITY - 1);
+        let err = PackedSharingParams::<F>::try_new(l).unwrap_err();
+        assert_eq!(
+            err,
+            SharingError::DomainTooLarge {
+                n: 4 * l,
+                two_adicity: F::TWO_ADICITY
+            }
+        );
+        assert!(err
+            .to_string()
+            .contains(&format!("2^{}", F::TWO_ADICITY + 1)));
+    }
+
+    #[test]
+    fn test_from_preset() {
+        let preset = |name| SharingPreset::try_from(name).unwrap();
+        assert_eq!(
+            PackedSharingParams::<F>::from_preset(preset("private"), L),
+            Ok(PackedSharingParams::new(L))
+        );
+        let fast =
+            PackedSharingParams::<F>::from_preset(preset("FAST"), L).unwrap();
+        assert_eq!((fast.t, fast.l, fast.n), (1, 3, 8));
+        assert_eq!(
+            PackedSharingParams::<F>::from_preset(preset("robust"), L),
+            Err(SharingError::UnsupportedPreset {
+                preset: SharingPreset::Robust
+            })
+        );
+        assert_eq!(
+            PackedSharingParams::<F>::from_preset(SharingPreset::Fast, 1),
+            Err(SharingError::OddBase { base_l: 1 })
+        );
+        assert_eq!(
+            SharingPreset::try_from("quick"),
+            Err(UnknownPreset("quick".to_string()))
+        );
+    }
+
     #[test]
     fn test_packing() {
         let pp = PackedSharingParams::<F>::new(L);
@@ -271,6 +725,25 @@ mod tests {
         assert_eq!(expected, lagrange_secrets);
     }
 
+    #[test]
+    fn test_pack_degree() {
+        let pp = PackedSharingParams::<F>::new(L);
+
+        let rng = &mut ark_std::test_rng();
+        let secrets = (0..L).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let shares = pp.pack(secrets.clone(), rng);
+        assert_eq!(secrets, pp.unpack(shares.clone()));
+
+        // a single wrong share raises the degree to n-1
+        assert_eq!(pp.first_coefficient_above(&shares, pp.t + pp.l - 1), None);
+        let mut bad_shares = shares;
+        bad_shares[1] += F::from(1u32);
+        assert!(matches!(
+            pp.first_coefficient_above(&bad_shares, pp.t + pp.l - 1),
+            Some((index, _)) if index >= pp.t + pp.l
+        ));
+    }
+
     #[test]
     fn test_det_packing() {
         let pp = PackedSharingParams::<F>::new(L);
@@ -287,6 +760,54 @@ mod tests {
         assert_eq!(expected, secrets);
     }
 
+    #[test]
+    fn test_pack_from_public() {
+        let pp = PackedSharingParams::<F>::new(L);
+
+        let rng = &mut ark_std::test_rng();
+        let secrets: [F; L] = UniformRand::rand(rng);
+        let secrets = secrets.to_vec();
+
+        let shares = pp.pack_from_public(secrets.clone());
+        assert_eq!(shares, pp.det_pack(secrets.clone()));
+        assert_eq!(secrets, pp.unpack(shares.clone()));
+        // a public packing has degree < l+t, so it also fits unpack2
+        assert_eq!(secrets, pp.unpack2(shares.clone()));
+
+        let mut in_place = secrets.clone();
+        pp.pack_from_public_in_place(&mut in_place);
+        assert_eq!(shares, in_place);
+    }
+
+    #[test]
+    fn test_unpack_missing_shares_many() {
+        let pp = PackedSharingParams::<F>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let secrets = (0..3 * L).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let columns = secrets
+            .chunks(L)
+            .map(|chunk| pp.pack(chunk.to_vec(), rng))
+            .collect::<Vec<_>>();
+        // the shares of parties 1..n, one row per party
+        let mut rows = (1..pp.n)
+            .map(|i| columns.iter().map(|column| column[i]).collect())
+            .collect::<Vec<Vec<F>>>();
+        let parties = (1..pp.n as u32).collect::<Vec<_>>();
+        let degree = T + L - 1;
+
+        let unpacked = pp
+            .unpack_missing_shares_many(&rows, &parties, degree)
+            .unwrap();
+        assert_eq!(unpacked.concat(), secrets);
+
+        // n - 1 shares of degree t+l-1 correct one liar, but not two
+        rows[0][1] += F::from(1u64);
+        rows[1][1] += F::from(1u64);
+        assert!(pp
+            .unpack_missing_shares_many(&rows, &parties, degree)
+            .is_err());
+    }
+
     #[test]
     fn test_multiplication() {
         let pp = PackedSharingParams::<F>::new(L);
@@ -310,6 +831,161 @@ mod tests {
         assert_eq!(expected, lagrange_secrets);
     }
 
+    #[test]
+    fn test_fast_config() {
+        let pp = PackedSharingParams::<F>::with_params(1, 3);
+        assert_eq!((pp.t, pp.l, pp.n), (1, 3, 8));
+
+        let rng = &mut ark_std::test_rng();
+        let secrets = (0..pp.l).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let expected: Vec<F> = secrets.iter().map(|x| (*x) * (*x)).collect();
+
+        let shares = pp.pack(secrets.clone(), rng);
+        assert_eq!(secrets, pp.unpack(shares.clone()));
+
+        let mul_shares: Vec<F> = shares.iter().map(|x| (*x) * (*x)).collect();
+        assert_eq!(expected, pp.unpack2(mul_shares.clone()));
+
+        // can tolerate 1 party dropping out
+        let parties = (1..pp.n as u32).collect::<Vec<_>>();
+        assert_eq!(expected, pp.lagrange_unpack(&mul_shares[1..], &parties));
+    }
+
+    #[test]
+    fn test_try_unpack2() {
+        let pp = PackedSharingParams::<F>::new(L);
+
+        let rng = &mut ark_std::test_rng();
+        let secrets: [F; L] = UniformRand::rand(rng);
+        let secrets = secrets.to_vec();
+        let expected: Vec<F> = secrets.iter().map(|x| (*x) * (*x)).collect();
+
+        let shares = pp.pack(secrets, rng);
+        let mul_shares: Vec<F> = shares.iter().map(|x| (*x) * (*x)).collect();
+        assert_eq!(Ok(expected), pp.try_unpack2(mul_shares.clone()));
+
+        // tampering with a single share raises the degree
+        let mut bad_shares = mul_shares.clone();
+        bad_shares[3] += F::from(1u32);
+        assert!(matches!(
+            pp.try_unpack2(bad_shares),
+            Err(UnpackError::NonZeroCoefficient { index, .. })
+                if index == 2 * (pp.l + pp.t) - 1
+        ));
+
+        assert_eq!(
+            Err(UnpackError::WrongLength {
+                expected: pp.n,
+                got: pp.n - 1
+            }),
+            pp.try_unpack2(mul_shares[..pp.n - 1].to_vec())
+        );
+    }
+
+    #[test]
+    fn test_unpack_missing_shares_robust() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let parties = (0..pp.n as u32).collect::<Vec<_>>();
+        let secrets = (0..pp.l).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let mut shares = pp.pack(secrets.clone(), rng);
+
+        let (unpacked, corrected) = pp
+            .unpack_missing_shares_robust(&shares, &parties, pp.t + pp.l - 1)
+            .unwrap();
+        assert_eq!(unpacked[..pp.l], secrets[..]);
+        assert!(corrected.is_empty());
+
+        shares[5] += F::from(1u32);
+        let (unpacked, corrected) = pp
+            .unpack_missing_shares_robust(&shares, &parties, pp.t + pp.l - 1)
+            .unwrap();
+        assert_eq!(unpacked[..pp.l], secrets[..]);
+        assert_eq!(corrected, vec![5]);
+
+        // without the share of party 0, the subset search finds the liar
+        let (unpacked, corrected) = pp
+            .unpack_missing_shares_robust(
+                &shares[1..],
+                &parties[1..],
+                pp.t + pp.l - 1,
+            )
+            .unwrap();
+        assert_eq!(unpacked[..pp.l], secrets[..]);
+        assert_eq!(corrected, vec![5]);
+    }
+
+    #[test]
+    fn test_unpack_missing_shares_checked() {
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let parties = (0..pp.n as u32).collect::<Vec<_>>();
+        let secrets = (0..pp.l).map(|_| F::rand(rng)).collect::<Vec<_>>();
+        let honest = pp.pack(secrets.clone(), rng);
+        let mut shares = honest.clone();
+
+        // 8 shares of degree 3 tolerate 2 liars
+        let degree = pp.t + pp.l - 1;
+        shares[3] += F::from(1u32);
+        assert_eq!(
+            pp.unpack_missing_shares_checked(&shares, &parties, degree),
+            Ok(secrets.clone())
+        );
+
+        // products have degree 6, so 8 shares only detect a liar
+        let degree = 2 * (pp.t + pp.l - 1);
+        let mut products = honest.iter().map(|x| *x * x).collect::<Vec<_>>();
+        let expected = secrets.iter().map(|x| *x * x).collect::<Vec<_>>();
+        assert_eq!(
+            pp.unpack_missing_shares_checked(&products, &parties, degree),
+            Ok(expected)
+        );
+        products[3] += F::from(1u32);
+        assert_eq!(
+            pp.unpack_missing_shares_checked(&products, &parties, degree),
+            Err(UnpackError::Inconsistent {
+                needed: 8,
+                received: 8
+            })
+        );
+    }
+
+    #[test]
+    fn test_robust_reconstruct() {
+        use ark_bls12_377::G1Projective as G1;
+
+        let rng = &mut ark_std::test_rng();
+        let pp = PackedSharingParams::<F>::new(L);
+        let degree = pp.t + pp.l - 1;
+        let parties = (0..pp.n as u32).collect::<Vec<_>>();
+        let secrets = (0..pp.l).map(|_| G1::rand(rng)).collect::<Vec<_>>();
+        let honest = pp.pack(secrets.clone(), rng);
+        let mut shares = honest.clone();
+
+        // 8 shares of degree 3 tolerate 2 liars
+        shares[1] += G1::rand(rng);
+        shares[6] += G1::rand(rng);
+        let (unpacked, consistent) =
+            pp.robust_reconstruct(&shares, &parties, degree).unwrap();
+        assert_eq!(unpacked, secrets);
+        assert_eq!(consistent, vec![0, 2, 3, 4, 5, 7]);
+
+        // without party 7, 7 shares only tolerate 1 liar
+        assert_eq!(
+            pp.robust_reconstruct(&shares[..7], &parties[..7], degree),
+            Err(UnpackError::Inconsistent {
+                needed: 6,
+                received: 7
+            })
+        );
+        shares[6] = honest[6];
+        let (unpacked, consistent) = pp
+            .robust_reconstruct(&shares[..7], &parties[..7], degree)
+            .unwrap();
+        assert_eq!(unpacked, secrets);
+        assert_eq!(consistent, vec![0, 2, 3, 4, 5, 6]);
+    }
+
     #[test]
     fn test_eval_interpolate() {
         let degree = 32u32;